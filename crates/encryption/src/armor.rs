@@ -0,0 +1,161 @@
+//! ASCII armor for encrypted data, so it can be pasted in a chat or an email
+//!
+//! The data is base64 encoded in lines of 64 characters between BEGIN and END lines,
+//! followed by a CRC24 checksum like OpenPGP armor:
+//!
+//! ```text
+//! -----BEGIN NCRYPT MESSAGE-----
+//! bkNyeXB0MQBQAAAALAAAAAAAAABN...
+//! =njUN
+//! -----END NCRYPT MESSAGE-----
+//! ```
+
+use base64::{engine::general_purpose::STANDARD, Engine};
+
+use anyhow::anyhow;
+
+pub const BEGIN: &str = "-----BEGIN NCRYPT MESSAGE-----";
+pub const END: &str = "-----END NCRYPT MESSAGE-----";
+
+/// Base64 characters per line
+const LINE_LENGTH: usize = 64;
+
+const CRC24_INIT: u32 = 0xB704CE;
+const CRC24_POLY: u32 = 0x1864CFB;
+
+/// Encode the data as armored text
+pub fn armor(data: &[u8]) -> String {
+    let encoded = STANDARD.encode(data);
+    let checksum = STANDARD.encode(&crc24(data).to_be_bytes()[1..]);
+
+    let mut armored = String::with_capacity(encoded.len() + encoded.len() / LINE_LENGTH + BEGIN.len() + END.len() + 16);
+    armored.push_str(BEGIN);
+    armored.push('\n');
+
+    for line in encoded.as_bytes().chunks(LINE_LENGTH) {
+        // base64 output is ASCII
+        armored.push_str(std::str::from_utf8(line).unwrap_or_default());
+        armored.push('\n');
+    }
+
+    armored.push('=');
+    armored.push_str(&checksum);
+    armored.push('\n');
+    armored.push_str(END);
+    armored.push('\n');
+
+    armored
+}
+
+/// Decode armored text back to the data
+///
+/// Text around the BEGIN and END lines is ignored, as is whitespace and `>` quoting
+/// that mail clients add to replies. The checksum must match.
+pub fn dearmor(text: &str) -> Result<Vec<u8>, anyhow::Error> {
+    let mut lines = text.lines().map(|line| line.trim_start_matches(['>', ' ', '\t']).trim());
+
+    lines
+        .by_ref()
+        .find(|line| *line == BEGIN)
+        .ok_or(anyhow!("BEGIN line not found, not an nCrypt message?"))?;
+
+    let mut encoded = String::new();
+    let mut checksum = None;
+    let mut ended = false;
+
+    for line in lines {
+        if line == END {
+            ended = true;
+            break;
+        }
+
+        if let Some(crc) = line.strip_prefix('=') {
+            checksum = Some(crc.to_string());
+        } else if checksum.is_some() {
+            return Err(anyhow!("Unexpected text after the checksum"));
+        } else {
+            encoded.push_str(line);
+        }
+    }
+
+    if !ended {
+        return Err(anyhow!("END line not found, the message is truncated"));
+    }
+
+    let checksum = checksum.ok_or(anyhow!("Checksum not found, the message is truncated"))?;
+
+    let data = STANDARD
+        .decode(encoded.as_bytes())
+        .map_err(|e| anyhow!("Invalid base64 {}", e))?;
+
+    let checksum = STANDARD
+        .decode(checksum.as_bytes())
+        .map_err(|e| anyhow!("Invalid checksum {}", e))?;
+
+    if checksum.len() != 3 || checksum[..] != crc24(&data).to_be_bytes()[1..] {
+        return Err(anyhow!("Checksum mismatch, the message was damaged while copying"));
+    }
+
+    Ok(data)
+}
+
+/// Whether the text contains an armored message
+pub fn is_armored(text: &str) -> bool {
+    text.lines().any(|line| line.trim_start_matches(['>', ' ', '\t']).trim() == BEGIN)
+}
+
+/// The CRC24 of OpenPGP, RFC 4880 section 6.1
+fn crc24(data: &[u8]) -> u32 {
+    let mut crc = CRC24_INIT;
+
+    for byte in data {
+        crc ^= (*byte as u32) << 16;
+
+        for _ in 0..8 {
+            crc <<= 1;
+            if crc & 0x1000000 != 0 {
+                crc ^= CRC24_POLY;
+            }
+        }
+    }
+
+    crc & 0xFFFFFF
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn armor_round_trip() {
+        for len in [0, 1, 47, 48, 49, 1000] {
+            let data: Vec<u8> = (0..len).map(|i| (i * 31 % 256) as u8).collect();
+            let armored = armor(&data);
+
+            assert!(armored.lines().all(|line| line.len() <= LINE_LENGTH || line == BEGIN || line == END));
+            assert_eq!(dearmor(&armored).unwrap(), data);
+
+            // Quoted in a reply, with text around it
+            let quoted: String = armored.lines().map(|line| format!("> {}\r\n", line)).collect();
+            let quoted = format!("Here it is:\n{}\nThanks", quoted);
+            assert!(is_armored(&quoted));
+            assert_eq!(dearmor(&quoted).unwrap(), data);
+        }
+    }
+
+    #[test]
+    fn detects_damage() {
+        let armored = armor(b"some encrypted data that is long enough");
+
+        // Known CRC24 check value
+        assert_eq!(crc24(b"123456789"), 0x21CF02);
+
+        let damaged = armored.replacen("c29tZ", "c29uZ", 1);
+        assert!(dearmor(&damaged).is_err());
+
+        let truncated: String = armored.lines().take(2).collect::<Vec<_>>().join("\n");
+        assert!(dearmor(&truncated).is_err());
+
+        assert!(dearmor("no message here").is_err());
+    }
+}
@@ -0,0 +1,190 @@
+use sysinfo::{MemoryRefreshKind, RefreshKind, System};
+use std::time::{Duration, Instant};
+
+use super::Argon2Params;
+use anyhow::anyhow;
+
+/// Memory cost used to probe the speed of this machine (32 MB)
+const PROBE_M_COST: u32 = 32_768;
+
+/// The lowest memory cost calibration will pick (8 MB)
+pub const MIN_M_COST: u32 = 8_192;
+
+/// The lowest iteration count calibration will pick
+pub const MIN_T_COST: u32 = 3;
+
+/// The highest parallelism calibration will pick
+pub const MAX_P_COST: u32 = 4;
+
+/// Argon2 runs twice to unlock a file, once for the password and once for the username
+pub const KDF_RUNS: u32 = 2;
+
+/// Measured Argon2 throughput of this machine
+///
+/// Argon2 time grows linearly with `m_cost * t_cost`,
+/// so a single measurement is enough to estimate the time of any other parameters.
+#[derive(Clone, Copy, Debug)]
+pub struct Benchmark {
+    /// Nanoseconds it takes to process one 1 KB block for one pass
+    nanos_per_block: f64,
+}
+
+impl Benchmark {
+    /// Benchmark this machine using a small probe
+    pub fn run() -> Result<Self, anyhow::Error> {
+        let params = Argon2Params::new(PROBE_M_COST, 1, 1, 64);
+        Self::from_params(&params)
+    }
+
+    /// Benchmark this machine by hashing once with the given parameters
+    pub fn from_params(params: &Argon2Params) -> Result<Self, anyhow::Error> {
+        let elapsed = measure(params)?;
+        let blocks = params.m_cost as f64 * params.t_cost as f64;
+
+        Ok(Self {
+            nanos_per_block: elapsed.as_nanos() as f64 / blocks,
+        })
+    }
+
+    /// Estimate how long it takes to unlock a file encrypted with the given parameters
+    pub fn estimate(&self, params: &Argon2Params) -> Duration {
+        let blocks = params.m_cost as f64 * params.t_cost as f64;
+        Duration::from_nanos((self.nanos_per_block * blocks) as u64) * KDF_RUNS
+    }
+
+    /// Time this machine needs to try one password, an attacker who knows the username
+    /// only runs the password KDF for every guess
+    pub fn guess_time(&self, params: &Argon2Params) -> Duration {
+        self.estimate(params) / KDF_RUNS
+    }
+}
+
+/// The result of [calibrate]
+#[derive(Clone, Debug)]
+pub struct Calibration {
+    pub params: Argon2Params,
+
+    /// The measured time it takes to unlock a file with `params`
+    pub duration: Duration,
+
+    pub benchmark: Benchmark,
+}
+
+/// Pick Argon2 parameters that take about `target` to unlock a file on this machine
+///
+/// Memory is preferred over iterations, the memory cost is capped to the lowest of `max_m_cost`
+/// and half of the available RAM, iterations are then raised until the target is reached.
+///
+/// ### Arguments
+///
+/// - `target` - The desired unlock time
+/// - `max_m_cost` - The maximum memory cost in kB
+pub fn calibrate(target: Duration, max_m_cost: u32) -> Result<Calibration, anyhow::Error> {
+    if target.is_zero() {
+        return Err(anyhow!("Target time must be greater than zero"));
+    }
+
+    let benchmark = Benchmark::run()?;
+    let p_cost = default_p_cost();
+
+    let m_cap = max_m_cost.min(memory_cap()).max(MIN_M_COST);
+    let target = target / KDF_RUNS;
+    let target_blocks = target.as_nanos() as f64 / benchmark.nanos_per_block;
+
+    // Spend the budget on memory first using the minimum iterations
+    let m_cost = (target_blocks / MIN_T_COST as f64).clamp(MIN_M_COST as f64, m_cap as f64) as u32;
+    let t_cost = (target_blocks / m_cost as f64).round().max(MIN_T_COST as f64) as u32;
+
+    let mut params = Argon2Params::new(m_cost, t_cost, p_cost, 64);
+    let mut duration = measure(&params)?;
+
+    // The probe may be off for bigger memory sizes, correct the iterations once
+    let ratio = target.as_secs_f64() / duration.as_secs_f64();
+    if !(0.8..=1.2).contains(&ratio) {
+        let t_cost = (params.t_cost as f64 * ratio).round().max(MIN_T_COST as f64) as u32;
+
+        if t_cost != params.t_cost {
+            params.t_cost = t_cost;
+            duration = measure(&params)?;
+        }
+    }
+
+    let blocks = params.m_cost as f64 * params.t_cost as f64;
+    let benchmark = Benchmark {
+        nanos_per_block: duration.as_nanos() as f64 / blocks,
+    };
+
+    Ok(Calibration {
+        params,
+        duration: duration * KDF_RUNS,
+        benchmark,
+    })
+}
+
+/// Available physical memory in kB
+pub fn available_memory() -> u64 {
+    let system = System::new_with_specifics(
+        RefreshKind::new().with_memory(MemoryRefreshKind::new().with_ram()),
+    );
+    system.available_memory() / 1024
+}
+
+/// Total physical memory in kB
+pub fn total_memory() -> u64 {
+    let system = System::new_with_specifics(
+        RefreshKind::new().with_memory(MemoryRefreshKind::new().with_ram()),
+    );
+    system.total_memory() / 1024
+}
+
+/// The highest memory cost calibration will pick, half of the available memory
+fn memory_cap() -> u32 {
+    (available_memory() / 2).min(u32::MAX as u64) as u32
+}
+
+fn default_p_cost() -> u32 {
+    std::thread::available_parallelism()
+        .map(|n| n.get() as u32)
+        .unwrap_or(1)
+        .min(MAX_P_COST)
+}
+
+/// Time a single key derivation with the given parameters
+fn measure(params: &Argon2Params) -> Result<Duration, anyhow::Error> {
+    let argon2 = params.argon2()?;
+
+    let mut output = vec![0u8; params.hash_length as usize];
+    let start = Instant::now();
+
+    argon2
+        .hash_password_into(b"calibration password", b"calibration salt", &mut output)
+        .map_err(|e| anyhow!("Failed to hash password {:?}", e))?;
+
+    Ok(start.elapsed())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn estimate_scales_with_cost() {
+        let benchmark = Benchmark { nanos_per_block: 1_000.0 };
+
+        let params = Argon2Params::new(1_000, 2, 1, 64);
+        assert_eq!(benchmark.estimate(&params), Duration::from_millis(2) * KDF_RUNS);
+
+        let params = Argon2Params::new(2_000, 2, 1, 64);
+        assert_eq!(benchmark.estimate(&params), Duration::from_millis(4) * KDF_RUNS);
+    }
+
+    #[test]
+    fn calibrate_respects_memory_cap() {
+        let calibration = calibrate(Duration::from_millis(200), 16_384).expect("Failed to calibrate");
+
+        assert!(calibration.params.m_cost <= 16_384);
+        assert!(calibration.params.m_cost >= MIN_M_COST);
+        assert!(calibration.params.t_cost >= MIN_T_COST);
+        assert!(calibration.params.p_cost >= 1 && calibration.params.p_cost <= MAX_P_COST);
+    }
+}
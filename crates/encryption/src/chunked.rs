@@ -0,0 +1,330 @@
+//! XChaCha20Poly1305 processed in chunks
+//!
+//! Produces exactly the same output as the one-shot `XChaCha20Poly1305` AEAD,
+//! so files stay compatible, but lets us report progress and cancel between chunks.
+//!
+//! This is only a thin wrapper over the streaming `XChaCha20` and `Poly1305` of the RustCrypto crates,
+//! composed like RFC 8439 section 2.8 with the extended nonce of draft-irtf-cfrg-xchacha:
+//! the first keystream block gives the one-time Poly1305 key, the data is encrypted from the second block,
+//! and the tag covers the padded AAD, the padded ciphertext and both lengths.
+
+use chacha20::{
+    cipher::{KeyIvInit, StreamCipher, StreamCipherSeek},
+    XChaCha20,
+};
+use chacha20poly1305::Tag;
+use poly1305::{
+    universal_hash::{generic_array::GenericArray, KeyInit, UniversalHash},
+    Poly1305,
+};
+use zeroize::Zeroize;
+
+use super::progress::{CancelToken, Progress};
+use super::secret::SecretBytes;
+use anyhow::anyhow;
+
+/// Bytes processed between progress reports, a multiple of the ChaCha20 and Poly1305 block sizes
+const CHUNK_SIZE: usize = 1024 * 1024;
+
+/// Size of the Poly1305 tag appended to the encrypted data
+pub const TAG_SIZE: usize = 16;
+
+/// The keystream and the tag being computed, the data is fed to both chunk by chunk
+///
+/// Every chunk but the last must be a multiple of 64 bytes, so the keystream and the padding line up
+struct Cipher {
+    cipher: XChaCha20,
+    mac: Poly1305,
+}
+
+impl Cipher {
+    fn new(key: &SecretBytes, nonce: &[u8], aad: &[u8]) -> Result<Self, anyhow::Error> {
+        if key.len() < 32 {
+            return Err(anyhow!("Hash length must be at least 32 bytes"));
+        }
+
+        if nonce.len() != 24 {
+            return Err(anyhow!("Invalid nonce length, invalid file format?"));
+        }
+
+        let mut cipher = XChaCha20::new(
+            GenericArray::from_slice(&key.expose()[..32]),
+            GenericArray::from_slice(nonce),
+        );
+
+        // The first keystream block gives the Poly1305 key
+        let mut mac_key = poly1305::Key::default();
+        cipher.apply_keystream(&mut mac_key);
+
+        let mut mac = Poly1305::new(&mac_key);
+        mac_key.zeroize();
+
+        cipher.seek(64u64);
+        mac.update_padded(aad);
+
+        Ok(Self { cipher, mac })
+    }
+
+    /// Feed the lengths block, the tag is what the returned MAC computes
+    fn finalize(mut self, aad_len: usize, data_len: usize) -> Poly1305 {
+        let mut block = GenericArray::default();
+        block[..8].copy_from_slice(&(aad_len as u64).to_le_bytes());
+        block[8..].copy_from_slice(&(data_len as u64).to_le_bytes());
+        self.mac.update(&[block]);
+        self.mac
+    }
+}
+
+/// Encrypt `data` in place and return the tag
+pub fn encrypt_in_place(
+    key: &SecretBytes,
+    nonce: &[u8],
+    aad: &[u8],
+    data: &mut [u8],
+    progress: &mut dyn FnMut(Progress),
+    cancel: &CancelToken,
+) -> Result<Tag, anyhow::Error> {
+    let mut cipher = Cipher::new(key, nonce, aad)?;
+    let total = data.len() as u64;
+    let mut processed = 0;
+
+    for chunk in data.chunks_mut(CHUNK_SIZE) {
+        cancel.check()?;
+
+        cipher.cipher.apply_keystream(chunk);
+        cipher.mac.update_padded(chunk);
+
+        processed += chunk.len() as u64;
+        progress(Progress::Payload { processed, total });
+    }
+
+    Ok(cipher.finalize(aad.len(), data.len()).finalize())
+}
+
+/// Decrypt `data` (encrypted data followed by the tag) into locked memory
+///
+/// Nothing is returned unless the tag is valid, on failure the partial output is zeroized.
+pub fn decrypt(
+    key: &SecretBytes,
+    nonce: &[u8],
+    aad: &[u8],
+    data: &[u8],
+    progress: &mut dyn FnMut(Progress),
+    cancel: &CancelToken,
+) -> Result<SecretBytes, anyhow::Error> {
+    if data.len() < TAG_SIZE {
+        return Err(anyhow!("Encrypted data is truncated, invalid file format?"));
+    }
+
+    let (encrypted, tag) = data.split_at(data.len() - TAG_SIZE);
+
+    let mut cipher = Cipher::new(key, nonce, aad)?;
+    let mut decrypted = SecretBytes::with_capacity(encrypted.len());
+    let total = encrypted.len() as u64;
+    let mut processed = 0;
+
+    for chunk in encrypted.chunks(CHUNK_SIZE) {
+        cancel.check()?;
+
+        cipher.mac.update_padded(chunk);
+
+        let start = decrypted.len();
+        decrypted.extend_from_slice(chunk);
+        cipher.cipher.apply_keystream(&mut decrypted.expose_mut()[start..]);
+
+        processed += chunk.len() as u64;
+        progress(Progress::Payload { processed, total });
+    }
+
+    cipher
+        .finalize(aad.len(), encrypted.len())
+        .verify(Tag::from_slice(tag))
+        .map_err(|e| anyhow!("Failed to decrypt data {:?}", e))?;
+
+    Ok(decrypted)
+}
+
+/// Check the tag of `data` (encrypted data followed by the tag) without decrypting anything
+pub fn verify(
+    key: &SecretBytes,
+    nonce: &[u8],
+    aad: &[u8],
+    data: &[u8],
+    progress: &mut dyn FnMut(Progress),
+    cancel: &CancelToken,
+) -> Result<(), anyhow::Error> {
+    if data.len() < TAG_SIZE {
+        return Err(anyhow!("Encrypted data is truncated, invalid file format?"));
+    }
+
+    let (encrypted, tag) = data.split_at(data.len() - TAG_SIZE);
+
+    let mut cipher = Cipher::new(key, nonce, aad)?;
+    let total = encrypted.len() as u64;
+    let mut processed = 0;
+
+    for chunk in encrypted.chunks(CHUNK_SIZE) {
+        cancel.check()?;
+
+        cipher.mac.update_padded(chunk);
+
+        processed += chunk.len() as u64;
+        progress(Progress::Payload { processed, total });
+    }
+
+    cipher
+        .finalize(aad.len(), encrypted.len())
+        .verify(Tag::from_slice(tag))
+        .map_err(|_| anyhow!("Authentication failed, wrong credentials or the file is corrupted"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chacha20poly1305::{
+        aead::{Aead, OsRng, Payload},
+        AeadCore, KeyInit, XChaCha20Poly1305,
+    };
+
+    fn unhex(hex: &str) -> Vec<u8> {
+        let hex: String = hex.split_whitespace().collect();
+        (0..hex.len()).step_by(2).map(|i| u8::from_str_radix(&hex[i..i + 2], 16).unwrap()).collect()
+    }
+
+    fn encrypted(key: &SecretBytes, nonce: &[u8], aad: &[u8], data: &[u8]) -> Vec<u8> {
+        let mut encrypted = data.to_vec();
+        let tag = encrypt_in_place(key, nonce, aad, &mut encrypted, &mut |_| {}, &CancelToken::new()).unwrap();
+        encrypted.extend_from_slice(&tag);
+        encrypted
+    }
+
+    /// draft-irtf-cfrg-xchacha-03 appendix A.3.1
+    #[test]
+    fn xchacha20poly1305_test_vector() {
+        let mut key = SecretBytes::zeroed(32);
+        key.expose_mut().copy_from_slice(&unhex("808182838485868788898a8b8c8d8e8f909192939495969798999a9b9c9d9e9f"));
+        let nonce = unhex("404142434445464748494a4b4c4d4e4f5051525354555657");
+        let aad = unhex("50515253c0c1c2c3c4c5c6c7");
+        let plaintext = b"Ladies and Gentlemen of the class of '99: If I could offer you only one tip for the future, sunscreen would be it.";
+
+        let mut expected = unhex(
+            "bd6d179d3e83d43b9576579493c0e939572a1700252bfaccbed2902c21396cbb
+             731c7f1b0b4aa6440bf3a82f4eda7e39ae64c6708c54c216cb96b72e1213b452
+             2f8c9ba40db5d945b11b69b982c1bb9e3f3fac2bc369488f76b2383565d3fff9
+             21f9664c97637da9768812f615c68b13b52e",
+        );
+        expected.extend_from_slice(&unhex("c0875924c1c7987947deafd8780acf49"));
+
+        assert_eq!(encrypted(&key, &nonce, &aad, plaintext), expected);
+
+        let cancel = CancelToken::new();
+        let decrypted = decrypt(&key, &nonce, &aad, &expected, &mut |_| {}, &cancel).unwrap();
+        assert_eq!(decrypted.expose(), plaintext);
+        assert!(verify(&key, &nonce, &aad, &expected, &mut |_| {}, &cancel).is_ok());
+    }
+
+    #[test]
+    fn matches_one_shot_aead() {
+        let mut key = SecretBytes::zeroed(64);
+        key.expose_mut().iter_mut().enumerate().for_each(|(i, b)| *b = i as u8);
+
+        let aead = XChaCha20Poly1305::new(GenericArray::from_slice(&key.expose()[..32]));
+        let nonce = XChaCha20Poly1305::generate_nonce(&mut OsRng);
+        let aad = b"some aad";
+        let cancel = CancelToken::new();
+
+        // Around the block and the chunk boundaries
+        for len in [0, 1, 15, 16, 63, 64, 65, CHUNK_SIZE - 1, CHUNK_SIZE, CHUNK_SIZE + 1, 3 * CHUNK_SIZE - 1] {
+            let data: Vec<u8> = (0..len).map(|i| (i % 251) as u8).collect();
+
+            let expected = aead.encrypt(&nonce, Payload { msg: &data, aad }).unwrap();
+            let encrypted = encrypted(&key, &nonce, aad, &data);
+            assert_eq!(encrypted, expected, "length {}", len);
+
+            let decrypted = decrypt(&key, &nonce, aad, &encrypted, &mut |_| {}, &cancel).unwrap();
+            assert_eq!(decrypted.expose(), &data[..], "length {}", len);
+
+            // The one-shot AEAD can read what we encrypt and the other way around
+            assert_eq!(aead.decrypt(&nonce, Payload { msg: &encrypted, aad }).unwrap(), data);
+        }
+    }
+
+    #[test]
+    fn rejects_tag_mismatch() {
+        let key = SecretBytes::zeroed(64);
+        let mut other_key = SecretBytes::zeroed(64);
+        other_key.expose_mut()[0] = 1;
+        let nonce = [0u8; 24];
+        let cancel = CancelToken::new();
+
+        for len in [0, CHUNK_SIZE - 1, CHUNK_SIZE, CHUNK_SIZE + 1] {
+            let data = encrypted(&key, &nonce, b"aad", &vec![7u8; len]);
+            assert!(verify(&key, &nonce, b"aad", &data, &mut |_| {}, &cancel).is_ok());
+
+            let mut mismatches = Vec::new();
+
+            // A flipped bit in the first and last byte of the ciphertext, and in the tag
+            for i in [0, len.saturating_sub(1), data.len() - 1] {
+                let mut tampered = data.clone();
+                tampered[i] ^= 1;
+                mismatches.push((tampered, &key, nonce, b"aad".as_slice()));
+            }
+
+            let mut wrong_nonce = nonce;
+            wrong_nonce[23] ^= 1;
+
+            mismatches.push((data.clone(), &other_key, nonce, b"aad".as_slice()));
+            mismatches.push((data.clone(), &key, wrong_nonce, b"aad".as_slice()));
+            mismatches.push((data.clone(), &key, nonce, b"aae".as_slice()));
+            mismatches.push((data.clone(), &key, nonce, b"".as_slice()));
+            mismatches.push((data[..data.len() - 1].to_vec(), &key, nonce, b"aad".as_slice()));
+
+            for (i, (data, key, nonce, aad)) in mismatches.iter().enumerate() {
+                assert!(decrypt(key, nonce, aad, data, &mut |_| {}, &cancel).is_err(), "length {} case {}", len, i);
+                assert!(verify(key, nonce, aad, data, &mut |_| {}, &cancel).is_err(), "length {} case {}", len, i);
+            }
+        }
+
+        // Shorter than a tag
+        assert!(decrypt(&key, &nonce, b"", &[0u8; TAG_SIZE - 1], &mut |_| {}, &cancel).is_err());
+        assert!(verify(&key, &nonce, b"", &[0u8; TAG_SIZE - 1], &mut |_| {}, &cancel).is_err());
+    }
+
+    #[test]
+    fn reports_progress_per_chunk() {
+        let key = SecretBytes::zeroed(64);
+        let nonce = [0u8; 24];
+        let cancel = CancelToken::new();
+
+        for (len, chunks) in [(0, 0), (CHUNK_SIZE - 1, 1), (CHUNK_SIZE, 1), (CHUNK_SIZE + 1, 2)] {
+            let mut data = vec![0u8; len];
+            let mut reports = Vec::new();
+            let tag = encrypt_in_place(&key, &nonce, b"", &mut data, &mut |p| reports.push(p), &cancel).unwrap();
+            data.extend_from_slice(&tag);
+
+            assert_eq!(reports.len(), chunks, "length {}", len);
+            assert_eq!(reports.last().copied(), (chunks > 0).then_some(Progress::Payload { processed: len as u64, total: len as u64 }));
+
+            let mut reports = Vec::new();
+            decrypt(&key, &nonce, b"", &data, &mut |p| reports.push(p), &cancel).unwrap();
+            assert_eq!(reports.len(), chunks, "length {}", len);
+        }
+    }
+
+    #[test]
+    fn cancels_between_chunks() {
+        let key = SecretBytes::zeroed(64);
+        let nonce = [0u8; 24];
+        let cancel = CancelToken::new();
+
+        let mut data = vec![0u8; 2 * CHUNK_SIZE];
+        let mut reports = 0;
+        let result = encrypt_in_place(&key, &nonce, b"", &mut data, &mut |_| {
+            reports += 1;
+            cancel.cancel();
+        }, &cancel);
+
+        assert!(result.unwrap_err().is::<crate::progress::Cancelled>());
+        assert_eq!(reports, 1);
+    }
+}
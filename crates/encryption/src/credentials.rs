@@ -1,29 +1,50 @@
 use zeroize::Zeroize;
 use anyhow::anyhow;
 
+use super::secret::SecretString;
+
 /// The credentials needed to encrypt and decrypt an encrypted file
-#[derive(Clone, Default)]
+///
+/// Encryption requires the password to be confirmed, see [Credentials::is_confirmed],
+/// decryption only needs the username and password.
+///
+/// Cannot be cloned, move it into the operation that needs it.
+#[derive(Debug, Default)]
 pub struct Credentials {
-    username: String,
-    password: String,
-    confirm_password: String,
-}
-
-impl Drop for Credentials {
-    fn drop(&mut self) {
-        self.destroy();
-    }
+    username: SecretString,
+    password: SecretString,
+    confirm_password: SecretString,
 }
 
 impl Credentials {
-    pub fn new(username: String, password: String, confirm_password: String) -> Self {
+    pub fn new(
+        username: impl Into<SecretString>,
+        password: impl Into<SecretString>,
+        confirm_password: impl Into<SecretString>,
+    ) -> Self {
         Self {
-            username,
-            password,
-            confirm_password,
+            username: username.into(),
+            password: password.into(),
+            confirm_password: confirm_password.into(),
         }
     }
 
+    /// Credentials without a confirmation, enough to decrypt or verify a file but not to encrypt
+    pub fn unconfirmed(username: impl Into<SecretString>, password: impl Into<SecretString>) -> Self {
+        Self::new(username, password, SecretString::default())
+    }
+
+    /// Explicit copy for workflows that need the credentials twice, like verifying a file right after encrypting it
+    ///
+    /// The copy lives in locked memory as well and is zeroized on drop.
+    pub fn duplicate(&self) -> Self {
+        Self::new(
+            self.username.expose(),
+            self.password.expose(),
+            self.confirm_password.expose(),
+        )
+    }
+
     /// Destroy the credentials by zeroizing the username and password
     pub fn destroy(&mut self) {
         self.username.zeroize();
@@ -31,53 +52,67 @@ impl Credentials {
         self.confirm_password.zeroize();
     }
 
-    pub fn username(&self) -> &String {
+    /// Nothing was typed, also true after [Credentials::destroy]
+    pub fn is_empty(&self) -> bool {
+        self.username.is_empty() && self.password.is_empty() && self.confirm_password.is_empty()
+    }
+
+    pub fn username(&self) -> &SecretString {
         &self.username
     }
 
-    pub fn password(&self) -> &String {
+    pub fn password(&self) -> &SecretString {
         &self.password
     }
 
-    pub fn confirm_password(&self) -> &String {
+    pub fn confirm_password(&self) -> &SecretString {
         &self.confirm_password
     }
 
     /// Get a mutable reference to the username
-    pub fn user_mut(&mut self) -> &mut String {
+    pub fn user_mut(&mut self) -> &mut SecretString {
         &mut self.username
     }
 
     /// Get a mutable reference to the password
-    pub fn passwd_mut(&mut self) -> &mut String {
+    pub fn passwd_mut(&mut self) -> &mut SecretString {
         &mut self.password
     }
 
     /// Get a mutable reference to the confirm password
-    pub fn confirm_passwd_mut(&mut self) -> &mut String {
+    pub fn confirm_passwd_mut(&mut self) -> &mut SecretString {
         &mut self.confirm_password
     }
 
     /// Copy password to confirm password
     pub fn copy_passwd_to_confirm(&mut self) {
         self.confirm_password.clear();
-        self.confirm_password.push_str(&self.password);
+        self.confirm_password.push_str(self.password.expose());
     }
 
+    /// Check that the username and password are provided, all that decryption needs
     pub fn is_valid(&self) -> Result<(), anyhow::Error> {
         if self.username.is_empty() {
             return Err(anyhow!("Username must be provided"));
         }
-        
+
         if self.password.is_empty() {
             return Err(anyhow!("Password must be provided"));
         }
 
+        Ok(())
+    }
+
+    /// Check that the password has been typed twice, required to encrypt
+    /// so a typo does not lock the user out of their file
+    pub fn is_confirmed(&self) -> Result<(), anyhow::Error> {
+        self.is_valid()?;
+
         if self.confirm_password.is_empty() {
             return Err(anyhow!("Confirm password must be provided"));
         }
 
-        if self.password != self.confirm_password {
+        if self.password.expose() != self.confirm_password.expose() {
             return Err(anyhow!("Passwords do not match"));
         }
 
@@ -86,7 +121,6 @@ impl Credentials {
 }
 
 #[cfg(test)]
-
 mod tests {
     use super::*;
 
@@ -98,9 +132,23 @@ mod tests {
             "password".to_string(),
         );
         assert!(credentials.is_valid().is_ok());
+        assert!(credentials.is_confirmed().is_ok());
 
 
+        assert!(!credentials.is_empty());
         credentials.destroy();
         assert!(credentials.is_valid().is_err());
+        assert!(credentials.is_empty());
     }
-}
\ No newline at end of file
+
+    #[test]
+    fn confirmation_only_needed_to_encrypt() {
+        let credentials = Credentials::unconfirmed("test", "password");
+        assert!(credentials.is_valid().is_ok());
+        assert!(credentials.is_confirmed().is_err());
+
+        let credentials = Credentials::new("test", "password", "passwrod");
+        assert!(credentials.is_valid().is_ok());
+        assert!(credentials.is_confirmed().is_err());
+    }
+}
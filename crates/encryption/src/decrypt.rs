@@ -1,12 +1,11 @@
-use argon2::{
-    password_hash::{PasswordHasher, SaltString},
-    Algorithm, Argon2, Params, Version,
-};
-use chacha20poly1305::aead::{generic_array::GenericArray, Aead, Payload};
+use argon2::password_hash::SaltString;
 
 use super::{
+    chunked,
     credentials::Credentials,
-    encrypt::{xchacha20_poly_1305, HEADER},
+    encrypt::{derive_key, HEADER},
+    progress::{CancelToken, Progress},
+    secret::SecretBytes,
     EncryptedInfo,
 };
 
@@ -18,83 +17,115 @@ use anyhow::anyhow;
 ///
 /// - `data` - The data to decrypt
 /// - `credentials` - The credentials to use for decryption
-pub fn decrypt_data(data: Vec<u8>, credentials: Credentials) -> Result<Vec<u8>, anyhow::Error> {
-
-        // Verify Header
-        if &data[0..8] != HEADER {
-            return Err(anyhow!("Header not found, invalid file format?"));
-        }
-    
-        // Read Metadata Length
-        let metadata_length = u32::from_le_bytes(
-            data[8..12].try_into().map_err(|e| anyhow!("Failed to parse metadata length {}", e))?,
-        );
-    
-        // Extract Metadata
-        let metadata_start = 12;
-        let metadata_end = metadata_start + metadata_length as usize;
-        let metadata_bytes = &data[metadata_start..metadata_end];
-    
-        let info: EncryptedInfo = bincode::deserialize(metadata_bytes).map_err(|e| anyhow!("Deserialization failed {}", e))?;
-    
-        // Extract Encrypted Data
-        let encrypted_data = &data[metadata_end..];
-
-    let decrypted_data = decrypt(credentials, info, encrypted_data.to_vec())?;
-    Ok(decrypted_data)
+pub fn decrypt_data(data: Vec<u8>, credentials: Credentials) -> Result<SecretBytes, anyhow::Error> {
+    decrypt_data_with(data, credentials, &mut |_| {}, &CancelToken::new())
 }
 
-fn decrypt(mut credentials: Credentials, info: EncryptedInfo, data: Vec<u8>) -> Result<Vec<u8>, anyhow::Error> {
-    credentials.is_valid()?;
+/// Same as [decrypt_data] but reports its progress and can be cancelled
+///
+/// ### Arguments
+///
+/// - `progress` - Called when the operation enters the key derivation and after each decrypted chunk
+/// - `cancel` - Checked between steps, a cancelled operation returns a [Cancelled](crate::progress::Cancelled) error
+pub fn decrypt_data_with(
+    data: Vec<u8>,
+    credentials: Credentials,
+    progress: &mut dyn FnMut(Progress),
+    cancel: &CancelToken,
+) -> Result<SecretBytes, anyhow::Error> {
+    let (info, encrypted_data) = read_header(&data)?;
+    let (key, aad) = derive_keys(credentials, &info, progress, cancel)?;
+
+    // decrypt using the hashed password as the key, straight into locked memory
+    chunked::decrypt(&key, &info.cipher_nonce, aad.expose(), encrypted_data, progress, cancel)
+}
 
-    let params = Params::new(
-        info.argon2_params.m_cost,
-        info.argon2_params.t_cost,
-        info.argon2_params.p_cost,
-        Some(info.argon2_params.hash_length as usize),
-    )
-    .map_err(|e| anyhow!("Failed to create Argon2 params {:?}", e))?;
+/// Checks that the data decrypts with the provided credentials without producing any plaintext
+///
+/// The whole file is authenticated exactly like [decrypt_data] does, but only the tag is computed,
+/// nothing is decrypted so no plaintext ever exists in memory.
+///
+/// ### Arguments
+///
+/// - `data` - The data to verify
+/// - `credentials` - The credentials to use for verification
+pub fn verify_data(data: &[u8], credentials: Credentials) -> Result<(), anyhow::Error> {
+    verify_data_with(data, credentials, &mut |_| {}, &CancelToken::new())
+}
 
-    let argon2 = Argon2::new(Algorithm::default(), Version::default(), params.clone());
+/// Same as [verify_data] but reports its progress and can be cancelled
+///
+/// ### Arguments
+///
+/// - `progress` - Called when the operation enters the key derivation and after each verified chunk
+/// - `cancel` - Checked between steps, a cancelled operation returns a [Cancelled](crate::progress::Cancelled) error
+pub fn verify_data_with(
+    data: &[u8],
+    credentials: Credentials,
+    progress: &mut dyn FnMut(Progress),
+    cancel: &CancelToken,
+) -> Result<(), anyhow::Error> {
+    let (info, encrypted_data) = read_header(data)?;
+    let (key, aad) = derive_keys(credentials, &info, progress, cancel)?;
+
+    chunked::verify(&key, &info.cipher_nonce, aad.expose(), encrypted_data, progress, cancel)
+}
+
+/// Parse the header and metadata of an encrypted file
+///
+/// Returns the metadata and the encrypted data that follows it
+pub fn read_header(data: &[u8]) -> Result<(EncryptedInfo, &[u8]), anyhow::Error> {
+    // Verify Header
+    if data.len() < 12 || &data[0..8] != HEADER {
+        return Err(anyhow!("Header not found, invalid file format?"));
+    }
+
+    // Read Metadata Length
+    let metadata_length = u32::from_le_bytes(
+        data[8..12].try_into().map_err(|e| anyhow!("Failed to parse metadata length {}", e))?,
+    );
+
+    // Extract Metadata
+    let metadata_start = 12;
+    let metadata_end = metadata_start + metadata_length as usize;
+    let metadata_bytes = data
+        .get(metadata_start..metadata_end)
+        .ok_or(anyhow!("Metadata is truncated, invalid file format?"))?;
+
+    let info: EncryptedInfo = bincode::deserialize(metadata_bytes).map_err(|e| anyhow!("Deserialization failed {}", e))?;
+
+    // Extract Encrypted Data
+    Ok((info, &data[metadata_end..]))
+}
+
+/// Hash the password into the key and the username into the additional data
+fn derive_keys(
+    mut credentials: Credentials,
+    info: &EncryptedInfo,
+    progress: &mut dyn FnMut(Progress),
+    cancel: &CancelToken,
+) -> Result<(SecretBytes, SecretBytes), anyhow::Error> {
+    credentials.is_valid()?;
+    cancel.check()?;
+    progress(Progress::DerivingKey);
+
+    let argon2 = info.argon2_params.argon2()?;
 
     let password_salt = SaltString::from_b64(&info.password_salt)
         .map_err(|e| anyhow!("Failed to parse password salt {:?}", e))?;
 
-    let password_hash = argon2
-        .hash_password(credentials.password().as_bytes(), &password_salt)
+    let key = derive_key(&argon2, credentials.password().expose().as_bytes(), &password_salt)
         .map_err(|e| anyhow!("Failed to hash password {:?}", e))?;
 
-    let key = password_hash
-        .hash
-        .ok_or(anyhow!("Failed to get the password hash output"))?;
-
-    // create the cipher using the hashed password as the key
-    let cipher = xchacha20_poly_1305(key);
+    cancel.check()?;
 
     let username_salt = SaltString::from_b64(&info.username_salt)
         .map_err(|e| anyhow!("Failed to parse username salt {:?}", e))?;
 
-    let username_hash = argon2
-        .hash_password(credentials.username().as_bytes(), &username_salt)
+    let aad = derive_key(&argon2, credentials.username().expose().as_bytes(), &username_salt)
         .map_err(|e| anyhow!("Failed to hash username {:?}", e))?;
 
     credentials.destroy();
 
-    let aad = username_hash
-        .hash
-        .ok_or(anyhow!("Failed to get the username hash output"))?;
-
-    let payload = Payload {
-        msg: data.as_ref(),
-        aad: aad.as_bytes(),
-    };
-
-    
-    let nonce = GenericArray::from_slice(&info.cipher_nonce);
-
-    let decrypted_data = cipher
-        .decrypt(nonce, payload)
-        .map_err(|e| anyhow!("Failed to decrypt data {:?}", e))?;
-
-    Ok(decrypted_data)
-}
\ No newline at end of file
+    Ok((key, aad))
+}
@@ -1,15 +1,17 @@
 use argon2::{
-    password_hash::{Output, PasswordHasher, SaltString},
-    Algorithm, Argon2, Params, Version,
-};
-use chacha20poly1305::{
-    aead::{generic_array::GenericArray, Aead, OsRng, Payload},
-    AeadCore, KeyInit, XChaCha20Poly1305,
+    password_hash::{Salt, SaltString},
+    Argon2, Params,
 };
+use chacha20poly1305::{aead::OsRng, AeadCore, XChaCha20Poly1305};
 
+use super::chunked;
 use super::credentials::Credentials;
+use super::progress::{CancelToken, Progress};
+use super::secret::SecretBytes;
+use super::strength::PasswordPolicy;
 use super::{Argon2Params, EncryptedInfo};
 use anyhow::anyhow;
+use zeroize::Zeroizing;
 use bincode;
 
 
@@ -51,13 +53,36 @@ pub const HEADER: &[u8; 8] = b"nCrypt1\0";
 ///
 /// - `argon_params` - The Argon2 parameters to use for the password hashing
 /// - `data` - The data to encrypt
-/// - `credentials` - The credentials to use for encryption
+/// - `credentials` - The credentials to use for encryption, the password must meet the default [PasswordPolicy]
 pub fn encrypt_data(
     argon_params: Argon2Params,
     data: Vec<u8>,
     credentials: Credentials,
 ) -> Result<Vec<u8>, anyhow::Error> {
-    let (encrypted_data, info) = encrypt(argon_params.clone(), credentials, data)?;
+    encrypt_data_with(argon_params, data, credentials, &PasswordPolicy::default(), &mut |_| {}, &CancelToken::new())
+}
+
+/// Same as [encrypt_data] but with a custom password policy, reports its progress and can be cancelled
+///
+/// ### Arguments
+///
+/// - `policy` - The password is refused if it does not meet this policy
+/// - `progress` - Called when the operation enters the key derivation and after each encrypted chunk
+/// - `cancel` - Checked between steps, a cancelled operation returns a [Cancelled](crate::progress::Cancelled) error
+pub fn encrypt_data_with(
+    argon_params: Argon2Params,
+    data: Vec<u8>,
+    credentials: Credentials,
+    policy: &PasswordPolicy,
+    progress: &mut dyn FnMut(Progress),
+    cancel: &CancelToken,
+) -> Result<Vec<u8>, anyhow::Error> {
+    // Wiped on every early return, only the encrypted data is taken out of it
+    let data = Zeroizing::new(data);
+
+    credentials.is_confirmed().and_then(|_| policy.check(&credentials))?;
+
+    let (encrypted_data, info) = encrypt(argon_params, credentials, data, progress, cancel)?;
 
     let serialized_info = bincode::serialize(&info)?;
 
@@ -81,72 +106,82 @@ pub fn encrypt_data(
 }
 
 /// Encrypts the given data using the provided credentials
+///
+/// The data is encrypted in place, so no copy of the plaintext is left behind,
+/// if anything fails the plaintext is zeroized when `data` is dropped
 fn encrypt(
     argon_params: Argon2Params,
     mut credentials: Credentials,
-    data: Vec<u8>,
+    mut data: Zeroizing<Vec<u8>>,
+    progress: &mut dyn FnMut(Progress),
+    cancel: &CancelToken,
 ) -> Result<(Vec<u8>, EncryptedInfo), anyhow::Error> {
-    credentials.is_valid()?;
-
-    let params = Params::new(
-        argon_params.m_cost,
-        argon_params.t_cost,
-        argon_params.p_cost,
-        Some(argon_params.hash_length as usize),
-    )
-    .map_err(|e| anyhow!("Failed to set Argon2 params {:?}", e))?;
+    cancel.check()?;
+    progress(Progress::DerivingKey);
 
-    let argon2 = Argon2::new(Algorithm::default(), Version::default(), params);
+    let argon2 = argon_params.argon2()?;
 
     let password_salt = SaltString::generate(&mut OsRng);
     let username_salt = SaltString::generate(&mut OsRng);
 
 
     // hash the password
-    let password_hash = match argon2.hash_password(credentials.password().as_bytes(), &password_salt) {
-        Ok(hash) => hash,
-        Err(e) => {
-            return Err(anyhow!("Failed to hash password {:?}", e));
-        }
-    };
-
-    // get the hash output
-    let key = password_hash
-        .hash
-        .ok_or(anyhow!("Failed to get the password hash output"))?;
+    let key = derive_key(&argon2, credentials.password().expose().as_bytes(), &password_salt)
+        .map_err(|e| anyhow!("Failed to hash password {:?}", e))?;
+
+    cancel.check()?;
 
     // hash the username for the AAD
-    let username_hash = match argon2.hash_password(credentials.username().as_bytes(), &username_salt) {
-        Ok(hash) => hash,
-        Err(e) => return Err(anyhow!("Failed to hash username {:?}", e)),
-    };
+    let aad = derive_key(&argon2, credentials.username().expose().as_bytes(), &username_salt)
+        .map_err(|e| anyhow!("Failed to hash username {:?}", e))?;
 
     credentials.destroy();
 
-    let aad = username_hash.hash.ok_or(anyhow!("Failed to get the username hash output"))?;
 
-
-    // create the cipher using the hashed password as the key
-    let cipher = xchacha20_poly_1305(key);
+    // encrypt using the hashed password as the key
     let cipher_nonce = XChaCha20Poly1305::generate_nonce(&mut OsRng);
 
+    let tag = chunked::encrypt_in_place(&key, &cipher_nonce, aad.expose(), &mut data, progress, cancel)?;
+    data.extend_from_slice(&tag);
+
+    let info = EncryptedInfo::new(password_salt.to_string(), username_salt.to_string(), cipher_nonce.to_vec(), argon_params);
+
+    Ok((std::mem::take(&mut *data), info))
+}
 
-    let payload = Payload {
-        msg: data.as_ref(),
-        aad: aad.as_bytes(),
-    };
+/// Hash a secret with Argon2 into a zeroizing buffer
+///
+/// Produces the same output as `PasswordHasher::hash_password` without keeping a copy in a `PasswordHash`
+pub fn derive_key(argon2: &Argon2, secret: &[u8], salt: &SaltString) -> Result<SecretBytes, anyhow::Error> {
+    let mut salt_buf = [0u8; Salt::MAX_LENGTH];
+    let salt = salt
+        .as_salt()
+        .decode_b64(&mut salt_buf)
+        .map_err(|e| anyhow!("Failed to decode salt {:?}", e))?;
+
+    let output_len = argon2.params().output_len().unwrap_or(Params::DEFAULT_OUTPUT_LEN);
+    let mut output = SecretBytes::zeroed(output_len);
+
+    argon2
+        .hash_password_into(secret, salt, output.expose_mut())
+        .map_err(|e| anyhow!("{:?}", e))?;
+
+    Ok(output)
+}
 
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use argon2::password_hash::PasswordHasher;
 
-    let encrypted_data = cipher
-        .encrypt(&cipher_nonce, payload)
-        .map_err(|e| anyhow!("Failed to encrypt data {:?}", e))?;
+    #[test]
+    fn derive_key_matches_password_hash() {
+        let argon2 = Argon2Params::very_fast().argon2().unwrap();
+        let salt = SaltString::generate(&mut OsRng);
 
-    let info = EncryptedInfo::new(password_salt.to_string(), username_salt.to_string(), cipher_nonce.to_vec(), argon_params);
+        let key = derive_key(&argon2, b"password", &salt).unwrap();
+        let hash = argon2.hash_password(b"password", &salt).unwrap();
 
-    Ok((encrypted_data, info))
+        assert_eq!(key.expose(), hash.hash.unwrap().as_bytes());
+    }
 }
-
-pub fn xchacha20_poly_1305(key: Output) -> XChaCha20Poly1305 {
-    let key = GenericArray::from_slice(&key.as_bytes()[..32]);
-    XChaCha20Poly1305::new(key)
-}
\ No newline at end of file
@@ -0,0 +1,581 @@
+use chacha20poly1305::aead::{rand_core::RngCore, OsRng};
+use std::collections::HashSet;
+use std::ffi::OsString;
+use std::fs::{self, File, OpenOptions};
+use std::io::{self, Read, Write};
+use std::path::{Path, PathBuf};
+use sysinfo::{Pid, ProcessRefreshKind, ProcessesToUpdate, System};
+
+use super::decrypt::read_header;
+use super::progress::CancelToken;
+use super::EncryptedInfo;
+use anyhow::anyhow;
+
+/// Extension of encrypted files, without the dot
+pub const FILE_EXTENSION: &str = "ncrypt";
+
+/// Appended to the name of a decrypted file whose input had no `.ncrypt` extension
+const DECRYPTED_SUFFIX: &str = "decrypted";
+
+/// Bytes written between cancellation checks
+const WRITE_CHUNK_SIZE: usize = 1024 * 1024;
+
+/// Largest metadata [read_info] accepts, real metadata is a few hundred bytes
+const MAX_METADATA_SIZE: u32 = 64 * 1024;
+
+/// Start of the names of the directories made by [create_private_dir]
+const PRIVATE_DIR_PREFIX: &str = ".ncrypt-";
+
+/// Whether the path has the `.ncrypt` extension
+pub fn is_encrypted_path(path: &Path) -> bool {
+    path.extension()
+        .is_some_and(|ext| ext.eq_ignore_ascii_case(FILE_EXTENSION))
+}
+
+/// Path of the encrypted file, `.ncrypt` appended to the file name
+///
+/// ### Arguments
+///
+/// - `input` - The file to encrypt
+/// - `output_dir` - The directory to write to, `None` for the directory of `input`
+pub fn encrypted_path(input: &Path, output_dir: Option<&Path>) -> Result<PathBuf, anyhow::Error> {
+    let mut name = file_name(input)?;
+    name.push(".");
+    name.push(FILE_EXTENSION);
+
+    Ok(output_path(input, output_dir, name))
+}
+
+/// Path of the decrypted file, the `.ncrypt` extension removed from the file name
+///
+/// If the file has no `.ncrypt` extension `.decrypted` is appended instead,
+/// so the output never has the same path as the input.
+///
+/// ### Arguments
+///
+/// - `input` - The file to decrypt
+/// - `output_dir` - The directory to write to, `None` for the directory of `input`
+pub fn decrypted_path(input: &Path, output_dir: Option<&Path>) -> Result<PathBuf, anyhow::Error> {
+    let name = if is_encrypted_path(input) {
+        input
+            .file_stem()
+            .map(|stem| stem.to_os_string())
+            .ok_or(anyhow!("Invalid file name {}", input.display()))?
+    } else {
+        let mut name = file_name(input)?;
+        name.push(".");
+        name.push(DECRYPTED_SUFFIX);
+        name
+    };
+
+    Ok(output_path(input, output_dir, name))
+}
+
+/// The first path that does not exist yet, adding ` (1)`, ` (2)`... before the extension
+pub fn unique_path(path: &Path) -> PathBuf {
+    unique_path_among(path, &HashSet::new())
+}
+
+/// Like [unique_path], also skipping the paths `reserved` for files that are not written yet
+pub fn unique_path_among(path: &Path, reserved: &HashSet<PathBuf>) -> PathBuf {
+    let taken = |path: &Path| path.exists() || reserved.contains(path);
+
+    if !taken(path) {
+        return path.to_path_buf();
+    }
+
+    let stem = path.file_stem().unwrap_or_default();
+    let extension = path.extension();
+
+    for i in 1.. {
+        let mut name = stem.to_os_string();
+        name.push(format!(" ({})", i));
+
+        if let Some(extension) = extension {
+            name.push(".");
+            name.push(extension);
+        }
+
+        let candidate = path.with_file_name(name);
+        if !taken(&candidate) {
+            return candidate;
+        }
+    }
+
+    unreachable!()
+}
+
+/// Read the metadata of an encrypted file without reading the encrypted data
+///
+/// Useful to find out what decrypting the file will need before asking for the credentials
+pub fn read_info(path: &Path) -> Result<EncryptedInfo, anyhow::Error> {
+    let mut file = File::open(path).map_err(|e| anyhow!("Failed to open {} {}", path.display(), e))?;
+
+    let mut header = vec![0u8; 12];
+    file.read_exact(&mut header)
+        .map_err(|_| anyhow!("Header not found, invalid file format?"))?;
+
+    let metadata_length = u32::from_le_bytes(header[8..12].try_into()?);
+    if metadata_length > MAX_METADATA_SIZE {
+        return Err(anyhow!("Metadata is too large, invalid file format?"));
+    }
+
+    header.resize(12 + metadata_length as usize, 0);
+    file.read_exact(&mut header[12..])
+        .map_err(|_| anyhow!("Metadata is truncated, invalid file format?"))?;
+
+    let (info, _) = read_header(&header)?;
+    Ok(info)
+}
+
+/// Write the data to `path` atomically
+///
+/// The data goes to a temporary file in the same directory which is synced to disk and then renamed to `path`,
+/// so `path` either keeps its old content or gets the new content, never a partial write.
+/// Without `overwrite` the temporary file is hard linked to `path` instead, which fails if another
+/// writer created `path` in the meantime.
+/// If writing fails or is cancelled the temporary file is removed.
+///
+/// ### Arguments
+///
+/// - `path` - The file to write
+/// - `data` - The data to write
+/// - `overwrite` - Whether to replace `path` if it already exists
+/// - `cancel` - Checked between chunks
+pub fn write_atomic(path: &Path, data: &[u8], overwrite: bool, cancel: &CancelToken) -> Result<(), anyhow::Error> {
+    if !overwrite && path.exists() {
+        return Err(anyhow!("{} already exists", path.display()));
+    }
+
+    let dir = parent_dir(path);
+    let (tmp_path, mut file) = create_temp_file(dir, path)?;
+
+    let result = (|| {
+        for chunk in data.chunks(WRITE_CHUNK_SIZE) {
+            cancel.check()?;
+            file.write_all(chunk)?;
+        }
+        file.sync_all()?;
+        drop(file);
+
+        cancel.check()?;
+        if overwrite {
+            fs::rename(&tmp_path, path)?;
+        } else {
+            publish_new(&tmp_path, path)?;
+        }
+        sync_dir(dir);
+        Ok(())
+    })();
+
+    if result.is_err() {
+        let _ = fs::remove_file(&tmp_path);
+    }
+
+    result
+}
+
+/// Move the temporary file to `path` without replacing a file created since the check in [write_atomic]
+fn publish_new(tmp_path: &Path, path: &Path) -> Result<(), anyhow::Error> {
+    match fs::hard_link(tmp_path, path) {
+        Ok(()) => {
+            fs::remove_file(tmp_path)?;
+            Ok(())
+        }
+        Err(e) if e.kind() == io::ErrorKind::AlreadyExists => Err(anyhow!("{} already exists", path.display())),
+
+        // Filesystems like FAT have no hard links, only a checked rename is left there
+        Err(e) if matches!(e.kind(), io::ErrorKind::Unsupported | io::ErrorKind::PermissionDenied) => {
+            if path.exists() {
+                return Err(anyhow!("{} already exists", path.display()));
+            }
+            fs::rename(tmp_path, path)?;
+            Ok(())
+        }
+        Err(e) => Err(e.into()),
+    }
+}
+
+/// Best effort secure deletion, overwrite the file with random data, truncate it and remove it
+///
+/// **Warning:** overwriting in place is not reliable on SSDs and flash storage (wear leveling writes elsewhere),
+/// on copy-on-write filesystems like btrfs, ZFS or APFS, or when snapshots and backups exist.
+/// There the original data may still be recoverable, full disk encryption is the only reliable protection.
+///
+/// Once started it cannot be cancelled, a half overwritten file would be useless anyway.
+/// A symbolic link is only removed, the file it points to is left untouched.
+pub fn secure_delete(path: &Path) -> Result<(), anyhow::Error> {
+    let metadata = fs::symlink_metadata(path).map_err(|e| anyhow!("Failed to read {} {}", path.display(), e))?;
+
+    if metadata.file_type().is_symlink() {
+        return fs::remove_file(path).map_err(|e| anyhow!("Failed to remove {} {}", path.display(), e));
+    }
+
+    if !metadata.is_file() {
+        return Err(anyhow!("{} is not a regular file", path.display()));
+    }
+
+    let mut options = OpenOptions::new();
+    options.write(true);
+
+    // In case the file was replaced by a link since the check
+    #[cfg(unix)]
+    std::os::unix::fs::OpenOptionsExt::custom_flags(&mut options, libc::O_NOFOLLOW);
+
+    let mut file = options.open(path).map_err(|e| anyhow!("Failed to open {} {}", path.display(), e))?;
+
+    let len = file.metadata()?.len();
+    let mut buf = vec![0u8; WRITE_CHUNK_SIZE];
+    let mut written = 0;
+
+    while written < len {
+        let n = (len - written).min(buf.len() as u64) as usize;
+        OsRng.fill_bytes(&mut buf[..n]);
+        file.write_all(&buf[..n])?;
+        written += n as u64;
+    }
+    file.sync_all()?;
+
+    file.set_len(0)?;
+    file.sync_all()?;
+    drop(file);
+
+    // Rename first so the original file name does not stay in the directory entry
+    let dir = parent_dir(path);
+    let hidden = dir.join(format!(".{:016x}.tmp", OsRng.next_u64()));
+    fs::rename(path, &hidden)?;
+    fs::remove_file(&hidden)?;
+    sync_dir(dir);
+
+    Ok(())
+}
+
+/// Create a directory only the current user can enter, for decrypted files that other apps open
+///
+/// It is created in memory when possible, see [memory_dir]. Otherwise it is only created in the temporary
+/// directory of the system if `allow_disk` is set, shredded files may still be recoverable from the disk there.
+/// The name holds the id of the process, so [stale_private_dirs] can tell which ones were left behind.
+/// Remove it with [shred_dir].
+pub fn create_private_dir(allow_disk: bool) -> Result<PathBuf, anyhow::Error> {
+    let base = match memory_dir() {
+        Some(dir) => dir,
+        None if allow_disk => std::env::temp_dir(),
+        None => return Err(anyhow!("No folder backed by memory was found, the decrypted file would be written to disk")),
+    };
+
+    loop {
+        let dir = base.join(format!("{}{}-{:016x}", PRIVATE_DIR_PREFIX, std::process::id(), OsRng.next_u64()));
+
+        let mut builder = fs::DirBuilder::new();
+
+        #[cfg(unix)]
+        std::os::unix::fs::DirBuilderExt::mode(&mut builder, 0o700);
+
+        match builder.create(&dir) {
+            Ok(()) => return Ok(dir),
+            Err(e) if e.kind() == io::ErrorKind::AlreadyExists => continue,
+            Err(e) => return Err(anyhow!("Failed to create a private directory in {} {}", base.display(), e)),
+        }
+    }
+}
+
+/// Securely delete every file in `dir`, then remove it
+///
+/// Goes on after a failure so as much as possible is deleted, the first error is returned
+pub fn shred_dir(dir: &Path) -> Result<(), anyhow::Error> {
+    let mut result = Ok(());
+
+    let entries = fs::read_dir(dir).map_err(|e| anyhow!("Failed to read {} {}", dir.display(), e))?;
+
+    for entry in entries {
+        let removed = entry.map_err(anyhow::Error::from).and_then(|entry| {
+            let path = entry.path();
+
+            if entry.file_type()?.is_dir() {
+                shred_dir(&path)
+            } else {
+                secure_delete(&path)
+            }
+        });
+
+        if result.is_ok() {
+            result = removed;
+        }
+    }
+
+    let removed = fs::remove_dir(dir).map_err(|e| anyhow!("Failed to remove {} {}", dir.display(), e));
+    result.and(removed)
+}
+
+/// Private directories of processes that are gone, left when the app crashed or was killed
+///
+/// Remove them with [shred_dir]. Those of other users and of instances still running are left alone.
+pub fn stale_private_dirs() -> Vec<PathBuf> {
+    let mut system = System::new();
+    let mut stale = Vec::new();
+
+    for base in memory_dirs().into_iter().chain([std::env::temp_dir()]) {
+        let Ok(entries) = fs::read_dir(&base) else {
+            continue;
+        };
+
+        for entry in entries.flatten() {
+            let Some(pid) = entry.file_name().to_str().and_then(private_dir_pid) else {
+                continue;
+            };
+
+            // Not a link, and owned by the current user
+            let Ok(metadata) = fs::symlink_metadata(entry.path()) else {
+                continue;
+            };
+
+            #[cfg(unix)]
+            let owned = std::os::unix::fs::MetadataExt::uid(&metadata) == unsafe { libc::geteuid() };
+
+            #[cfg(not(unix))]
+            let owned = true;
+
+            if !metadata.is_dir() || !owned || pid == std::process::id() || is_running(&mut system, pid) {
+                continue;
+            }
+
+            stale.push(entry.path());
+        }
+    }
+
+    stale
+}
+
+/// The process id in the name of a directory made by [create_private_dir]
+fn private_dir_pid(name: &str) -> Option<u32> {
+    let (pid, random) = name.strip_prefix(PRIVATE_DIR_PREFIX)?.split_once('-')?;
+
+    if random.len() != 16 || !random.chars().all(|c| c.is_ascii_hexdigit()) {
+        return None;
+    }
+
+    pid.parse().ok()
+}
+
+fn is_running(system: &mut System, pid: u32) -> bool {
+    let pid = Pid::from_u32(pid);
+    system.refresh_processes_specifics(ProcessesToUpdate::Some(&[pid]), true, ProcessRefreshKind::new());
+    system.process(pid).is_some()
+}
+
+/// The first directory backed by memory that exists, `None` where decrypted files can only go to disk
+///
+/// On Linux `$XDG_RUNTIME_DIR` or `/dev/shm`, both tmpfs
+pub fn memory_dir() -> Option<PathBuf> {
+    memory_dirs().into_iter().find(|dir| dir.is_dir())
+}
+
+/// Where [memory_dir] looks, directories backed by memory
+fn memory_dirs() -> Vec<PathBuf> {
+    if !cfg!(target_os = "linux") {
+        return Vec::new();
+    }
+
+    std::env::var_os("XDG_RUNTIME_DIR")
+        .map(PathBuf::from)
+        .into_iter()
+        .chain([PathBuf::from("/dev/shm")])
+        .collect()
+}
+
+fn file_name(path: &Path) -> Result<OsString, anyhow::Error> {
+    path.file_name()
+        .map(|name| name.to_os_string())
+        .ok_or(anyhow!("Invalid file name {}", path.display()))
+}
+
+fn output_path(input: &Path, output_dir: Option<&Path>, name: OsString) -> PathBuf {
+    match output_dir {
+        Some(dir) => dir.join(name),
+        None => input.with_file_name(name),
+    }
+}
+
+fn parent_dir(path: &Path) -> &Path {
+    match path.parent() {
+        Some(parent) if !parent.as_os_str().is_empty() => parent,
+        _ => Path::new("."),
+    }
+}
+
+/// Create a new hidden temporary file next to `path`, only readable by the owner
+fn create_temp_file(dir: &Path, path: &Path) -> Result<(PathBuf, File), anyhow::Error> {
+    let name = path.file_name().unwrap_or_default().to_string_lossy();
+
+    let mut options = OpenOptions::new();
+    options.write(true).create_new(true);
+
+    #[cfg(unix)]
+    std::os::unix::fs::OpenOptionsExt::mode(&mut options, 0o600);
+
+    loop {
+        let tmp_path = dir.join(format!(".{}.{:016x}.tmp", name, OsRng.next_u64()));
+
+        match options.open(&tmp_path) {
+            Ok(file) => return Ok((tmp_path, file)),
+            Err(e) if e.kind() == io::ErrorKind::AlreadyExists => continue,
+            Err(e) => return Err(anyhow!("Failed to create a temporary file in {} {}", dir.display(), e)),
+        }
+    }
+}
+
+/// Make the rename durable, not supported on every platform so errors are ignored
+fn sync_dir(dir: &Path) {
+    #[cfg(unix)]
+    if let Ok(dir) = File::open(dir) {
+        let _ = dir.sync_all();
+    }
+
+    #[cfg(not(unix))]
+    let _ = dir;
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn encrypted_and_decrypted_paths() {
+        let input = Path::new("/data/backup.ncrypt/report.pdf");
+        let encrypted = encrypted_path(input, None).unwrap();
+        assert_eq!(encrypted, Path::new("/data/backup.ncrypt/report.pdf.ncrypt"));
+
+        // Only the extension of the file name is removed, not a directory named `.ncrypt`
+        let decrypted = decrypted_path(&encrypted, None).unwrap();
+        assert_eq!(decrypted, input);
+
+        let decrypted = decrypted_path(Path::new("/data/report"), Some(Path::new("/out"))).unwrap();
+        assert_eq!(decrypted, Path::new("/out/report.decrypted"));
+
+        assert!(is_encrypted_path(Path::new("a.NCRYPT")));
+        assert!(!is_encrypted_path(Path::new(".ncrypt")));
+    }
+
+    #[test]
+    fn write_atomic_and_unique_path() {
+        let dir = std::env::temp_dir().join(format!("ncrypt_file_test_{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("out.txt");
+        let cancel = CancelToken::new();
+
+        write_atomic(&path, b"first", false, &cancel).unwrap();
+        assert!(write_atomic(&path, b"second", false, &cancel).is_err());
+        assert_eq!(fs::read(&path).unwrap(), b"first");
+
+        write_atomic(&path, b"second", true, &cancel).unwrap();
+        assert_eq!(fs::read(&path).unwrap(), b"second");
+
+        assert_eq!(unique_path(&path), dir.join("out (1).txt"));
+        let reserved = HashSet::from([dir.join("out (1).txt")]);
+        assert_eq!(unique_path_among(&path, &reserved), dir.join("out (2).txt"));
+
+        // A file created after the existence check is not replaced
+        let tmp = dir.join("tmp");
+        fs::write(&tmp, b"third").unwrap();
+        assert!(publish_new(&tmp, &path).is_err());
+        assert_eq!(fs::read(&path).unwrap(), b"second");
+        fs::remove_file(&tmp).unwrap();
+
+        cancel.cancel();
+        assert!(write_atomic(&dir.join("cancelled.txt"), b"data", false, &cancel).is_err());
+
+        // Only the written file is left, no temporary files
+        assert_eq!(fs::read_dir(&dir).unwrap().count(), 1);
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn decrypted_output_is_private() {
+        use std::os::unix::fs::PermissionsExt;
+
+        let path = std::env::temp_dir().join(format!("ncrypt_mode_test_{}", std::process::id()));
+        let credentials = || crate::credentials::Credentials::new("user", "x7$Kq!9vLm#2Pz", "x7$Kq!9vLm#2Pz");
+
+        let data = crate::encrypt::encrypt_data(crate::Argon2Params::very_fast(), b"secret".to_vec(), credentials()).unwrap();
+        let decrypted = crate::decrypt::decrypt_data(data, credentials()).unwrap();
+
+        write_atomic(&path, decrypted.expose(), false, &CancelToken::new()).unwrap();
+        assert_eq!(fs::metadata(&path).unwrap().permissions().mode() & 0o777, 0o600);
+
+        // Replacing a file does not keep its permissions
+        fs::set_permissions(&path, fs::Permissions::from_mode(0o644)).unwrap();
+        write_atomic(&path, decrypted.expose(), true, &CancelToken::new()).unwrap();
+        assert_eq!(fs::metadata(&path).unwrap().permissions().mode() & 0o777, 0o600);
+
+        fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn read_info_from_file() {
+        let path = std::env::temp_dir().join(format!("ncrypt_info_test_{}", std::process::id()));
+        let credentials = crate::credentials::Credentials::new("user", "x7$Kq!9vLm#2Pz", "x7$Kq!9vLm#2Pz");
+        let params = crate::Argon2Params::very_fast();
+
+        let data = crate::encrypt::encrypt_data(params.clone(), vec![1u8; 100], credentials).unwrap();
+        fs::write(&path, &data).unwrap();
+
+        assert_eq!(read_info(&path).unwrap().argon2_params, params);
+
+        fs::write(&path, b"not an encrypted file").unwrap();
+        assert!(read_info(&path).is_err());
+
+        fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn private_dir_is_shredded() {
+        let dir = create_private_dir(true).unwrap();
+
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::PermissionsExt;
+            assert_eq!(fs::metadata(&dir).unwrap().permissions().mode() & 0o777, 0o700);
+        }
+
+        fs::write(dir.join("report.pdf"), b"secret").unwrap();
+        fs::create_dir(dir.join("backup")).unwrap();
+        fs::write(dir.join("backup").join("report.pdf~"), b"older secret").unwrap();
+
+        // Only the directories of processes that are gone are stale, no process has this id
+        let left = std::env::temp_dir().join(format!("{}4000000000-{:016x}", PRIVATE_DIR_PREFIX, OsRng.next_u64()));
+        fs::create_dir(&left).unwrap();
+
+        let stale = stale_private_dirs();
+        assert!(stale.contains(&left));
+        assert!(!stale.contains(&dir));
+        shred_dir(&left).unwrap();
+
+        shred_dir(&dir).unwrap();
+        assert!(!dir.exists());
+    }
+
+    #[test]
+    fn secure_delete_removes_file() {
+        let path = std::env::temp_dir().join(format!("ncrypt_delete_test_{}", std::process::id()));
+        fs::write(&path, vec![1u8; 3 * WRITE_CHUNK_SIZE + 5]).unwrap();
+
+        secure_delete(&path).unwrap();
+        assert!(!path.exists());
+
+        // A link is removed without touching what it points to
+        #[cfg(unix)]
+        {
+            let outside = std::env::temp_dir().join(format!("ncrypt_link_test_{}", std::process::id()));
+            fs::write(&outside, b"keep").unwrap();
+            std::os::unix::fs::symlink(&outside, &path).unwrap();
+
+            secure_delete(&path).unwrap();
+            assert!(fs::symlink_metadata(&path).is_err());
+            assert_eq!(fs::read(&outside).unwrap(), b"keep");
+
+            fs::remove_file(&outside).unwrap();
+        }
+    }
+}
@@ -0,0 +1,291 @@
+//! Random passphrases, passwords and keys
+//!
+//! Everything is drawn from the operating system's random generator and built directly in locked memory.
+
+use base64::{engine::general_purpose::STANDARD, Engine};
+use chacha20poly1305::aead::{rand_core::RngCore, OsRng};
+use std::sync::OnceLock;
+
+use super::secret::{SecretBytes, SecretString};
+use anyhow::anyhow;
+
+/// The BIP39 English wordlist, 2048 short and distinct words
+const WORDLIST: &str = include_str!("wordlist.txt");
+
+const LOWERCASE: &str = "abcdefghijklmnopqrstuvwxyz";
+const UPPERCASE: &str = "ABCDEFGHIJKLMNOPQRSTUVWXYZ";
+const DIGITS: &str = "0123456789";
+const SYMBOLS: &str = "!\"#$%&'()*+,-./:;<=>?@[\\]^_`{|}~";
+
+const HEX_DIGITS: &[u8; 16] = b"0123456789abcdef";
+
+/// Characters that are easy to confuse when read or typed
+const AMBIGUOUS: &str = "0O1lI|`'\"";
+
+/// A generated secret and how much entropy it has
+#[derive(Debug)]
+pub struct Generated {
+    pub secret: SecretString,
+
+    /// Entropy in bits, assuming the attacker knows how the secret was generated
+    pub entropy: f64,
+}
+
+/// Options for [passphrase]
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct PassphraseOptions {
+    pub words: usize,
+
+    /// Put between the words
+    pub separator: String,
+
+    /// Start every word with an uppercase letter
+    pub capitalize: bool,
+}
+
+impl Default for PassphraseOptions {
+    fn default() -> Self {
+        Self {
+            words: 6,
+            separator: "-".to_string(),
+            capitalize: false,
+        }
+    }
+}
+
+/// Options for [password]
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct PasswordOptions {
+    pub length: usize,
+    pub lowercase: bool,
+    pub uppercase: bool,
+    pub digits: bool,
+    pub symbols: bool,
+
+    /// Leave out characters like `0` and `O` that are easy to confuse
+    pub exclude_ambiguous: bool,
+}
+
+impl Default for PasswordOptions {
+    fn default() -> Self {
+        Self {
+            length: 20,
+            lowercase: true,
+            uppercase: true,
+            digits: true,
+            symbols: true,
+            exclude_ambiguous: false,
+        }
+    }
+}
+
+impl PasswordOptions {
+    /// The characters the password is made of
+    fn charset(&self) -> Vec<char> {
+        [
+            (self.lowercase, LOWERCASE),
+            (self.uppercase, UPPERCASE),
+            (self.digits, DIGITS),
+            (self.symbols, SYMBOLS),
+        ]
+        .iter()
+        .filter(|(enabled, _)| *enabled)
+        .flat_map(|(_, chars)| chars.chars())
+        .filter(|c| !self.exclude_ambiguous || !AMBIGUOUS.contains(*c))
+        .collect()
+    }
+}
+
+/// Encoding of a generated key
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum KeyEncoding {
+    Hex,
+    Base64,
+}
+
+impl KeyEncoding {
+    pub fn to_vec() -> Vec<Self> {
+        vec![Self::Hex, Self::Base64]
+    }
+}
+
+impl std::fmt::Display for KeyEncoding {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Hex => write!(f, "Hex"),
+            Self::Base64 => write!(f, "Base64"),
+        }
+    }
+}
+
+/// A passphrase of random words from the embedded wordlist
+pub fn passphrase(options: &PassphraseOptions) -> Result<Generated, anyhow::Error> {
+    if options.words == 0 {
+        return Err(anyhow!("A passphrase needs at least one word"));
+    }
+
+    let words = wordlist();
+    let mut secret = SecretString::new();
+
+    for i in 0..options.words {
+        if i > 0 {
+            secret.push_str(&options.separator);
+        }
+
+        let word = words[random_index(words.len())];
+        let mut chars = word.chars();
+
+        match chars.next() {
+            Some(first) if options.capitalize => {
+                secret.push_str(first.to_ascii_uppercase().encode_utf8(&mut [0; 4]));
+                secret.push_str(chars.as_str());
+            }
+            _ => secret.push_str(word),
+        }
+    }
+
+    Ok(Generated {
+        secret,
+        entropy: options.words as f64 * (words.len() as f64).log2(),
+    })
+}
+
+/// A password of random characters from the selected classes
+pub fn password(options: &PasswordOptions) -> Result<Generated, anyhow::Error> {
+    if options.length == 0 {
+        return Err(anyhow!("Password length must be at least 1"));
+    }
+
+    let charset = options.charset();
+    if charset.is_empty() {
+        return Err(anyhow!("Select at least one character class"));
+    }
+
+    let mut secret = SecretString::new();
+    let mut buf = [0; 4];
+
+    for _ in 0..options.length {
+        let c = charset[random_index(charset.len())];
+        secret.push_str(c.encode_utf8(&mut buf));
+    }
+
+    Ok(Generated {
+        secret,
+        entropy: options.length as f64 * (charset.len() as f64).log2(),
+    })
+}
+
+/// `len` random bytes from the system generator
+pub fn random_bytes(len: usize) -> SecretBytes {
+    let mut bytes = SecretBytes::zeroed(len);
+    OsRng.fill_bytes(bytes.expose_mut());
+    bytes
+}
+
+/// A random key of `bytes` bytes, encoded as text
+pub fn key(bytes: usize, encoding: KeyEncoding) -> Result<Generated, anyhow::Error> {
+    if bytes == 0 {
+        return Err(anyhow!("Key length must be at least 1 byte"));
+    }
+
+    let key = random_bytes(bytes);
+
+    let secret = match encoding {
+        KeyEncoding::Hex => {
+            let mut secret = SecretString::new();
+            for byte in key.expose() {
+                let digits = [HEX_DIGITS[(byte >> 4) as usize], HEX_DIGITS[(byte & 0xf) as usize]];
+                secret.push_str(std::str::from_utf8(&digits)?);
+            }
+            secret
+        }
+        KeyEncoding::Base64 => SecretString::from(STANDARD.encode(key.expose())),
+    };
+
+    Ok(Generated {
+        secret,
+        entropy: bytes as f64 * 8.0,
+    })
+}
+
+fn wordlist() -> &'static [&'static str] {
+    static WORDS: OnceLock<Vec<&'static str>> = OnceLock::new();
+
+    WORDS.get_or_init(|| WORDLIST.lines().map(str::trim).filter(|w| !w.is_empty()).collect())
+}
+
+/// A uniformly distributed index below `len`
+fn random_index(len: usize) -> usize {
+    let len = len as u64;
+
+    // Reject the top values that would make the lower indexes more likely
+    let zone = u64::MAX - (u64::MAX % len);
+
+    loop {
+        let value = OsRng.next_u64();
+        if value < zone {
+            return (value % len) as usize;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn generates_passphrases() {
+        assert_eq!(wordlist().len(), 2048);
+
+        let options = PassphraseOptions {
+            words: 5,
+            separator: " ".to_string(),
+            capitalize: true,
+        };
+
+        let generated = passphrase(&options).unwrap();
+        let words: Vec<&str> = generated.secret.expose().split(' ').collect();
+
+        assert_eq!(words.len(), 5);
+        assert!(words.iter().all(|w| w.chars().next().unwrap().is_uppercase()));
+        assert_eq!(generated.entropy, 55.0);
+    }
+
+    #[test]
+    fn generates_passwords_from_the_selected_classes() {
+        let options = PasswordOptions {
+            length: 64,
+            uppercase: false,
+            symbols: false,
+            exclude_ambiguous: true,
+            ..Default::default()
+        };
+
+        let generated = password(&options).unwrap();
+        let secret = generated.secret.expose();
+
+        assert_eq!(secret.chars().count(), 64);
+        assert!(secret.chars().all(|c| c.is_ascii_lowercase() || c.is_ascii_digit()));
+        assert!(!secret.contains(['0', '1', 'l']));
+
+        let no_classes = PasswordOptions {
+            lowercase: false,
+            uppercase: false,
+            digits: false,
+            symbols: false,
+            ..Default::default()
+        };
+        assert!(password(&no_classes).is_err());
+    }
+
+    #[test]
+    fn generates_keys() {
+        let hex = key(32, KeyEncoding::Hex).unwrap();
+        assert_eq!(hex.secret.len(), 64);
+        assert!(hex.secret.expose().chars().all(|c| c.is_ascii_hexdigit()));
+        assert_eq!(hex.entropy, 256.0);
+
+        let base64 = key(32, KeyEncoding::Base64).unwrap();
+        assert_eq!(STANDARD.decode(base64.secret.expose()).unwrap().len(), 32);
+    }
+}
@@ -1,6 +1,15 @@
 pub mod credentials;
 pub mod encrypt;
 pub mod decrypt;
+pub mod calibrate;
+pub mod secret;
+pub mod memlock;
+pub mod progress;
+pub mod file;
+pub mod strength;
+pub mod generate;
+pub mod armor;
+mod chunked;
 pub mod prelude;
 
 pub use anyhow::anyhow;
@@ -30,7 +39,7 @@ impl EncryptedInfo {
 
 
 /// Argon2 parameters
-#[derive(Clone, Debug, serde::Serialize, serde::Deserialize)]
+#[derive(Clone, Debug, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
 pub struct Argon2Params {
     pub m_cost: u32,
     pub t_cost: u32,
@@ -48,6 +57,19 @@ impl Argon2Params {
         }
     }
 
+    /// Create the Argon2 hasher for these parameters
+    pub fn argon2(&self) -> Result<Argon2<'static>, anyhow::Error> {
+        let params = argon2::Params::new(
+            self.m_cost,
+            self.t_cost,
+            self.p_cost,
+            Some(self.hash_length as usize),
+        )
+        .map_err(|e| anyhow!("Failed to create Argon2 params {:?}", e))?;
+
+        Ok(Argon2::new(argon2::Algorithm::default(), argon2::Version::default(), params))
+    }
+
     pub fn from_argon2(argon2: Argon2) -> Result<Self, anyhow::Error> {
         let hash_lenght = argon2.params().output_len();
 
@@ -66,6 +88,11 @@ impl Argon2Params {
 
 // Argon2Params Presets
 impl Argon2Params {
+
+    /// The memory Argon2 needs with these parameters in bytes
+    pub fn memory_usage(&self) -> u64 {
+        self.m_cost as u64 * 1024
+    }
     
     pub fn very_fast() -> Self {
         Self {
@@ -116,28 +143,122 @@ impl Argon2Params {
 
 
 
+/// The named Argon2 presets, `Custom` for any other parameters
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Argon2Preset {
+    VeryFast,
+    Fast,
+    Balanced,
+    Slow,
+    VerySlow,
+    Custom,
+}
+
+impl std::fmt::Display for Argon2Preset {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let name = match self {
+            Argon2Preset::VeryFast => "Very Fast",
+            Argon2Preset::Fast => "Fast",
+            Argon2Preset::Balanced => "Balanced",
+            Argon2Preset::Slow => "Slow",
+            Argon2Preset::VerySlow => "Very Slow",
+            Argon2Preset::Custom => "Custom",
+        };
+        write!(f, "{}", name)
+    }
+}
+
+impl Argon2Preset {
+    pub fn to_vec() -> Vec<Argon2Preset> {
+        vec![
+            Argon2Preset::VeryFast,
+            Argon2Preset::Fast,
+            Argon2Preset::Balanced,
+            Argon2Preset::Slow,
+            Argon2Preset::VerySlow,
+            Argon2Preset::Custom,
+        ]
+    }
+
+    /// The parameters of this preset, `None` for `Custom`
+    pub fn params(&self) -> Option<Argon2Params> {
+        match self {
+            Argon2Preset::VeryFast => Some(Argon2Params::very_fast()),
+            Argon2Preset::Fast => Some(Argon2Params::fast()),
+            Argon2Preset::Balanced => Some(Argon2Params::balanced()),
+            Argon2Preset::Slow => Some(Argon2Params::slow()),
+            Argon2Preset::VerySlow => Some(Argon2Params::very_slow()),
+            Argon2Preset::Custom => None,
+        }
+    }
+
+    /// Find the preset matching the given parameters
+    pub fn from_params(params: &Argon2Params) -> Self {
+        Self::to_vec()
+            .into_iter()
+            .find(|preset| preset.params().as_ref() == Some(params))
+            .unwrap_or(Argon2Preset::Custom)
+    }
+}
+
+
 #[cfg(test)]
 mod tests {
 
     use super::prelude::*;
 
+    /// Strong enough for the default password policy
+    const PASSWORD: &str = "x7$Kq!9vLm#2Pz";
+
     #[test]
     fn can_encrypt_decrypt() {
 
         let some_data = vec![1, 2, 3, 4, 5, 6, 7, 8, 9, 10];
-        let credentials = Credentials::new("username".to_string(), "password".to_string(), "password".to_string());
+        let credentials = || Credentials::new("username", PASSWORD, PASSWORD);
         let argon_params = Argon2Params::very_fast();
 
-        let encrypted_data = encrypt_data(argon_params, some_data.clone(), credentials.clone()).expect("Failed to encrypt data");
+        let encrypted_data = encrypt_data(argon_params, some_data.clone(), credentials()).expect("Failed to encrypt data");
 
         std::fs::write("test.ncrypt", &encrypted_data).expect("Failed to write encrypted data to file");
 
         let encrypted_data = std::fs::read("test.ncrypt").expect("Failed to read encrypted data from file");
 
-        let decrypted_data = decrypt_data(encrypted_data, credentials).expect("Failed to decrypt data");
+        // Decrypting does not need the password confirmed
+        let decrypted_data = decrypt_data(encrypted_data, Credentials::unconfirmed("username", PASSWORD)).expect("Failed to decrypt data");
 
-        assert_eq!(some_data, decrypted_data);
+        assert_eq!(some_data, decrypted_data.expose());
 
         std::fs::remove_file("test.ncrypt").expect("Failed to remove test file"); 
     }
+
+    #[test]
+    fn can_verify() {
+        let credentials = |password| Credentials::new("username", password, password);
+        let argon_params = Argon2Params::very_fast();
+
+        let mut encrypted_data = encrypt_data(argon_params, vec![42; 100], credentials(PASSWORD)).unwrap();
+
+        assert!(verify_data(&encrypted_data, credentials(PASSWORD)).is_ok());
+        assert!(verify_data(&encrypted_data, credentials("wrong")).is_err());
+        assert!(encrypt_data(Argon2Params::very_fast(), vec![1], Credentials::unconfirmed("username", PASSWORD)).is_err());
+
+        // Weak passwords are refused when encrypting
+        assert!(encrypt_data(Argon2Params::very_fast(), vec![1], credentials("password")).is_err());
+
+        let last = encrypted_data.len() - 1;
+        encrypted_data[last] ^= 1;
+        assert!(verify_data(&encrypted_data, credentials(PASSWORD)).is_err());
+    }
+
+    #[test]
+    fn preset_from_params() {
+        for preset in Argon2Preset::to_vec() {
+            if let Some(params) = preset.params() {
+                assert_eq!(Argon2Preset::from_params(&params), preset);
+            }
+        }
+
+        let params = Argon2Params::new(100_000, 7, 3, 64);
+        assert_eq!(Argon2Preset::from_params(&params), Argon2Preset::Custom);
+    }
 }
\ No newline at end of file
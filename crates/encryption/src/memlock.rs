@@ -0,0 +1,263 @@
+//! Page aligned allocations that are locked in RAM
+//!
+//! On Linux the pages are `mlock`ed so they are never written to swap and marked `MADV_DONTDUMP`
+//! so they are left out of core dumps.
+//! If locking fails, usually because `RLIMIT_MEMLOCK` is too low, the memory is still usable
+//! but the first error is recorded, see [lock_error].
+
+use std::alloc::{self, Layout};
+use std::ptr::NonNull;
+use std::io;
+use std::sync::OnceLock;
+use zeroize::Zeroize;
+
+use anyhow::anyhow;
+
+/// The first error returned by `mlock`
+static LOCK_ERROR: OnceLock<io::Error> = OnceLock::new();
+
+/// Memory needed to lock the credentials of a single operation
+const MIN_MEMLOCK: u64 = 64 * 1024;
+
+/// Whether locking memory has failed at least once
+pub fn lock_failed() -> bool {
+    LOCK_ERROR.get().is_some()
+}
+
+/// Why locking memory failed the first time, `None` if it never did
+pub fn lock_error() -> Option<&'static io::Error> {
+    LOCK_ERROR.get()
+}
+
+/// Check that enough memory can be locked to protect the credentials
+///
+/// Returns a warning explaining why memory could not be locked
+pub fn check() -> Result<(), anyhow::Error> {
+    if !cfg!(unix) {
+        return Err(anyhow!("Locking memory is not supported on this platform, secrets may be written to swap"));
+    }
+
+    match memlock_limit() {
+        Some(limit) if limit < MIN_MEMLOCK => Err(anyhow!(
+            "RLIMIT_MEMLOCK is {} kB, at least {} kB are needed to keep secrets out of swap",
+            limit / 1024,
+            MIN_MEMLOCK / 1024
+        )),
+        _ => {
+            let buf = LockedBuf::new(page_size());
+            if buf.locked {
+                return Ok(());
+            }
+
+            match lock_error() {
+                Some(e) => Err(anyhow!("Failed to lock memory ({}), secrets may be written to swap", e)),
+                None => Err(anyhow!("Failed to lock memory, secrets may be written to swap")),
+            }
+        }
+    }
+}
+
+/// Disable core dumps for this process, so secrets in memory cannot end up on disk after a crash
+///
+/// On Linux this also makes the process non-dumpable, which stops other processes of the same user
+/// from attaching to it with ptrace.
+pub fn disable_core_dumps() -> Result<(), anyhow::Error> {
+    #[cfg(unix)]
+    unsafe {
+        let limit = libc::rlimit {
+            rlim_cur: 0,
+            rlim_max: 0,
+        };
+
+        if libc::setrlimit(libc::RLIMIT_CORE, &limit) != 0 {
+            return Err(anyhow!("Failed to disable core dumps {}", std::io::Error::last_os_error()));
+        }
+    }
+
+    #[cfg(target_os = "linux")]
+    unsafe {
+        if libc::prctl(libc::PR_SET_DUMPABLE, 0, 0, 0, 0) != 0 {
+            return Err(anyhow!("Failed to mark the process as not dumpable {}", std::io::Error::last_os_error()));
+        }
+    }
+
+    Ok(())
+}
+
+/// The maximum amount of memory this process may lock in bytes, `None` if unlimited or unknown
+fn memlock_limit() -> Option<u64> {
+    #[cfg(unix)]
+    unsafe {
+        let mut limit = libc::rlimit {
+            rlim_cur: 0,
+            rlim_max: 0,
+        };
+
+        if libc::getrlimit(libc::RLIMIT_MEMLOCK, &mut limit) == 0 && limit.rlim_cur != libc::RLIM_INFINITY {
+            // rlim_t is not u64 on every platform
+            #[allow(clippy::unnecessary_cast)]
+            return Some(limit.rlim_cur as u64);
+        }
+    }
+
+    None
+}
+
+fn page_size() -> usize {
+    static PAGE_SIZE: OnceLock<usize> = OnceLock::new();
+
+    *PAGE_SIZE.get_or_init(|| {
+        #[cfg(unix)]
+        unsafe {
+            let size = libc::sysconf(libc::_SC_PAGESIZE);
+            if size > 0 {
+                return size as usize;
+            }
+        }
+
+        4096
+    })
+}
+
+/// A zero initialized, page aligned allocation that is locked in RAM when possible
+///
+/// The memory is zeroized and unlocked on drop.
+pub(crate) struct LockedBuf {
+    ptr: NonNull<u8>,
+    capacity: usize,
+    locked: bool,
+}
+
+// The buffer owns its allocation exclusively
+unsafe impl Send for LockedBuf {}
+unsafe impl Sync for LockedBuf {}
+
+impl LockedBuf {
+    /// Allocate at least `capacity` bytes, rounded up to whole pages
+    pub(crate) fn new(capacity: usize) -> Self {
+        if capacity == 0 {
+            return Self::default();
+        }
+
+        let page_size = page_size();
+        let capacity = capacity.div_ceil(page_size) * page_size;
+        let layout = Layout::from_size_align(capacity, page_size).expect("Invalid layout");
+
+        let ptr = unsafe { alloc::alloc_zeroed(layout) };
+        let ptr = match NonNull::new(ptr) {
+            Some(ptr) => ptr,
+            None => alloc::handle_alloc_error(layout),
+        };
+
+        let locked = lock(ptr, capacity);
+
+        Self {
+            ptr,
+            capacity,
+            locked,
+        }
+    }
+
+    pub(crate) fn capacity(&self) -> usize {
+        self.capacity
+    }
+
+    pub(crate) fn as_slice(&self) -> &[u8] {
+        unsafe { std::slice::from_raw_parts(self.ptr.as_ptr(), self.capacity) }
+    }
+
+    pub(crate) fn as_mut_slice(&mut self) -> &mut [u8] {
+        unsafe { std::slice::from_raw_parts_mut(self.ptr.as_ptr(), self.capacity) }
+    }
+}
+
+impl Default for LockedBuf {
+    fn default() -> Self {
+        Self {
+            ptr: NonNull::dangling(),
+            capacity: 0,
+            locked: false,
+        }
+    }
+}
+
+impl Drop for LockedBuf {
+    fn drop(&mut self) {
+        if self.capacity == 0 {
+            return;
+        }
+
+        self.as_mut_slice().zeroize();
+
+        if self.locked {
+            unlock(self.ptr, self.capacity);
+        }
+
+        let layout = Layout::from_size_align(self.capacity, page_size()).expect("Invalid layout");
+        unsafe { alloc::dealloc(self.ptr.as_ptr(), layout) };
+    }
+}
+
+#[cfg(unix)]
+fn lock(ptr: NonNull<u8>, len: usize) -> bool {
+    let addr = ptr.as_ptr() as *mut libc::c_void;
+
+    #[cfg(target_os = "linux")]
+    unsafe {
+        // A failure only means the pages may still show up in core dumps
+        libc::madvise(addr, len, libc::MADV_DONTDUMP);
+    }
+
+    if unsafe { libc::mlock(addr, len) } == 0 {
+        return true;
+    }
+
+    let _ = LOCK_ERROR.set(io::Error::last_os_error());
+    false
+}
+
+#[cfg(not(unix))]
+fn lock(_ptr: NonNull<u8>, _len: usize) -> bool {
+    let _ = LOCK_ERROR.set(io::Error::from(io::ErrorKind::Unsupported));
+    false
+}
+
+#[cfg(unix)]
+fn unlock(ptr: NonNull<u8>, len: usize) {
+    let addr = ptr.as_ptr() as *mut libc::c_void;
+
+    unsafe {
+        libc::munlock(addr, len);
+
+        // The pages go back to the allocator, let them be dumped again
+        #[cfg(target_os = "linux")]
+        libc::madvise(addr, len, libc::MADV_DODUMP);
+    }
+}
+
+#[cfg(not(unix))]
+fn unlock(_ptr: NonNull<u8>, _len: usize) {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn allocation_is_page_aligned_and_zeroed() {
+        let mut buf = LockedBuf::new(10);
+
+        assert_eq!(buf.capacity() % page_size(), 0);
+        assert_eq!(buf.as_slice().as_ptr() as usize % page_size(), 0);
+        assert!(buf.as_slice().iter().all(|b| *b == 0));
+
+        buf.as_mut_slice()[0] = 1;
+        assert_eq!(buf.as_slice()[0], 1);
+    }
+
+    #[test]
+    fn empty_allocation() {
+        let buf = LockedBuf::new(0);
+        assert_eq!(buf.capacity(), 0);
+        assert!(buf.as_slice().is_empty());
+    }
+}
@@ -1,4 +1,8 @@
 pub use crate::credentials::Credentials;
-pub use crate::encrypt::encrypt_data;
-pub use crate::decrypt::decrypt_data;
-pub use crate::{EncryptedInfo, Argon2Params};
\ No newline at end of file
+pub use crate::encrypt::{encrypt_data, encrypt_data_with};
+pub use crate::decrypt::{decrypt_data, decrypt_data_with, verify_data, verify_data_with};
+pub use crate::progress::{CancelToken, Cancelled, Progress};
+pub use crate::secret::{SecretBytes, SecretString};
+pub use crate::strength::{estimate, PasswordPolicy, Strength};
+pub use crate::calibrate::{calibrate, Benchmark, Calibration};
+pub use crate::{EncryptedInfo, Argon2Params, Argon2Preset};
\ No newline at end of file
@@ -0,0 +1,81 @@
+use std::fmt;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+
+/// What a running operation is currently doing
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Progress {
+    /// Hashing the credentials with Argon2, this step cannot report finer progress
+    ///
+    /// Argon2 cannot be interrupted either, a [CancelToken] is only checked once the key is derived.
+    DerivingKey,
+
+    /// Encrypting or decrypting the payload
+    Payload { processed: u64, total: u64 },
+}
+
+impl Progress {
+    /// Completed fraction between 0.0 and 1.0, `None` while deriving the key
+    pub fn fraction(&self) -> Option<f32> {
+        match self {
+            Progress::DerivingKey => None,
+            Progress::Payload { total: 0, .. } => Some(1.0),
+            Progress::Payload { processed, total } => Some(*processed as f32 / *total as f32),
+        }
+    }
+}
+
+impl fmt::Display for Progress {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Progress::DerivingKey => write!(f, "Deriving key..."),
+            Progress::Payload { processed, total } => {
+                write!(f, "{:.1} / {:.1} MB", *processed as f64 / 1_048_576.0, *total as f64 / 1_048_576.0)
+            }
+        }
+    }
+}
+
+/// A token to cancel a running operation from another thread
+///
+/// Cloning the token shares it, cancelling any clone cancels them all.
+#[derive(Clone, Debug, Default)]
+pub struct CancelToken {
+    cancelled: Arc<AtomicBool>,
+}
+
+impl CancelToken {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn cancel(&self) {
+        self.cancelled.store(true, Ordering::Relaxed);
+    }
+
+    pub fn is_cancelled(&self) -> bool {
+        self.cancelled.load(Ordering::Relaxed)
+    }
+
+    /// Return a [Cancelled] error if the operation has been cancelled
+    pub fn check(&self) -> Result<(), anyhow::Error> {
+        if self.is_cancelled() {
+            return Err(Cancelled.into());
+        }
+        Ok(())
+    }
+}
+
+/// The error returned when an operation is cancelled
+///
+/// Use `error.is::<Cancelled>()` to tell a cancellation apart from a failure.
+#[derive(Debug)]
+pub struct Cancelled;
+
+impl fmt::Display for Cancelled {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "Operation cancelled")
+    }
+}
+
+impl std::error::Error for Cancelled {}
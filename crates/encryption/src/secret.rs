@@ -0,0 +1,273 @@
+use std::fmt;
+use std::ops::Range;
+use zeroize::{Zeroize, ZeroizeOnDrop};
+
+use super::memlock::LockedBuf;
+
+/// Bytes holding sensitive data like keys or decrypted content
+///
+/// It cannot be cloned, `Debug` never prints the contents and the memory is zeroized on drop.
+/// The data lives in locked memory (see [memlock](crate::memlock)), when the buffer has to grow
+/// the data is moved to a new allocation and the old one is zeroized.
+#[derive(Default)]
+pub struct SecretBytes {
+    buf: LockedBuf,
+    len: usize,
+}
+
+impl SecretBytes {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn with_capacity(capacity: usize) -> Self {
+        Self {
+            buf: LockedBuf::new(capacity),
+            len: 0,
+        }
+    }
+
+    /// A buffer of `len` zeroed bytes, to be filled in place by a KDF or a cipher
+    pub fn zeroed(len: usize) -> Self {
+        Self {
+            buf: LockedBuf::new(len),
+            len,
+        }
+    }
+
+    pub fn expose(&self) -> &[u8] {
+        &self.buf.as_slice()[..self.len]
+    }
+
+    pub fn expose_mut(&mut self) -> &mut [u8] {
+        &mut self.buf.as_mut_slice()[..self.len]
+    }
+
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    pub fn extend_from_slice(&mut self, data: &[u8]) {
+        self.reserve(data.len());
+        let len = self.len;
+        self.buf.as_mut_slice()[len..len + data.len()].copy_from_slice(data);
+        self.len += data.len();
+    }
+
+    /// Insert `data` at the byte index `at`
+    pub fn insert_slice(&mut self, at: usize, data: &[u8]) {
+        assert!(at <= self.len, "index out of bounds");
+        self.reserve(data.len());
+
+        let len = self.len;
+        let buf = self.buf.as_mut_slice();
+        buf.copy_within(at..len, at + data.len());
+        buf[at..at + data.len()].copy_from_slice(data);
+        self.len += data.len();
+    }
+
+    /// Remove the given byte range, the freed bytes at the end are zeroized
+    pub fn remove_range(&mut self, range: Range<usize>) {
+        assert!(range.start <= range.end && range.end <= self.len, "range out of bounds");
+
+        let len = self.len;
+        self.buf.as_mut_slice().copy_within(range.end..len, range.start);
+        self.truncate(len - range.len());
+    }
+
+    /// Shorten the buffer to `len` bytes and zeroize the rest
+    pub fn truncate(&mut self, len: usize) {
+        if len < self.len {
+            let old_len = self.len;
+            self.buf.as_mut_slice()[len..old_len].zeroize();
+            self.len = len;
+        }
+    }
+
+    pub fn clear(&mut self) {
+        self.truncate(0);
+    }
+
+    /// Make room for `additional` bytes
+    fn reserve(&mut self, additional: usize) {
+        let required = self.len + additional;
+        if required <= self.buf.capacity() {
+            return;
+        }
+
+        let capacity = required.max(self.buf.capacity() * 2);
+        let mut buf = LockedBuf::new(capacity);
+        buf.as_mut_slice()[..self.len].copy_from_slice(self.expose());
+
+        // The old buffer is zeroized when dropped
+        self.buf = buf;
+    }
+}
+
+impl From<Vec<u8>> for SecretBytes {
+    /// Move the data into locked memory, the vector is zeroized
+    fn from(mut data: Vec<u8>) -> Self {
+        let mut secret = Self::with_capacity(data.len());
+        secret.extend_from_slice(&data);
+        data.zeroize();
+        secret
+    }
+}
+
+impl Zeroize for SecretBytes {
+    fn zeroize(&mut self) {
+        self.buf.as_mut_slice().zeroize();
+        self.len = 0;
+    }
+}
+
+impl ZeroizeOnDrop for SecretBytes {}
+
+impl fmt::Debug for SecretBytes {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "SecretBytes([REDACTED; {}])", self.len)
+    }
+}
+
+/// A string holding sensitive data like a password
+///
+/// Same guarantees as [SecretBytes], the contents are always valid UTF-8.
+#[derive(Default)]
+pub struct SecretString {
+    bytes: SecretBytes,
+}
+
+impl SecretString {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn expose(&self) -> &str {
+        // Only whole `&str` are ever inserted and only ranges on char boundaries are removed
+        std::str::from_utf8(self.bytes.expose()).expect("SecretString is always valid UTF-8")
+    }
+
+    pub fn len(&self) -> usize {
+        self.bytes.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.bytes.is_empty()
+    }
+
+    pub fn push_str(&mut self, s: &str) {
+        self.bytes.extend_from_slice(s.as_bytes());
+    }
+
+    /// Insert `s` at the byte index `at`
+    ///
+    /// Panics if `at` is not on a char boundary
+    pub fn insert_str(&mut self, at: usize, s: &str) {
+        assert!(self.expose().is_char_boundary(at), "index is not a char boundary");
+        self.bytes.insert_slice(at, s.as_bytes());
+    }
+
+    /// Remove the given byte range
+    ///
+    /// Panics if the range is not on char boundaries
+    pub fn remove_range(&mut self, range: Range<usize>) {
+        let s = self.expose();
+        assert!(
+            s.is_char_boundary(range.start) && s.is_char_boundary(range.end),
+            "range is not on char boundaries"
+        );
+        self.bytes.remove_range(range);
+    }
+
+    pub fn clear(&mut self) {
+        self.bytes.clear();
+    }
+}
+
+impl From<String> for SecretString {
+    /// Move the string into locked memory, the string is zeroized
+    fn from(s: String) -> Self {
+        Self {
+            bytes: SecretBytes::from(s.into_bytes()),
+        }
+    }
+}
+
+impl From<&str> for SecretString {
+    fn from(s: &str) -> Self {
+        let mut secret = Self::new();
+        secret.push_str(s);
+        secret
+    }
+}
+
+impl Zeroize for SecretString {
+    fn zeroize(&mut self) {
+        self.bytes.zeroize();
+    }
+}
+
+impl ZeroizeOnDrop for SecretString {}
+
+impl fmt::Debug for SecretString {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("SecretString([REDACTED])")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn debug_is_redacted() {
+        let secret = SecretString::from("hunter2");
+        assert!(!format!("{:?}", secret).contains("hunter2"));
+
+        let bytes = SecretBytes::from(b"hunter2".to_vec());
+        assert!(!format!("{:?}", bytes).contains("104"));
+    }
+
+    #[test]
+    fn edit_secret_string() {
+        let mut secret = SecretString::new();
+        secret.push_str("pass");
+        secret.push_str("wörd");
+        secret.insert_str(0, "my ");
+        assert_eq!(secret.expose(), "my passwörd");
+
+        secret.remove_range(0..3);
+        assert_eq!(secret.expose(), "passwörd");
+
+        secret.clear();
+        assert!(secret.is_empty());
+    }
+
+    #[test]
+    fn secret_bytes_grow() {
+        let mut bytes = SecretBytes::new();
+        let data = vec![7u8; 10_000];
+
+        bytes.extend_from_slice(&data[..100]);
+        bytes.extend_from_slice(&data[100..]);
+        assert_eq!(bytes.expose(), &data[..]);
+
+        bytes.insert_slice(0, &[1, 2, 3]);
+        assert_eq!(&bytes.expose()[..4], &[1, 2, 3, 7]);
+        assert_eq!(bytes.len(), 10_003);
+
+        bytes.remove_range(0..3);
+        assert_eq!(bytes.expose(), &data[..]);
+    }
+
+    #[test]
+    #[should_panic]
+    fn insert_inside_char_panics() {
+        let mut secret = SecretString::from("ö");
+        secret.insert_str(1, "a");
+    }
+}
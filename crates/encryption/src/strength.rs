@@ -0,0 +1,732 @@
+//! Password strength estimation in the style of zxcvbn
+//!
+//! The password is split into the patterns an attacker tries first: common passwords, the username,
+//! keyboard rows, sequences, repeats and years. Whatever is left is counted as brute force.
+//! The estimate is the number of guesses needed for the cheapest way to build the password out of those patterns.
+
+use std::collections::HashMap;
+use std::sync::OnceLock;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use super::credentials::Credentials;
+use anyhow::anyhow;
+
+/// Most common passwords first, the line number is the rank
+const COMMON_PASSWORDS: &str = include_str!("common_passwords.txt");
+
+/// Only the start of longer passwords is analysed, the estimate is cubic in the length
+const MAX_LENGTH: usize = 100;
+
+/// Guesses per brute forced character, low on purpose since real passwords are rarely random
+const BRUTEFORCE_CARDINALITY: f64 = 10.0;
+
+/// Extra guesses for every additional pattern, so a random password is not split into many small matches
+const PATTERN_PENALTY: f64 = 10_000.0;
+
+/// Smallest number of guesses a pattern of one or more characters can take
+const MIN_SINGLE_CHAR_GUESSES: f64 = 10.0;
+const MIN_MULTI_CHAR_GUESSES: f64 = 50.0;
+
+/// Years closer than this to the current year are all about as likely
+const MIN_YEAR_SPACE: f64 = 20.0;
+
+/// Keys on the rows of a qwerty keyboard, shifted and unshifted
+const KEYBOARD_ROWS: [&str; 8] = [
+    "`1234567890-=",
+    "qwertyuiop[]\\",
+    "asdfghjkl;'",
+    "zxcvbnm,./",
+    "~!@#$%^&*()_+",
+    "QWERTYUIOP{}|",
+    "ASDFGHJKL:\"",
+    "ZXCVBNM<>?",
+];
+
+/// Starting keys and average neighbours on a qwerty keyboard
+const KEYBOARD_STARTING_POSITIONS: f64 = 94.0;
+const KEYBOARD_AVERAGE_DEGREE: f64 = 4.6;
+
+/// The kind of pattern that makes a password guessable
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Pattern {
+    /// A common password, possibly reversed or with l33t substitutions
+    Dictionary { rank: usize, altered: bool },
+
+    /// A word from the username or another user provided input
+    UserInput,
+
+    /// Neighbouring keys on the keyboard like `qwerty` or `asdf`
+    Keyboard,
+
+    /// Characters that follow each other like `abc` or `9876`
+    Sequence,
+
+    /// A repeated character or block like `aaa` or `abcabc`
+    Repeat,
+
+    /// A year like `1987`
+    Year,
+
+    /// Anything else, guessed character by character
+    Bruteforce,
+}
+
+#[derive(Clone, Copy, Debug)]
+struct Match {
+    /// Index of the first char
+    start: usize,
+
+    /// Index after the last char
+    end: usize,
+
+    guesses: f64,
+
+    pattern: Pattern,
+}
+
+/// The estimated strength of a password
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Strength {
+    /// Number of guesses an attacker who knows these patterns needs to find the password
+    pub guesses: f64,
+
+    /// From 0 (too guessable) to 4 (very unguessable), same scale as zxcvbn
+    pub score: u8,
+
+    /// Why the password is weak, `None` for strong passwords
+    pub warning: Option<&'static str>,
+}
+
+impl Strength {
+    /// Average time to find the password at the given time per guess
+    pub fn crack_time(&self, guess_time: Duration) -> Duration {
+        let seconds = self.guesses / 2.0 * guess_time.as_secs_f64();
+        Duration::try_from_secs_f64(seconds).unwrap_or(Duration::MAX)
+    }
+
+    /// Name of the score
+    pub fn label(&self) -> &'static str {
+        match self.score {
+            0 => "Very weak",
+            1 => "Weak",
+            2 => "Fair",
+            3 => "Strong",
+            _ => "Very strong",
+        }
+    }
+}
+
+/// Estimate the strength of a password
+///
+/// ### Arguments
+///
+/// - `password` - The password to estimate
+/// - `user_inputs` - Words an attacker may know, like the username, they count as very common passwords
+pub fn estimate(password: &str, user_inputs: &[&str]) -> Strength {
+    let chars: Vec<char> = password.chars().take(MAX_LENGTH).collect();
+
+    if chars.is_empty() {
+        return Strength {
+            guesses: 1.0,
+            score: 0,
+            warning: None,
+        };
+    }
+
+    let user_inputs = user_dictionary(user_inputs);
+    let (guesses, matches) = most_guessable(&chars, &user_inputs);
+    let score = score(guesses);
+
+    let warning = if score >= 3 {
+        None
+    } else {
+        Some(warning(&matches))
+    };
+
+    Strength {
+        guesses,
+        score,
+        warning,
+    }
+}
+
+/// The minimum a password must meet to be used for encryption
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct PasswordPolicy {
+    /// Lowest accepted [Strength::score]
+    pub min_score: u8,
+
+    /// Lowest accepted number of characters
+    pub min_length: usize,
+
+    /// Passwords that are refused, compared case insensitively.
+    /// They also count as known words when estimating the strength
+    pub banned: Vec<String>,
+}
+
+impl Default for PasswordPolicy {
+    fn default() -> Self {
+        Self {
+            min_score: 3,
+            min_length: 8,
+            banned: Vec::new(),
+        }
+    }
+}
+
+impl PasswordPolicy {
+    /// A policy that accepts any non empty password
+    pub fn none() -> Self {
+        Self {
+            min_score: 0,
+            min_length: 1,
+            banned: Vec::new(),
+        }
+    }
+
+    /// Estimate the strength of the password, the username and the banned passwords count as known words
+    pub fn estimate(&self, credentials: &Credentials) -> Strength {
+        let mut user_inputs = vec![credentials.username().expose()];
+        user_inputs.extend(self.banned.iter().map(|s| s.as_str()));
+
+        estimate(credentials.password().expose(), &user_inputs)
+    }
+
+    /// Check that the password meets the policy
+    pub fn check(&self, credentials: &Credentials) -> Result<(), anyhow::Error> {
+        let password = credentials.password().expose();
+
+        if password.chars().count() < self.min_length {
+            return Err(anyhow!("Password must be at least {} characters", self.min_length));
+        }
+
+        let lowercase = password.to_lowercase();
+        if self.banned.iter().any(|banned| banned.to_lowercase() == lowercase) {
+            return Err(anyhow!("This password is not allowed"));
+        }
+
+        let strength = self.estimate(credentials);
+        if strength.score < self.min_score {
+            return Err(anyhow!(
+                "Password is too weak ({}), {}",
+                strength.label().to_lowercase(),
+                strength.warning.unwrap_or("add more words or characters").to_lowercase()
+            ));
+        }
+
+        Ok(())
+    }
+}
+
+fn score(guesses: f64) -> u8 {
+    // A little above the powers of ten so the boundaries are not decided by rounding
+    const DELTA: f64 = 5.0;
+
+    if guesses < 1e3 + DELTA {
+        0
+    } else if guesses < 1e6 + DELTA {
+        1
+    } else if guesses < 1e8 + DELTA {
+        2
+    } else if guesses < 1e10 + DELTA {
+        3
+    } else {
+        4
+    }
+}
+
+fn warning(matches: &[Match]) -> &'static str {
+    // The longest pattern is the one to explain
+    let longest = matches
+        .iter()
+        .filter(|m| m.pattern != Pattern::Bruteforce)
+        .max_by_key(|m| m.end - m.start);
+
+    let Some(longest) = longest else {
+        return "Add more words or characters";
+    };
+
+    match longest.pattern {
+        Pattern::Dictionary { rank, altered: false } if rank <= 100 && matches.len() == 1 => "This is a top 100 password",
+        Pattern::Dictionary { altered: false, .. } if matches.len() == 1 => "This is a very common password",
+        Pattern::Dictionary { altered: true, .. } => "Reversed words and substitutions like '@' for 'a' don't help much",
+        Pattern::Dictionary { .. } => "Common passwords are easy to guess even as part of a longer one",
+        Pattern::UserInput => "Avoid using your username or known words in the password",
+        Pattern::Keyboard => "Rows of keys like qwerty are easy to guess",
+        Pattern::Sequence => "Sequences like abc or 6543 are easy to guess",
+        Pattern::Repeat => "Repeats like aaa or abcabc are easy to guess",
+        Pattern::Year => "Years are easy to guess",
+        Pattern::Bruteforce => "Add more words or characters",
+    }
+}
+
+/// The cheapest way to build the password out of matches, and the number of guesses it takes
+///
+/// An attacker trying `l` patterns has to try them in any order, so a sequence of `l` matches
+/// costs `l! * product of guesses`, plus a penalty for each extra pattern.
+fn most_guessable(chars: &[char], user_inputs: &HashMap<String, usize>) -> (f64, Vec<Match>) {
+    let n = chars.len();
+    let matches = find_matches(chars, user_inputs);
+
+    let mut by_end: Vec<Vec<Match>> = vec![Vec::new(); n + 1];
+    for m in matches {
+        by_end[m.end].push(m);
+    }
+
+    // products[k][l]: the smallest product of guesses covering chars[..k] with l matches
+    let mut products = vec![vec![f64::INFINITY; n + 1]; n + 1];
+    let mut previous: Vec<Vec<Option<Match>>> = vec![vec![None; n + 1]; n + 1];
+    products[0][0] = 1.0;
+
+    for end in 1..=n {
+        for m in &by_end[end] {
+            for l in 1..=end {
+                let product = products[m.start][l - 1] * m.guesses;
+
+                if product < products[end][l] {
+                    products[end][l] = product;
+                    previous[end][l] = Some(*m);
+                }
+            }
+        }
+    }
+
+    let mut best = (f64::INFINITY, 1);
+    let mut factorial = 1.0;
+
+    for (l, product) in products[n].iter().enumerate().skip(1) {
+        factorial *= l as f64;
+
+        let guesses = factorial * product + PATTERN_PENALTY.powi(l as i32 - 1);
+        if guesses < best.0 {
+            best = (guesses, l);
+        }
+    }
+
+    let (guesses, mut l) = best;
+    let mut sequence = Vec::new();
+    let mut end = n;
+
+    while end > 0 {
+        let Some(m) = previous[end][l] else {
+            break;
+        };
+
+        sequence.push(m);
+        end = m.start;
+        l -= 1;
+    }
+
+    sequence.reverse();
+    (guesses, sequence)
+}
+
+fn find_matches(chars: &[char], user_inputs: &HashMap<String, usize>) -> Vec<Match> {
+    let mut matches = Vec::new();
+
+    bruteforce_matches(chars, &mut matches);
+    dictionary_matches(chars, user_inputs, &mut matches);
+    keyboard_matches(chars, &mut matches);
+    sequence_matches(chars, &mut matches);
+    repeat_matches(chars, user_inputs, &mut matches);
+    year_matches(chars, &mut matches);
+
+    for m in matches.iter_mut().filter(|m| m.pattern != Pattern::Bruteforce) {
+        let min = if m.end - m.start == 1 {
+            MIN_SINGLE_CHAR_GUESSES
+        } else {
+            MIN_MULTI_CHAR_GUESSES
+        };
+
+        m.guesses = m.guesses.max(min);
+    }
+
+    matches
+}
+
+fn bruteforce_matches(chars: &[char], matches: &mut Vec<Match>) {
+    for start in 0..chars.len() {
+        for end in start + 1..=chars.len() {
+            let len = end - start;
+            let min = if len == 1 {
+                MIN_SINGLE_CHAR_GUESSES
+            } else {
+                MIN_MULTI_CHAR_GUESSES
+            };
+
+            matches.push(Match {
+                start,
+                end,
+                guesses: BRUTEFORCE_CARDINALITY.powi(len as i32).max(min + 1.0),
+                pattern: Pattern::Bruteforce,
+            });
+        }
+    }
+}
+
+fn common_passwords() -> &'static HashMap<String, usize> {
+    static DICTIONARY: OnceLock<HashMap<String, usize>> = OnceLock::new();
+
+    DICTIONARY.get_or_init(|| {
+        let mut dictionary = HashMap::new();
+
+        for (i, word) in COMMON_PASSWORDS.lines().map(str::trim).filter(|w| !w.is_empty()).enumerate() {
+            dictionary.entry(word.to_lowercase()).or_insert(i + 1);
+        }
+
+        dictionary
+    })
+}
+
+/// The user inputs and their words, ranked like the most common passwords
+fn user_dictionary(user_inputs: &[&str]) -> HashMap<String, usize> {
+    let mut dictionary = HashMap::new();
+    let mut rank = 1;
+
+    for input in user_inputs {
+        let input = input.to_lowercase();
+        let words = input.split(|c: char| !c.is_alphanumeric()).filter(|w| w.chars().count() >= 3);
+
+        for word in std::iter::once(input.as_str()).chain(words) {
+            if word.chars().count() >= 3 && !dictionary.contains_key(word) {
+                dictionary.insert(word.to_string(), rank);
+                rank += 1;
+            }
+        }
+    }
+
+    dictionary
+}
+
+fn dictionary_matches(chars: &[char], user_inputs: &HashMap<String, usize>, matches: &mut Vec<Match>) {
+    let lower: Vec<char> = chars.iter().map(|c| c.to_lowercase().next().unwrap_or(*c)).collect();
+
+    for start in 0..chars.len() {
+        for end in start + 3..=chars.len() {
+            let original = &chars[start..end];
+            let word: String = lower[start..end].iter().collect();
+            let reversed: String = word.chars().rev().collect();
+            let unleeted = unleet(&word);
+
+            let candidates = [
+                (word.as_str(), 1.0, false),
+                (reversed.as_str(), 2.0, true),
+                (unleeted.0.as_str(), unleeted.1, unleeted.1 > 1.0),
+            ];
+
+            for (candidate, variations, altered) in candidates {
+                if altered && candidate == word {
+                    continue;
+                }
+
+                let guesses_for = |rank: usize| rank as f64 * variations * uppercase_variations(original);
+
+                if let Some(&rank) = user_inputs.get(candidate) {
+                    matches.push(Match {
+                        start,
+                        end,
+                        guesses: guesses_for(rank),
+                        pattern: Pattern::UserInput,
+                    });
+                }
+
+                if let Some(&rank) = common_passwords().get(candidate) {
+                    matches.push(Match {
+                        start,
+                        end,
+                        guesses: guesses_for(rank),
+                        pattern: Pattern::Dictionary { rank, altered },
+                    });
+                }
+            }
+        }
+    }
+}
+
+/// Undo the common l33t substitutions, returns the word and the extra guesses they are worth
+fn unleet(word: &str) -> (String, f64) {
+    let mut substitutions = 0;
+
+    let unleeted = word
+        .chars()
+        .map(|c| {
+            let replaced = match c {
+                '4' | '@' => 'a',
+                '8' => 'b',
+                '(' | '{' | '[' | '<' => 'c',
+                '3' => 'e',
+                '6' | '9' => 'g',
+                '1' | '!' | '|' => 'i',
+                '0' => 'o',
+                '$' | '5' => 's',
+                '7' | '+' => 't',
+                '%' => 'x',
+                '2' => 'z',
+                _ => c,
+            };
+
+            if replaced != c {
+                substitutions += 1;
+            }
+
+            replaced
+        })
+        .collect();
+
+    (unleeted, 2f64.powi(substitutions).max(2.0))
+}
+
+/// Extra guesses for the capitalisation of a word, the common ones are cheap
+fn uppercase_variations(word: &[char]) -> f64 {
+    let upper = word.iter().filter(|c| c.is_uppercase()).count();
+    let lower = word.iter().filter(|c| c.is_lowercase()).count();
+
+    if upper == 0 {
+        return 1.0;
+    }
+
+    let first_only = upper == 1 && word[0].is_uppercase();
+    let last_only = upper == 1 && word[word.len() - 1].is_uppercase();
+
+    if lower == 0 || first_only || last_only {
+        return 2.0;
+    }
+
+    (1..=upper.min(lower))
+        .map(|i| binomial(upper + lower, i))
+        .sum()
+}
+
+fn binomial(n: usize, k: usize) -> f64 {
+    (1..=k).fold(1.0, |acc, i| acc * (n + 1 - i) as f64 / i as f64)
+}
+
+fn keyboard_matches(chars: &[char], matches: &mut Vec<Match>) {
+    let position = |c: char| {
+        KEYBOARD_ROWS
+            .iter()
+            .enumerate()
+            .find_map(|(row, keys)| keys.chars().position(|k| k == c).map(|col| (row % 4, col)))
+    };
+
+    let mut start = 0;
+
+    while start < chars.len() {
+        let mut end = start + 1;
+        let mut turns = 1;
+        let mut direction = 0;
+
+        while end < chars.len() {
+            let (Some((row_a, col_a)), Some((row_b, col_b))) = (position(chars[end - 1]), position(chars[end])) else {
+                break;
+            };
+
+            let step = col_b as i32 - col_a as i32;
+            if row_a != row_b || step.abs() != 1 {
+                break;
+            }
+
+            if direction != 0 && step != direction {
+                turns += 1;
+            }
+
+            direction = step;
+            end += 1;
+        }
+
+        let len = end - start;
+        if len >= 3 {
+            matches.push(Match {
+                start,
+                end,
+                guesses: keyboard_guesses(len, turns),
+                pattern: Pattern::Keyboard,
+            });
+        }
+
+        start = if len > 1 { end - 1 } else { end };
+    }
+}
+
+/// Guesses for a keyboard pattern of `len` keys with `turns` changes of direction, the zxcvbn formula
+fn keyboard_guesses(len: usize, turns: usize) -> f64 {
+    let mut guesses = 0.0;
+
+    for i in 2..=len {
+        for j in 1..=turns.min(i - 1) {
+            guesses += binomial(i - 1, j - 1) * KEYBOARD_STARTING_POSITIONS * KEYBOARD_AVERAGE_DEGREE.powi(j as i32);
+        }
+    }
+
+    guesses
+}
+
+fn sequence_matches(chars: &[char], matches: &mut Vec<Match>) {
+    let class = |c: char| {
+        if c.is_ascii_digit() {
+            Some(0)
+        } else if c.is_ascii_lowercase() {
+            Some(1)
+        } else if c.is_ascii_uppercase() {
+            Some(2)
+        } else {
+            None
+        }
+    };
+
+    let mut start = 0;
+
+    while start + 1 < chars.len() {
+        let delta = chars[start + 1] as i32 - chars[start] as i32;
+        let mut end = start + 1;
+
+        if delta.abs() == 1 && class(chars[start]).is_some() {
+            while end < chars.len()
+                && chars[end] as i32 - chars[end - 1] as i32 == delta
+                && class(chars[end]) == class(chars[start])
+            {
+                end += 1;
+            }
+        }
+
+        let len = end - start;
+        if len >= 3 {
+            let first = chars[start];
+            let mut base = if matches!(first, 'a' | 'A' | 'z' | 'Z' | '0' | '1' | '9') {
+                4.0
+            } else if first.is_ascii_digit() {
+                10.0
+            } else {
+                26.0
+            };
+
+            if delta < 0 {
+                base *= 2.0;
+            }
+
+            matches.push(Match {
+                start,
+                end,
+                guesses: base * len as f64,
+                pattern: Pattern::Sequence,
+            });
+
+            start = end - 1;
+        } else {
+            start += 1;
+        }
+    }
+}
+
+fn repeat_matches(chars: &[char], user_inputs: &HashMap<String, usize>, matches: &mut Vec<Match>) {
+    let n = chars.len();
+
+    for start in 0..n {
+        for period in 1..=(n - start) / 2 {
+            let block = &chars[start..start + period];
+
+            // Only primitive blocks, `abab` repeated is already found as `ab` repeated
+            if (1..period).any(|p| period % p == 0 && block.chunks(p).all(|c| c == &block[..p])) {
+                continue;
+            }
+
+            // Only where the repetition starts
+            if start >= period && &chars[start - period..start] == block {
+                continue;
+            }
+
+            let mut count = 1;
+            while start + (count + 1) * period <= n && &chars[start + count * period..start + (count + 1) * period] == block {
+                count += 1;
+            }
+
+            if count < 2 || (period == 1 && count < 3) {
+                continue;
+            }
+
+            let block_guesses = if period == 1 {
+                MIN_SINGLE_CHAR_GUESSES + 1.0
+            } else {
+                most_guessable(block, user_inputs).0
+            };
+
+            matches.push(Match {
+                start,
+                end: start + count * period,
+                guesses: block_guesses * count as f64,
+                pattern: Pattern::Repeat,
+            });
+        }
+    }
+}
+
+fn year_matches(chars: &[char], matches: &mut Vec<Match>) {
+    let current_year = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| 1970.0 + (d.as_secs() / 31_556_952) as f64)
+        .unwrap_or(2024.0);
+
+    for start in 0..chars.len().saturating_sub(3) {
+        let digits: String = chars[start..start + 4].iter().collect();
+
+        let Ok(year) = digits.parse::<u32>() else {
+            continue;
+        };
+
+        if !digits.chars().all(|c| c.is_ascii_digit()) || !(1900..=2099).contains(&year) {
+            continue;
+        }
+
+        matches.push(Match {
+            start,
+            end: start + 4,
+            guesses: (year as f64 - current_year).abs().max(MIN_YEAR_SPACE),
+            pattern: Pattern::Year,
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn guessable_passwords_are_weak() {
+        for password in ["password", "P@ssw0rd", "drowssap", "abcdefgh", "qwertyuiop", "aaaaaaaaaaaa", "1987", "abcabcabcabc"] {
+            let strength = estimate(password, &[]);
+            assert!(strength.score <= 1, "{} scored {}", password, strength.score);
+            assert!(strength.warning.is_some());
+        }
+
+        // The username is known to the attacker
+        assert!(estimate("Elizabeth_Winthrop", &["elizabeth_winthrop"]).score <= 1);
+        assert!(estimate("Elizabeth_Winthrop", &[]).score >= 3);
+    }
+
+    #[test]
+    fn random_passwords_are_strong() {
+        for password in ["x7$Kq!9vLm#2Pz", "tMb2-Rw9f-Hz4q", "correct horse battery staple"] {
+            let strength = estimate(password, &[]);
+            assert_eq!(strength.score, 4, "{}", password);
+            assert_eq!(strength.warning, None);
+        }
+    }
+
+    #[test]
+    fn policy() {
+        let policy = PasswordPolicy {
+            banned: vec!["CompanyName2024".to_string()],
+            ..Default::default()
+        };
+
+        let check = |password| policy.check(&Credentials::new("user", password, password));
+
+        assert!(check("x7$Kq!9vLm#2Pz").is_ok());
+        assert!(check("x7$Kq!").is_err());
+        assert!(check("password123").is_err());
+        assert!(check("companyname2024").is_err());
+        assert!(check("CompanyName2024!").is_err());
+
+        assert!(PasswordPolicy::none().check(&Credentials::new("user", "a", "a")).is_ok());
+    }
+}
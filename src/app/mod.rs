@@ -12,13 +12,12 @@ use eframe::{
         Ui,
         Visuals,
         Style,
+        ViewportCommand,
     },
     CreationContext,
 };
-use encryption::zeroize::Zeroize;
-
-use std::time::Duration;
-use crate::gui::{ central_panel, left_panel, right_panel, GUI };
+use crate::gui::{ central_panel, drop, left_panel, right_panel, GUI };
+use crate::settings::{ Settings, Theme, WindowGeometry };
 use window::window_frame;
 
 pub mod window;
@@ -26,27 +25,36 @@ pub mod window;
 /// The main application struct
 pub struct NCryptApp {
     pub gui: GUI,
+
+    /// The theme the style was last set with
+    theme: Theme,
 }
 
 impl NCryptApp {
     pub fn new(cc: &CreationContext) -> Self {
-        let ctx = cc.egui_ctx.clone();
-        std::thread::spawn(move || request_repaint(ctx));
+        let settings = Settings::load(cc.storage);
+
+        if let Some(window) = settings.window.filter(|_| settings.remember_window) {
+            restore_window(&cc.egui_ctx, window);
+        }
 
+        let theme = settings.theme;
         let app = Self {
-            gui: GUI::new(),
+            gui: GUI::new(cc.egui_ctx.clone(), settings),
+            theme,
         };
 
-        Self::set_style(&cc.egui_ctx);
+        Self::set_style(&cc.egui_ctx, theme);
 
         app
     }
 
-    fn set_style(ctx: &Context) {
+    fn set_style(ctx: &Context, theme: Theme) {
         let visuals = Visuals::dark();
-        let mut style = Style::default();
-
-        style.visuals = visuals;
+        let mut style = Style {
+            visuals,
+            ..Default::default()
+        };
 
         // Bg color of widgets like TextEdit
         style.visuals.extreme_bg_color = Color32::TRANSPARENT;
@@ -60,6 +68,10 @@ impl NCryptApp {
         // Widgets Stroke
         style.visuals.widgets.inactive.bg_stroke = Stroke::new(1.0, Color32::WHITE);
 
+        // Bg color of the panels and windows
+        style.visuals.panel_fill = theme.background();
+        style.visuals.window_fill = theme.background();
+
         ctx.set_style(style)
     }
 }
@@ -70,17 +82,33 @@ impl eframe::App for NCryptApp {
     }
 
     fn update(&mut self, ctx: &Context, _frame: &mut eframe::Frame) {
+        self.gui.poll_jobs();
+        self.gui.handle_dropped_files(ctx);
+        self.gui.guard_exit(ctx);
+        self.gui.auto_lock(ctx);
+        self.gui.clear_clipboard(ctx);
+
+        if self.gui.settings.theme != self.theme {
+            self.theme = self.gui.settings.theme;
+            Self::set_style(ctx, self.theme);
+        }
+
+        self.gui.settings.window = match self.gui.settings.remember_window {
+            true => window_geometry(ctx).or(self.gui.settings.window),
+            false => None,
+        };
+
         window_frame(ctx, "nCrypt 1.0.0", |ui| {
             apply_visuals(ui);
 
-            let frame = Frame::none().fill(Color32::from_hex("#212529").unwrap());
+            let frame = Frame::none().fill(ui.visuals().panel_fill);
 
             // UI that belongs to the right panel
             SidePanel::right("right_panel")
                 .min_width(50.0)
                 .resizable(false)
                 .frame(
-                    frame.clone().inner_margin(Margin { left: 20.0, right: 0.0, top: 100.0, bottom: 0.0 })
+                    frame.inner_margin(Margin { left: 20.0, right: 0.0, top: 100.0, bottom: 0.0 })
                 )
                 .show_inside(ui, |ui| {
                     right_panel::show(ui, &mut self.gui);
@@ -92,7 +120,7 @@ impl eframe::App for NCryptApp {
                 .min_width(50.0)
                 .resizable(false)
                 .frame(
-                    frame.clone().inner_margin(Margin { left: 0.0, right: 0.0, top: 100.0, bottom: 0.0 })
+                    frame.inner_margin(Margin { left: 0.0, right: 0.0, top: 100.0, bottom: 0.0 })
                 )
                 .show_inside(ui, |ui| {
                     left_panel::show(ui, &mut self.gui);
@@ -107,27 +135,48 @@ impl eframe::App for NCryptApp {
                     central_panel::show(ui, &mut self.gui);
                 });
         });
+
+        drop::hover_overlay(ctx);
+    }
+
+    fn save(&mut self, storage: &mut dyn eframe::Storage) {
+        self.gui.settings().save(storage);
+    }
+
+    /// The egui memory holds the state of text fields, only the settings are saved
+    fn persist_egui_memory(&self) -> bool {
+        false
     }
 
     fn on_exit(&mut self, _gl: Option<&eframe::glow::Context>) {
-        self.gui.encryption_ui.credentials.destroy();
-        self.gui.text_hashing_ui.input_text.zeroize();
-        self.gui.text_hashing_ui.output_hash.zeroize();
+        self.gui.encryption_ui.sessions.close_all();
+        self.gui.wipe_secrets();
     }
 }
 
-/// Request repaint every 32ms (30 FPS) only if the Viewport is not minimized.
-fn request_repaint(ctx: Context) {
-    let duration = Duration::from_millis(32);
-
-    loop {
-        let is_minimized = ctx.input(|i| i.viewport().minimized.unwrap_or(false));
+/// Where the window is and how big it is, `None` if the platform doesn't say
+fn window_geometry(ctx: &Context) -> Option<WindowGeometry> {
+    ctx.input(|i| {
+        let viewport = i.viewport();
 
-        if !is_minimized {
-            ctx.request_repaint();
+        // Don't remember the size of a maximized window
+        if viewport.maximized.unwrap_or(false) {
+            return None;
         }
-        std::thread::sleep(duration);
-    }
+
+        let position = viewport.outer_rect?.min;
+        let size = viewport.inner_rect?.size();
+
+        Some(WindowGeometry { position: [position.x, position.y], size: [size.x, size.y] })
+    })
+}
+
+fn restore_window(ctx: &Context, window: WindowGeometry) {
+    let [x, y] = window.position;
+    let [width, height] = window.size;
+
+    ctx.send_viewport_cmd(ViewportCommand::OuterPosition([x, y].into()));
+    ctx.send_viewport_cmd(ViewportCommand::InnerSize([width.max(860.0), height.max(500.0)].into()));
 }
 
 pub fn apply_visuals(ui: &mut Ui) {
@@ -5,7 +5,7 @@ use eframe::egui::{self, CentralPanel, Color32, Margin, Stroke, UiBuilder, Viewp
 pub fn window_frame(ctx: &egui::Context, title: &str, add_contents: impl FnOnce(&mut egui::Ui)) {
     
     let panel_frame = egui::Frame {
-        fill: Color32::from_hex("#212529").unwrap(),
+        fill: ctx.style().visuals.panel_fill,
         rounding: 10.0.into(),
         stroke: ctx.style().visuals.widgets.noninteractive.fg_stroke,
         outer_margin: 0.5.into(), // so the stroke is within the bounds
@@ -0,0 +1,182 @@
+//! Edit an encrypted file in the app the system opens it with
+//!
+//! The file is decrypted to a private directory, in memory where the system has a tmpfs, and
+//! encrypted back to the original file every time the other app saves it. Closing the session
+//! shreds the decrypted copy.
+
+use encryption::{ file, prelude::* };
+use std::fs::{ self, OpenOptions };
+use std::io::Write;
+use std::path::{ Path, PathBuf };
+use std::time::SystemTime;
+use anyhow::anyhow;
+use crate::hashing;
+
+/// A decrypted copy of an encrypted file, watched for changes
+pub struct EditSession {
+    /// The encrypted file, replaced when the copy changes
+    pub encrypted: PathBuf,
+
+    /// The private directory the copy is in, apps may add lock and backup files next to it
+    pub dir: PathBuf,
+
+    /// The decrypted copy, named like the original file so the right app opens it
+    pub path: PathBuf,
+
+    /// Confirmed, the file was already encrypted with them
+    credentials: Credentials,
+
+    /// Parameters of the encrypted file, kept when encrypting it back
+    argon_params: Argon2Params,
+
+    /// Length and modification time of the copy when it was last encrypted
+    stamp: Option<(u64, SystemTime)>,
+
+    /// Hash of the content last encrypted, saving without changes does not encrypt again
+    hash: blake3::Hash,
+}
+
+impl EditSession {
+    /// Decrypt `encrypted` to a new private directory, on disk only if `allow_disk` is set
+    pub fn start(
+        encrypted: &Path,
+        credentials: Credentials,
+        allow_disk: bool,
+        progress: &mut dyn FnMut(Progress),
+        cancel: &CancelToken,
+    ) -> Result<Self, anyhow::Error> {
+        let argon_params = file::read_info(encrypted)?.argon2_params;
+        let data = fs::read(encrypted).map_err(|e| anyhow!("Failed to read {} {}", encrypted.display(), e))?;
+
+        let mut credentials = credentials;
+        credentials.copy_passwd_to_confirm();
+
+        let decrypted_data = decrypt_data_with(data, credentials.duplicate(), progress, cancel)
+            .map_err(|e| e.context("Failed to decrypt file"))?;
+
+        let dir = file::create_private_dir(allow_disk)?;
+        let path = file::decrypted_path(encrypted, Some(&dir))?;
+
+        if let Err(e) = write_private(&path, decrypted_data.expose()) {
+            let _ = file::shred_dir(&dir);
+            return Err(e);
+        }
+
+        Ok(Self {
+            encrypted: encrypted.to_path_buf(),
+            dir,
+            stamp: stamp(&path),
+            path,
+            credentials,
+            argon_params,
+            hash: hashing::fingerprint(decrypted_data.expose()),
+        })
+    }
+
+    /// Whether the copy was saved since it was last encrypted, only compares the length and
+    /// modification time so it is cheap enough to call often
+    pub fn is_modified(&self) -> bool {
+        stamp(&self.path) != self.stamp
+    }
+
+    /// Encrypt the copy back to the original file if its content changed, returns whether it did
+    pub fn sync(&mut self, progress: &mut dyn FnMut(Progress), cancel: &CancelToken) -> Result<bool, anyhow::Error> {
+        // Read the stamp first, a save while encrypting is seen on the next check
+        let stamp = stamp(&self.path);
+        let data = SecretBytes::from(fs::read(&self.path).map_err(|e| anyhow!("Failed to read {} {}", self.path.display(), e))?);
+        let hash = hashing::fingerprint(data.expose());
+
+        if hash == self.hash {
+            self.stamp = stamp;
+            return Ok(false);
+        }
+
+        let encrypted_data = encrypt_data_with(
+            self.argon_params.clone(),
+            data.expose().to_vec(),
+            self.credentials.duplicate(),
+            &PasswordPolicy::none(),
+            progress,
+            cancel,
+        )
+        .map_err(|e| e.context("Failed to encrypt file"))?;
+
+        file::write_atomic(&self.encrypted, &encrypted_data, true, cancel)
+            .map_err(|e| e.context("Failed to save the encrypted file"))?;
+
+        self.stamp = stamp;
+        self.hash = hash;
+
+        Ok(true)
+    }
+
+    /// Encrypt the last changes, then shred the private directory
+    ///
+    /// The directory is shredded even if encrypting fails, the error is returned
+    pub fn close(&mut self, progress: &mut dyn FnMut(Progress), cancel: &CancelToken) -> Result<(), anyhow::Error> {
+        let synced = self.sync(progress, cancel);
+        let shredded = file::shred_dir(&self.dir);
+        synced.and(shredded)
+    }
+}
+
+/// Write a file only the current user can read
+fn write_private(path: &Path, data: &[u8]) -> Result<(), anyhow::Error> {
+    let mut options = OpenOptions::new();
+    options.write(true).create_new(true);
+
+    #[cfg(unix)]
+    std::os::unix::fs::OpenOptionsExt::mode(&mut options, 0o600);
+
+    let mut file = options.open(path).map_err(|e| anyhow!("Failed to create {} {}", path.display(), e))?;
+    file.write_all(data)?;
+    file.sync_all()?;
+
+    Ok(())
+}
+
+fn stamp(path: &Path) -> Option<(u64, SystemTime)> {
+    let metadata = fs::metadata(path).ok()?;
+    Some((metadata.len(), metadata.modified().ok()?))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn edits_are_encrypted_back() {
+        let dir = std::env::temp_dir().join(format!("ncrypt_edit_test_{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let encrypted = dir.join("notes.txt.ncrypt");
+
+        let password = "x7$Kq!9vLm#2Pz";
+        let data = encrypt_data(Argon2Params::very_fast(), b"first".to_vec(), Credentials::new("user", password, password)).unwrap();
+        fs::write(&encrypted, data).unwrap();
+
+        let cancel = CancelToken::new();
+        let mut session = EditSession::start(&encrypted, Credentials::unconfirmed("user", password), true, &mut |_| {}, &cancel).unwrap();
+        assert_eq!(session.path, session.dir.join("notes.txt"));
+        assert_eq!(fs::read(&session.path).unwrap(), b"first");
+        assert!(!session.sync(&mut |_| {}, &cancel).unwrap());
+
+        fs::write(&session.path, b"second, longer").unwrap();
+        assert!(session.is_modified());
+        assert!(session.sync(&mut |_| {}, &cancel).unwrap());
+        assert!(!session.is_modified());
+
+        let decrypted = decrypt_data(fs::read(&encrypted).unwrap(), Credentials::unconfirmed("user", password)).unwrap();
+        assert_eq!(decrypted.expose(), b"second, longer");
+
+        // Changes made just before closing are kept
+        fs::write(&session.path, b"third").unwrap();
+        let private_dir = session.dir.clone();
+        session.close(&mut |_| {}, &cancel).unwrap();
+        assert!(!private_dir.exists());
+
+        let decrypted = decrypt_data(fs::read(&encrypted).unwrap(), Credentials::unconfirmed("user", password)).unwrap();
+        assert_eq!(decrypted.expose(), b"third");
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
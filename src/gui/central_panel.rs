@@ -1,4 +1,4 @@
-use eframe::egui::{ vec2, Align2, Color32, Frame, Ui, Vec2b, Window };
+use eframe::egui::{ vec2, Align2, Color32, Frame, ScrollArea, Ui, Vec2b, Window };
 use std::sync::{ Arc, RwLock };
 use super::{ui::*, GUI};
 
@@ -11,17 +11,19 @@ pub fn show(ui: &mut Ui, gui: &mut GUI) {
     ui.add_space(90.0);
 
     ui.vertical_centered(|ui| {
-    gui.encryption_ui.show(ui);
+    gui.encryption_ui.show(ui, &gui.jobs);
+    gui.text_encryption_ui.show(ui, &gui.encryption_ui.argon_params);
     gui.text_hashing_ui.show(ui);
+    gui.notes_ui.show(ui, &gui.jobs, &gui.encryption_ui.argon_params, &gui.encryption_ui.policy);
+    gui.settings_ui.show(ui, &mut gui.settings, &mut gui.encryption_ui);
     });
 
 });
 
+    gui.jobs_ui.show(ui, &mut gui.jobs);
     window_msg(ui, gui.pop_msg.clone());
 }
 
-
-
 /// Show a popup window with a message
 pub fn window_msg(ui: &mut Ui, pop_msg: Arc<RwLock<WindowMsg>>) {
     let msg;
@@ -39,14 +41,17 @@ pub fn window_msg(ui: &mut Ui, pop_msg: Arc<RwLock<WindowMsg>>) {
         .anchor(Align2::CENTER_CENTER, [0.0, 0.0])
         .collapsible(false)
         .resizable(Vec2b::new(false, false))
-        .frame(Frame::window(&ui.style().clone()).fill(Color32::from_hex("#212529").unwrap()))
+        .frame(Frame::window(&ui.style().clone()).fill(ui.visuals().window_fill))
         .show(ui.ctx(), |ui| {
             ui.set_min_size(vec2(300.0, 150.0));
 
             ui.vertical_centered(|ui| {
                 ui.spacing_mut().item_spacing.y = 15.0;
 
-                ui.label(rich_text(msg).size(14.0));
+                // Long messages like a verification summary scroll instead of growing the window
+                ScrollArea::vertical().max_height(400.0).show(ui, |ui| {
+                    ui.label(rich_text(msg).size(14.0));
+                });
 
                 let button = button(rich_text("Ok").color(Color32::BLACK));
                 
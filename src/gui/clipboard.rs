@@ -0,0 +1,125 @@
+use anyhow::anyhow;
+use encryption::zeroize::Zeroize;
+use std::sync::{ Arc, Mutex };
+use std::time::{ Duration, Instant };
+use crate::hashing;
+
+/// The system clipboard, cleans up after the secrets copied or pasted through it
+///
+/// Only a [fingerprint](hashing::fingerprint) of the secret is kept. When the timeout ends the clipboard is cleared if it
+/// still holds the secret, so whatever the user copied since is left alone.
+#[derive(Clone)]
+pub struct Clipboard {
+    state: Arc<Mutex<State>>,
+}
+
+#[derive(Default)]
+struct State {
+    /// Opened on first use, on Linux the copied text is served for as long as it lives
+    system: Option<arboard::Clipboard>,
+
+    secret: Option<Secret>,
+}
+
+struct Secret {
+    hash: blake3::Hash,
+    since: Instant,
+}
+
+impl Default for Clipboard {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Clipboard {
+    pub fn new() -> Self {
+        Self { state: Arc::new(Mutex::new(State::default())) }
+    }
+
+    /// Copy text that is not secret, like a digest or an encrypted message
+    pub fn copy(&self, text: &str) -> Result<(), anyhow::Error> {
+        let mut state = self.state.lock().unwrap();
+        set_text(&mut state, text)?;
+        state.secret = None;
+        Ok(())
+    }
+
+    /// Copy a secret, it is cleared after the timeout
+    pub fn copy_secret(&self, text: &str) -> Result<(), anyhow::Error> {
+        let mut state = self.state.lock().unwrap();
+        set_text(&mut state, text)?;
+        state.secret = Some(Secret { hash: hashing::fingerprint(text.as_bytes()), since: Instant::now() });
+        Ok(())
+    }
+
+    pub fn paste(&self) -> Result<String, anyhow::Error> {
+        let mut state = self.state.lock().unwrap();
+        system(&mut state)?.get_text().map_err(|e| anyhow!("Failed to paste {}", e))
+    }
+
+    /// Paste into a secret field, the clipboard is cleared after the timeout like a copied secret
+    pub fn paste_secret(&self) -> Result<String, anyhow::Error> {
+        let text = self.paste()?;
+        self.state.lock().unwrap().secret = Some(Secret { hash: hashing::fingerprint(text.as_bytes()), since: Instant::now() });
+        Ok(text)
+    }
+
+    /// Time left before the secret is cleared, `None` if there is nothing to clear
+    pub fn remaining(&self, timeout: Option<Duration>) -> Option<Duration> {
+        let state = self.state.lock().unwrap();
+        let secret = state.secret.as_ref()?;
+        Some(timeout?.saturating_sub(secret.since.elapsed()))
+    }
+
+    /// Clear the secret once the `timeout` is over, returns the time left
+    ///
+    /// `None` as the timeout keeps the secret in the clipboard
+    pub fn tick(&self, timeout: Option<Duration>) -> Option<Duration> {
+        let remaining = self.remaining(timeout)?;
+
+        if remaining.is_zero() {
+            self.clear_secret();
+            return None;
+        }
+
+        Some(remaining)
+    }
+
+    /// Clear the clipboard now if it still holds the secret
+    pub fn clear_secret(&self) {
+        let mut state = self.state.lock().unwrap();
+
+        let Some(secret) = state.secret.take() else {
+            return;
+        };
+
+        let Ok(clipboard) = system(&mut state) else {
+            return;
+        };
+
+        // Something else was copied since, or the clipboard holds an image
+        let Ok(mut text) = clipboard.get_text() else {
+            return;
+        };
+
+        if hashing::fingerprint(text.as_bytes()) == secret.hash {
+            let _ = clipboard.clear();
+        }
+
+        text.zeroize();
+    }
+}
+
+fn system(state: &mut State) -> Result<&mut arboard::Clipboard, anyhow::Error> {
+    if state.system.is_none() {
+        let clipboard = arboard::Clipboard::new().map_err(|e| anyhow!("Failed to open the clipboard {}", e))?;
+        state.system = Some(clipboard);
+    }
+
+    Ok(state.system.as_mut().unwrap())
+}
+
+fn set_text(state: &mut State, text: &str) -> Result<(), anyhow::Error> {
+    system(state)?.set_text(text).map_err(|e| anyhow!("Failed to copy {}", e))
+}
@@ -0,0 +1,50 @@
+use eframe::egui::{ Align2, Color32, Context, FontId, Id, LayerId, Order };
+use std::path::PathBuf;
+use crate::hashing::collect_files;
+
+/// The files dropped on the window this frame, folders are replaced by the files they contain
+///
+/// Returns the files and the folders that could not be read
+pub fn dropped_files(ctx: &Context) -> (Vec<PathBuf>, Vec<anyhow::Error>) {
+    let paths: Vec<PathBuf> = ctx.input(|i| {
+        i.raw.dropped_files.iter().filter_map(|file| file.path.clone()).collect()
+    });
+
+    let mut files = Vec::new();
+    let mut errors = Vec::new();
+
+    for path in paths {
+        if path.is_dir() {
+            match collect_files(&path) {
+                Ok(found) => files.extend(found),
+                Err(e) => errors.push(e),
+            }
+        } else {
+            files.push(path);
+        }
+    }
+
+    (files, errors)
+}
+
+/// Darken the window and say what dropping does while files are dragged over it
+pub fn hover_overlay(ctx: &Context) {
+    let hovered = ctx.input(|i| i.raw.hovered_files.len());
+
+    if hovered == 0 {
+        return;
+    }
+
+    let painter = ctx.layer_painter(LayerId::new(Order::Foreground, Id::new("drop_overlay")));
+    let rect = ctx.screen_rect();
+
+    painter.rect_filled(rect, 10.0, Color32::from_black_alpha(200));
+
+    painter.text(
+        rect.center(),
+        Align2::CENTER_CENTER,
+        "Drop to add to the queue\n.ncrypt files are decrypted, everything else is encrypted",
+        FontId::proportional(18.0),
+        Color32::WHITE,
+    );
+}
@@ -1,5 +1,5 @@
 use eframe::egui::{Color32, Stroke, Ui};
-use super::{GUI, ui::{button, rich_text}};
+use super::{GUI, lock::{self, PANIC_WIPE}, ui::{button, rich_text}};
 
 pub fn show(ui: &mut Ui, gui: &mut GUI) {
     ui.set_max_width(120.0);
@@ -21,14 +21,88 @@ pub fn show(ui: &mut Ui, gui: &mut GUI) {
 
         if ui.add(button(rich_text("Encryption").size(16.0))).clicked() {
             gui.text_hashing_ui.open = false;
+            gui.text_encryption_ui.open = false;
             gui.encryption_ui.open = true;
+            gui.notes_ui.open = false;
+            gui.settings_ui.open = false;
+        }
+
+        if ui.add(button(rich_text("Text Encryption").size(16.0))).clicked() {
+            gui.text_hashing_ui.open = false;
+            gui.text_encryption_ui.open = true;
+            gui.encryption_ui.open = false;
+            gui.notes_ui.open = false;
+            gui.settings_ui.open = false;
         }
 
         if ui.add(button(rich_text("Text Hashing").size(16.0))).clicked() {
             gui.text_hashing_ui.open = true;
+            gui.text_encryption_ui.open = false;
             gui.encryption_ui.open = false;
+            gui.notes_ui.open = false;
+            gui.settings_ui.open = false;
+        }
+
+        if ui.add(button(rich_text("Secure Notes").size(16.0))).clicked() {
+            gui.text_hashing_ui.open = false;
+            gui.text_encryption_ui.open = false;
+            gui.encryption_ui.open = false;
+            gui.notes_ui.open = true;
+            gui.settings_ui.open = false;
+        }
+
+        if ui.add(button(rich_text("Settings").size(16.0))).clicked() {
+            gui.text_hashing_ui.open = false;
+            gui.text_encryption_ui.open = false;
+            gui.encryption_ui.open = false;
+            gui.notes_ui.open = false;
+            gui.settings_ui.open = true;
+        }
+
+        let active = gui.jobs.active();
+        let text = if active > 0 { format!("Jobs ({})", active) } else { "Jobs".to_string() };
+
+        if ui.add(button(rich_text(text).size(16.0))).clicked() {
+            gui.jobs_ui.open = !gui.jobs_ui.open;
         }
 
+        secrets_indicator(ui, gui);
+        clipboard_countdown(ui, gui);
+
     });
 });
 }
+
+/// Shown while secrets are in memory, clicking it wipes them
+fn secrets_indicator(ui: &mut Ui, gui: &mut GUI) {
+    if !gui.has_secrets() {
+        return;
+    }
+
+    let shortcut = ui.ctx().format_shortcut(&PANIC_WIPE);
+    let mut hover = format!("Passwords or secret text are in memory\nClick to wipe them, {} also cancels the jobs", shortcut);
+
+    if let Some(timeout) = lock::timeout(&gui.settings) {
+        hover.push_str(&format!("\nWiped after {} min without activity", timeout.as_secs() / 60));
+    }
+
+    let text = rich_text("Secrets Loaded").size(14.0).color(Color32::from_rgb(255, 200, 0));
+
+    if ui.add(button(text)).on_hover_text(hover).clicked() {
+        gui.wipe_secrets();
+    }
+}
+
+/// Time left before a copied secret is cleared from the clipboard, clicking it clears it now
+fn clipboard_countdown(ui: &mut Ui, gui: &mut GUI) {
+    let Some(remaining) = gui.clipboard.remaining(gui.clipboard_timeout()) else {
+        return;
+    };
+
+    let text = rich_text(format!("Clipboard: {} s", remaining.as_secs_f32().ceil())).size(14.0);
+    let hover = "A secret is in the clipboard, it is cleared unless something else was copied since\nClick to clear it now";
+
+    if ui.add(button(text)).on_hover_text(hover).clicked() {
+        gui.clipboard.clear_secret();
+    }
+}
@@ -0,0 +1,97 @@
+use eframe::egui::{ Context, Key, KeyboardShortcut, Modifiers };
+use std::fmt;
+use std::time::{ Duration, Instant };
+use crate::settings::Settings;
+
+/// Wipes every secret and cancels the jobs at once
+pub const PANIC_WIPE: KeyboardShortcut = KeyboardShortcut::new(Modifiers::COMMAND.plus(Modifiers::SHIFT), Key::L);
+
+/// Why the secrets were wiped
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum LockReason {
+    /// No input for this long
+    Idle(Duration),
+    Minimized,
+    FocusLost,
+    Panic,
+}
+
+impl fmt::Display for LockReason {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            LockReason::Idle(timeout) => write!(f, "No activity for {} min", timeout.as_secs() / 60),
+            LockReason::Minimized => write!(f, "The window was minimized"),
+            LockReason::FocusLost => write!(f, "The window lost focus"),
+            LockReason::Panic => write!(f, "Panic wipe"),
+        }
+    }
+}
+
+/// Decides when the secrets typed in the tabs must be wiped
+pub struct AutoLock {
+    last_activity: Instant,
+}
+
+impl Default for AutoLock {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl AutoLock {
+    pub fn new() -> Self {
+        Self { last_activity: Instant::now() }
+    }
+
+    /// Why the secrets must be wiped now, `None` to keep them
+    ///
+    /// Called every frame. While `secrets` are loaded a repaint is scheduled for the end of
+    /// the idle timeout, the app does not redraw on its own otherwise.
+    pub fn check(&mut self, ctx: &Context, settings: &Settings, secrets: bool) -> Option<LockReason> {
+        let (active, minimized, focused, panic) = ctx.input_mut(|i| {
+            (
+                !i.events.is_empty() || i.pointer.is_moving(),
+                i.viewport().minimized.unwrap_or(false),
+                i.viewport().focused.unwrap_or(true),
+                i.consume_shortcut(&PANIC_WIPE),
+            )
+        });
+
+        if active {
+            self.last_activity = Instant::now();
+        }
+
+        if panic {
+            return Some(LockReason::Panic);
+        }
+
+        if !secrets {
+            return None;
+        }
+
+        if settings.wipe_on_minimize {
+            if minimized {
+                return Some(LockReason::Minimized);
+            }
+
+            if !focused {
+                return Some(LockReason::FocusLost);
+            }
+        }
+
+        let timeout = timeout(settings)?;
+        let idle = self.last_activity.elapsed();
+
+        if idle >= timeout {
+            return Some(LockReason::Idle(timeout));
+        }
+
+        ctx.request_repaint_after(timeout - idle);
+        None
+    }
+}
+
+/// The idle timeout, `None` if the secrets are kept until the app closes
+pub fn timeout(settings: &Settings) -> Option<Duration> {
+    (settings.lock_timeout > 0).then(|| Duration::from_secs(settings.lock_timeout * 60))
+}
@@ -2,31 +2,224 @@ pub mod ui;
 pub mod central_panel;
 pub mod left_panel;
 pub mod right_panel;
+pub mod repaint;
+pub mod drop;
+pub mod clipboard;
+pub mod lock;
 
-use eframe::egui::Ui;
+use eframe::egui::{Context, Ui, ViewportCommand};
 use std::sync::{Arc, RwLock};
-use ui::{WindowMsg, file_encryption::FileEncryptionUi, text_hashing::TextHashingUi};
+use clipboard::Clipboard;
+use lock::{AutoLock, LockReason};
+use std::time::Duration;
+use repaint::Repaint;
+use crate::jobs::{JobManager, JobState, WORKERS};
+use crate::settings::Settings;
+use ui::{WindowMsg, file_encryption::FileEncryptionUi, jobs::JobsUi, notes::{GuardedAction, NotesUi}, settings::SettingsUi, text_encryption::TextEncryptionUi, text_hashing::TextHashingUi};
 
 
 pub struct GUI {
 
     pub encryption_ui: FileEncryptionUi,
 
+    pub text_encryption_ui: TextEncryptionUi,
+
     pub text_hashing_ui: TextHashingUi,
 
-    pub pop_msg: Arc<RwLock<WindowMsg>>
+    pub notes_ui: NotesUi,
+
+    pub jobs_ui: JobsUi,
+
+    pub settings_ui: SettingsUi,
+
+    /// Saved when the app closes, the encryption settings are kept in [FileEncryptionUi] until then
+    pub settings: Settings,
+
+    /// Messages about what the user just did, the outcome of jobs is shown in the jobs window
+    pub pop_msg: Arc<RwLock<WindowMsg>>,
+
+    pub jobs: JobManager,
+
+    pub repaint: Repaint,
+
+    pub lock: AutoLock,
+
+    pub clipboard: Clipboard,
+
+    /// Kept to forget the text edit state of secret fields when wiping
+    ctx: Context,
 }
 
 impl GUI {
-    pub fn new() -> Self {
+    pub fn new(ctx: Context, settings: Settings) -> Self {
+        let repaint = Repaint::new(ctx.clone());
         let pop_msg = Arc::new(RwLock::new(WindowMsg::default()));
+
+        // Warn if passwords and keys may end up in swap
+        if cfg!(unix) {
+            if let Err(e) = encryption::memlock::check() {
+                pop_msg.write().unwrap().show("Warning", e);
+            }
+        }
+
+        let wake = repaint.clone();
+        let jobs = JobManager::new(WORKERS, move || wake.request());
+
+        // Decrypted copies of files opened in other apps, left when the app crashed or was killed
+        let stale = encryption::file::stale_private_dirs();
+        if !stale.is_empty() {
+            jobs.sender().submit("Shredding files left by a previous run", move |_| {
+                let mut result = Ok(());
+
+                for dir in &stale {
+                    let shredded = encryption::file::shred_dir(dir);
+                    if result.is_ok() {
+                        result = shredded;
+                    }
+                }
+
+                result.map(|_| format!("{} folders of decrypted files left by a previous run were shredded", stale.len()))
+            });
+        }
+
+        let clipboard = Clipboard::new();
+        let mut encryption_ui = FileEncryptionUi::new(pop_msg.clone(), jobs.sender(), &repaint, clipboard.clone());
+        encryption_ui.load_settings(&settings);
+
         Self {
-            encryption_ui: FileEncryptionUi::new(pop_msg.clone()),
-            text_hashing_ui: TextHashingUi::new(),
-            pop_msg
+            encryption_ui,
+            text_encryption_ui: TextEncryptionUi::new(pop_msg.clone(), jobs.sender(), clipboard.clone()),
+            text_hashing_ui: TextHashingUi::new(pop_msg.clone(), jobs.sender(), clipboard.clone()),
+            notes_ui: NotesUi::new(pop_msg.clone(), jobs.sender(), clipboard.clone()),
+            jobs_ui: JobsUi::new(),
+            settings_ui: SettingsUi::new(),
+            settings,
+            pop_msg,
+            jobs,
+            repaint,
+            lock: AutoLock::new(),
+            clipboard,
+            ctx,
         }
     }
 
+    /// The settings to save, with the current values of the tabs
+    pub fn settings(&self) -> Settings {
+        let mut settings = self.settings.clone();
+        self.encryption_ui.store_settings(&mut settings);
+        settings
+    }
+
+    /// Whether passwords, keys or secret text are loaded in one of the tabs
+    pub fn has_secrets(&self) -> bool {
+        self.encryption_ui.has_secrets()
+            || self.text_encryption_ui.has_secrets()
+            || self.text_hashing_ui.has_secrets()
+            || self.notes_ui.has_secrets()
+    }
+
+    /// Zeroize the credentials, the secret text and the hashed text of every tab
+    ///
+    /// A secret still in the clipboard and the undo history of the text fields are cleared too. Jobs keep
+    /// running, they own their copy of the credentials.
+    pub fn wipe_secrets(&mut self) {
+        self.encryption_ui.clear();
+        self.text_encryption_ui.clear();
+        self.text_hashing_ui.clear();
+        self.notes_ui.clear(&self.encryption_ui.policy);
+        self.clipboard.clear_secret();
+        ui::forget_text_edits(&self.ctx);
+    }
+
+    /// How long a copied secret stays in the clipboard, `None` to keep it
+    pub fn clipboard_timeout(&self) -> Option<Duration> {
+        (self.settings.clipboard_timeout > 0).then(|| Duration::from_secs(self.settings.clipboard_timeout))
+    }
+
+    /// Clear a copied secret once its timeout is over, called once per frame
+    pub fn clear_clipboard(&mut self, ctx: &Context) {
+        // Every second to update the countdown
+        if let Some(remaining) = self.clipboard.tick(self.clipboard_timeout()) {
+            ctx.request_repaint_after(remaining.min(Duration::from_secs(1)));
+        }
+    }
+
+    /// Wipe everything, cancel every job and shred the files open in other apps
+    pub fn panic_wipe(&mut self) {
+        self.wipe_secrets();
+        self.encryption_ui.sessions.shred_all();
+
+        self.jobs.cancel_all();
+    }
+
+    /// Wipe the secrets after the idle timeout, on minimize or on the panic shortcut, called once per frame
+    pub fn auto_lock(&mut self, ctx: &Context) {
+        let Some(reason) = self.lock.check(ctx, &self.settings, self.has_secrets()) else {
+            return;
+        };
+
+        if reason == LockReason::Panic {
+            self.panic_wipe();
+        } else {
+            self.wipe_secrets();
+        }
+
+        let message = format!("{}, the passwords and the text in every tab were cleared", reason);
+        self.pop_msg.write().unwrap().show("Secrets wiped", message);
+    }
+
+    /// Apply what the workers reported, called once per frame before drawing
+    pub fn poll_jobs(&mut self) {
+        let queued = self.jobs.jobs().len();
+        let finished = self.jobs.poll();
+        self.encryption_ui.queue.update(&self.jobs, &finished);
+
+        // Show new jobs, and failures that happened while the window was closed
+        let failed = finished
+            .iter()
+            .any(|id| self.jobs.get(*id).is_some_and(|job| job.state == JobState::Failed));
+
+        if self.jobs.jobs().len() > queued || failed {
+            self.jobs_ui.open = true;
+        }
+    }
+
+    /// Queue the files dropped on the window in the encryption tab
+    pub fn handle_dropped_files(&mut self, ctx: &Context) {
+        let (files, errors) = drop::dropped_files(ctx);
+
+        if !errors.is_empty() {
+            let message: Vec<String> = errors.iter().map(|e| format!("{}", e)).collect();
+            self.pop_msg.write().unwrap().show("Some folders could not be read", message.join("\n"));
+        }
+
+        if files.is_empty() {
+            return;
+        }
+
+        self.text_hashing_ui.open = false;
+        self.text_encryption_ui.open = false;
+        self.notes_ui.open = false;
+        self.settings_ui.open = false;
+        self.encryption_ui.open = true;
+        self.encryption_ui.add_files(files);
+    }
+
+    /// Keep the app open while the note has unsaved changes, the user is asked what to do with them
+    pub fn guard_exit(&mut self, ctx: &Context) {
+        if !ctx.input(|i| i.viewport().close_requested()) || self.notes_ui.guard(GuardedAction::Exit) {
+            return;
+        }
+
+        ctx.send_viewport_cmd(ViewportCommand::CancelClose);
+
+        self.text_hashing_ui.open = false;
+        self.text_encryption_ui.open = false;
+        self.encryption_ui.open = false;
+        self.settings_ui.open = false;
+        self.notes_ui.open = true;
+    }
+
     pub fn show_central_panel(&mut self, ui: &mut Ui) {
         central_panel::show(ui, self);
     }
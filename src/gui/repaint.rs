@@ -0,0 +1,60 @@
+use eframe::egui::Context;
+use std::sync::mpsc::{ self, Sender };
+use std::thread::{ self, JoinHandle };
+use std::time::Duration;
+
+/// Shortest time between two repaints requested by background threads, 30 FPS
+const MIN_INTERVAL: Duration = Duration::from_millis(32);
+
+/// Wakes the UI when a background thread changed what it shows
+///
+/// The UI only redraws on input events and on these requests. They go through a channel
+/// to a thread that merges bursts, like progress updates, into at most one repaint per [MIN_INTERVAL].
+#[derive(Clone)]
+pub struct Repaint {
+    sender: Sender<()>,
+}
+
+impl Repaint {
+    pub fn new(ctx: Context) -> Self {
+        let (sender, receiver) = mpsc::channel();
+
+        // Ends when every sender is dropped
+        thread::spawn(move || {
+            while receiver.recv().is_ok() {
+                while receiver.try_recv().is_ok() {}
+
+                ctx.request_repaint();
+                thread::sleep(MIN_INTERVAL);
+            }
+        });
+
+        Self { sender }
+    }
+
+    pub fn request(&self) {
+        // Only fails when the app is closing
+        let _ = self.sender.send(());
+    }
+
+    /// Run `f` in a new thread, the UI is repainted when it returns or panics
+    pub fn spawn<F>(&self, f: F) -> JoinHandle<()>
+    where
+        F: FnOnce() + Send + 'static,
+    {
+        let guard = RepaintOnDrop(self.clone());
+
+        thread::spawn(move || {
+            let _guard = guard;
+            f();
+        })
+    }
+}
+
+struct RepaintOnDrop(Repaint);
+
+impl Drop for RepaintOnDrop {
+    fn drop(&mut self) {
+        self.0.request();
+    }
+}
@@ -1,6 +1,13 @@
 use eframe::egui::Ui;
-use super::GUI;
+use super::{GUI, ui::file_encryption::Operation};
 
 pub fn show(ui: &mut Ui, gui: &mut GUI) {
-    gui.encryption_ui.argon_params_ui(ui);
+    // Both encryption tabs use the same Argon2 parameters
+    let decrypting = if gui.text_encryption_ui.open {
+        gui.text_encryption_ui.mode == Operation::Decrypt
+    } else {
+        gui.encryption_ui.mode == Operation::Decrypt
+    };
+
+    gui.encryption_ui.argon_params_ui(ui, decrypting);
 }
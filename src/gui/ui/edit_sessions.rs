@@ -0,0 +1,289 @@
+use eframe::egui::{ vec2, Align2, Color32, Frame, Label, Ui, Window };
+use std::path::PathBuf;
+use std::sync::{ Arc, Mutex };
+use std::time::Duration;
+use encryption::{ file, prelude::* };
+use super::*;
+use super::jobs::state_color;
+use crate::edit::EditSession;
+use crate::jobs::{ JobId, JobManager, JobSender, JobState };
+
+/// How often the decrypted copies are checked for changes
+const POLL_INTERVAL: Duration = Duration::from_secs(1);
+
+struct SessionEntry {
+    session: Arc<Mutex<EditSession>>,
+
+    /// Kept outside the lock so a panic wipe never waits for a job
+    dir: PathBuf,
+
+    name: String,
+
+    /// The job encrypting the copy back
+    job: Option<JobId>,
+
+    /// Encrypting back failed or was cancelled, waits for the user to retry
+    paused: bool,
+}
+
+/// Encrypted files open in the apps the system opens them with
+///
+/// The sessions stay open when the secrets are wiped after inactivity or when the window loses
+/// focus, the user is working in the other app then. A panic wipe shreds them right away.
+pub struct EditSessionsUi {
+    entries: Vec<SessionEntry>,
+
+    /// Set by the jobs that decrypt the files, moved to the entries on the next frame
+    started: Arc<Mutex<Vec<EditSession>>>,
+
+    /// A file waiting for the user to agree to decrypt it to disk, no memory backed folder was found
+    confirm: Option<(PathBuf, Credentials)>,
+
+    pub jobs: JobSender,
+}
+
+impl EditSessionsUi {
+    pub fn new(jobs: JobSender) -> Self {
+        Self { entries: Vec::new(), started: Arc::new(Mutex::new(Vec::new())), confirm: None, jobs }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    /// Forget the credentials of a file waiting to be decrypted to disk
+    pub fn clear(&mut self) {
+        self.confirm = None;
+    }
+
+    pub fn has_secrets(&self) -> bool {
+        self.confirm.is_some()
+    }
+
+    /// Decrypt `path` to a private directory and open it with the default app
+    ///
+    /// Asks first if the directory would be on disk
+    pub fn open(&mut self, path: PathBuf, credentials: Credentials) {
+        if file::memory_dir().is_none() {
+            self.confirm = Some((path, credentials));
+            return;
+        }
+
+        self.start(path, credentials, false);
+    }
+
+    fn start(&mut self, path: PathBuf, credentials: Credentials, allow_disk: bool) {
+        let started = self.started.clone();
+
+        self.jobs.submit(format!("Opening {}", file_name(&path)), move |ctx| {
+            let mut session = EditSession::start(&path, credentials, allow_disk, &mut ctx.progress(), ctx.cancel_token())?;
+
+            // Cancelled once the copy was written, shred it before anything opens it
+            if let Err(e) = ctx.cancel_token().check() {
+                let _ = file::shred_dir(&session.dir);
+                return Err(e);
+            }
+
+            if let Err(e) = open::that_detached(&session.path) {
+                let _ = session.close(&mut |_| {}, &CancelToken::new());
+                return Err(anyhow::anyhow!("Failed to open {} with the default app {}", file_name(&path), e));
+            }
+
+            let message = format!("{} is open, saving it in the other app encrypts it back", file_name(&path));
+            started.lock().unwrap().push(session);
+
+            Ok(message)
+        });
+    }
+
+    /// Encrypt back the copies that were saved, called once per frame
+    pub fn update(&mut self, ui: &Ui, jobs: &JobManager) {
+        self.confirm_window(ui);
+
+        for session in self.started.lock().unwrap().drain(..) {
+            self.entries.push(SessionEntry {
+                dir: session.dir.clone(),
+                name: file_name(&session.encrypted),
+                session: Arc::new(Mutex::new(session)),
+                job: None,
+                paused: false,
+            });
+        }
+
+        if self.entries.is_empty() {
+            return;
+        }
+
+        for entry in &mut self.entries {
+            let state = entry.job.and_then(|id| jobs.get(id)).map(|job| job.state);
+
+            match state {
+                Some(JobState::Queued | JobState::Running) => continue,
+                Some(JobState::Failed | JobState::Cancelled) => {
+                    entry.paused = true;
+                    entry.job = None;
+                }
+                _ => {}
+            }
+
+            if entry.paused {
+                continue;
+            }
+
+            // A job still holds the lock while it finishes
+            let modified = entry.session.try_lock().is_ok_and(|session| session.is_modified());
+
+            if modified {
+                entry.job = Some(sync(&self.jobs, entry));
+            }
+        }
+
+        // The other app does not wake this one up
+        ui.ctx().request_repaint_after(POLL_INTERVAL);
+    }
+
+    /// Ask before decrypting a file to disk, where shredding it may not erase it
+    fn confirm_window(&mut self, ui: &Ui) {
+        let Some((path, _)) = &self.confirm else {
+            return;
+        };
+
+        let message = format!(
+            "No folder backed by memory was found, {} would be decrypted to {}. Shredding it when it is closed \
+             may not erase it from SSDs or copy-on-write filesystems.",
+            file_name(path),
+            std::env::temp_dir().display()
+        );
+
+        let mut answer = None;
+
+        Window::new(rich_text("Decrypt to Disk?").size(16.0))
+            .anchor(Align2::CENTER_CENTER, [0.0, 0.0])
+            .collapsible(false)
+            .resizable(false)
+            .frame(Frame::window(&ui.style().clone()).fill(ui.visuals().window_fill))
+            .show(ui.ctx(), |ui| {
+                ui.set_min_size(vec2(300.0, 100.0));
+
+                ui.vertical_centered(|ui| {
+                    ui.spacing_mut().item_spacing.y = 15.0;
+                    ui.add(Label::new(rich_text(message).size(14.0)).wrap());
+
+                    ui.horizontal(|ui| {
+                        if ui.add(button(rich_text("Open Anyway").color(Color32::BLACK))).clicked() {
+                            answer = Some(true);
+                        }
+
+                        if ui.add(button(rich_text("Cancel").color(Color32::BLACK))).clicked() {
+                            answer = Some(false);
+                        }
+                    });
+                });
+            });
+
+        match answer {
+            Some(true) => {
+                if let Some((path, credentials)) = self.confirm.take() {
+                    self.start(path, credentials, true);
+                }
+            }
+            Some(false) => self.confirm = None,
+            None => {}
+        }
+    }
+
+    pub fn show(&mut self, ui: &mut Ui, jobs: &JobManager) {
+        if self.entries.is_empty() {
+            return;
+        }
+
+        ui.label(rich_text("Open in other apps:"));
+
+        let mut close = None;
+
+        for (i, entry) in self.entries.iter_mut().enumerate() {
+            ui.horizontal(|ui| {
+                let job = entry.job.and_then(|id| jobs.get(id));
+
+                let (text, color) = match job {
+                    Some(job) if !job.state.is_finished() => ("Saving".to_string(), state_color(JobState::Running)),
+                    _ if entry.paused => ("Not saved".to_string(), state_color(JobState::Failed)),
+                    _ => ("Watching".to_string(), Color32::WHITE),
+                };
+
+                let mut hover = match entry.session.try_lock() {
+                    Ok(session) => format!("{}\nDecrypted copy: {}", session.encrypted.display(), session.path.display()),
+                    Err(_) => entry.name.clone(),
+                };
+
+                if let Some(job) = job.filter(|job| job.state.is_finished()) {
+                    hover.push_str(&format!("\n\n{}", job.message));
+                }
+
+                ui.add_sized(vec2(70.0, 18.0), Label::new(rich_text(text).color(color)));
+                ui.add_sized(vec2(180.0, 18.0), Label::new(rich_text(&entry.name)).truncate()).on_hover_text(hover);
+
+                if entry.paused && ui.add(button(rich_text("Retry").color(Color32::BLACK))).clicked() {
+                    entry.paused = false;
+                }
+
+                let busy = job.is_some_and(|job| !job.state.is_finished());
+                let response = ui.add_enabled(!busy, button(rich_text("Close").color(Color32::BLACK)))
+                    .on_hover_text("Encrypt the last changes and shred the decrypted copy, close the file in the other app first");
+
+                if response.clicked() {
+                    close = Some(i);
+                }
+            });
+        }
+
+        if let Some(i) = close {
+            let entry = self.entries.remove(i);
+
+            self.jobs.submit(format!("Closing {}", entry.name), move |ctx| {
+                entry.session.lock().unwrap().close(&mut ctx.progress(), ctx.cancel_token())?;
+                Ok(format!("{} saved and the decrypted copy shredded", entry.name))
+            });
+        }
+
+        ui.add_space(15.0);
+    }
+
+    /// Encrypt the last changes and shred every copy, blocks until done
+    ///
+    /// Called when the app exits
+    pub fn close_all(&mut self) {
+        for entry in self.entries.drain(..) {
+            let _ = entry.session.lock().unwrap().close(&mut |_| {}, &CancelToken::new());
+        }
+
+        for mut session in self.started.lock().unwrap().drain(..) {
+            let _ = session.close(&mut |_| {}, &CancelToken::new());
+        }
+    }
+
+    /// Shred every copy now, the changes not encrypted back yet are lost
+    pub fn shred_all(&mut self) {
+        for entry in self.entries.drain(..) {
+            let _ = file::shred_dir(&entry.dir);
+        }
+
+        for session in self.started.lock().unwrap().drain(..) {
+            let _ = file::shred_dir(&session.dir);
+        }
+    }
+}
+
+fn sync(jobs: &JobSender, entry: &SessionEntry) -> JobId {
+    let session = entry.session.clone();
+    let name = entry.name.clone();
+
+    jobs.submit(format!("Saving {}", name), move |ctx| {
+        let synced = session.lock().unwrap().sync(&mut ctx.progress(), ctx.cancel_token())?;
+
+        Ok(match synced {
+            true => format!("{} encrypted back", name),
+            false => format!("{} has no changes", name),
+        })
+    })
+}
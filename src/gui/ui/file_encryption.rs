@@ -1,191 +1,645 @@
-use eframe::egui::{ Color32, Ui, Slider };
+use eframe::egui::{ Color32, ComboBox, ProgressBar, Ui, Slider };
 use num_format::{ Locale, ToFormattedString };
+use std::collections::HashSet;
+use std::fmt;
+use std::ops::RangeInclusive;
+use std::path::{ Path, PathBuf };
 use std::sync::{ Arc, RwLock };
-use encryption::prelude::*;
+use std::time::Duration;
+use encryption::{ file, prelude::* };
 use super::*;
+use super::edit_sessions::EditSessionsUi;
+use super::file_queue::{ EntryState, FileQueue };
+use super::generator::GeneratorUi;
+use super::preview::PreviewUi;
+use crate::gui::clipboard::Clipboard;
+use crate::gui::repaint::Repaint;
+use crate::preview::Preview;
+use crate::settings::{ RecentFiles, Settings };
+use crate::jobs::{ JobContext, JobId, JobManager, JobSender, JobState };
 
-const FILE_EXTENSION: &str = ".ncrypt";
+/// Highest memory cost the sliders allow (kB)
+const MAX_M_COST: u32 = 10_000_000;
+
+/// What the Argon2 sliders allow, saved parameters outside of them are not loaded
+pub const M_COST_RANGE: RangeInclusive<u32> = 2048..=MAX_M_COST;
+pub const T_COST_RANGE: RangeInclusive<u32> = 1..=5000;
+pub const P_COST_RANGE: RangeInclusive<u32> = 1..=64;
+
+/// An offline attacker is assumed to try passwords this many times faster than this machine
+const ATTACKER_SPEEDUP: f64 = 1000.0;
+
+/// State shared with the calibration thread
+#[derive(Default)]
+pub struct CalibrationState {
+    pub running: bool,
+
+    /// Speed of this machine, used to estimate the unlock time
+    pub benchmark: Option<Benchmark>,
+
+    /// Parameters picked by the last calibration, applied on the next frame
+    pub params: Option<Argon2Params>,
+
+    /// Available physical memory in kB
+    pub available_memory: u64,
+}
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub enum Operation {
+    Encrypt,
+    Decrypt,
+}
+
+impl fmt::Display for Operation {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            Operation::Encrypt => "Encrypt",
+            Operation::Decrypt => "Decrypt",
+        };
+        write!(f, "{}", name)
+    }
+}
+
+impl Operation {
+    fn title(&self) -> &'static str {
+        match self {
+            Operation::Encrypt => "Encrypting",
+            Operation::Decrypt => "Decrypting",
+        }
+    }
+
+    fn past_tense(&self) -> &'static str {
+        match self {
+            Operation::Encrypt => "encrypted",
+            Operation::Decrypt => "decrypted",
+        }
+    }
+}
+
+/// What to do when the output file already exists
+#[derive(Clone, Copy, Debug, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+pub enum Overwrite {
+    Replace,
+    Rename,
+    Skip,
+}
+
+impl fmt::Display for Overwrite {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            Overwrite::Replace => "Overwrite",
+            Overwrite::Rename => "Rename",
+            Overwrite::Skip => "Skip",
+        };
+        write!(f, "{}", name)
+    }
+}
 
 /// File Encryption/Decryption Ui
 pub struct FileEncryptionUi {
     pub open: bool,
 
+    /// Which form is shown, encrypting asks to confirm the password, decrypting does not
+    ///
+    /// Follows the queue, the encrypt form is shown while a waiting file is to be encrypted
+    pub mode: Operation,
+
     pub credentials: Credentials,
 
-    pub file_path: String,
+    /// Minimum password strength required to encrypt
+    pub policy: PasswordPolicy,
+
+    /// Strength of the password being typed, updated when the credentials change
+    pub strength: Option<Strength>,
+
+    pub generator: GeneratorUi,
+
+    /// Shows a file decrypted in memory
+    pub viewer: PreviewUi,
+
+    /// Files open in other apps, encrypted back when they are saved
+    pub sessions: EditSessionsUi,
+
+    /// The files to encrypt or decrypt
+    pub queue: FileQueue,
+
+    /// Where to write the output, `None` for the folder of the input file
+    pub output_dir: Option<PathBuf>,
+
+    /// Files added to the queue, if the user wants them remembered
+    pub recent: RecentFiles,
+
+    /// Verify the encrypted file and securely delete the original
+    pub remove_original: bool,
 
     pub argon_params: Argon2Params,
 
+    pub preset: Argon2Preset,
+
+    /// Unlock time in seconds the calibration aims for
+    pub target_time: f64,
+
+    pub calibration: Arc<RwLock<CalibrationState>>,
+
     pub pop_msg: Arc<RwLock<WindowMsg>>,
+
+    pub clipboard: Clipboard,
+
+    /// Runs the encryption, decryption, verification and calibration jobs
+    pub jobs: JobSender,
 }
 
 impl FileEncryptionUi {
-    pub fn new(pop_msg: Arc<RwLock<WindowMsg>>) -> Self {
+    pub fn new(pop_msg: Arc<RwLock<WindowMsg>>, jobs: JobSender, repaint: &Repaint, clipboard: Clipboard) -> Self {
+        let calibration = Arc::new(RwLock::new(CalibrationState::default()));
+        measure_machine(calibration.clone(), repaint);
+
         Self {
             open: true,
+            mode: Operation::Encrypt,
             credentials: Credentials::default(),
-            file_path: String::new(),
+            policy: PasswordPolicy::default(),
+            strength: None,
+            generator: GeneratorUi::new(),
+            viewer: PreviewUi::new(),
+            sessions: EditSessionsUi::new(jobs.clone()),
+            queue: FileQueue::new(),
+            output_dir: None,
+            recent: RecentFiles::default(),
+            remove_original: false,
             argon_params: Argon2Params::fast(),
+            preset: Argon2Preset::Fast,
+            target_time: 1.0,
+            calibration,
             pop_msg,
+            clipboard,
+            jobs,
         }
     }
 
-    pub fn show(&mut self, ui: &mut Ui) {
+    /// Wipe the credentials, the generated password and the previewed file
+    pub fn clear(&mut self) {
+        self.credentials.destroy();
+        self.strength = None;
+        self.generator.close();
+        self.viewer.close();
+        self.sessions.clear();
+    }
+
+    /// Whether credentials, a generated password or a previewed file are in memory
+    pub fn has_secrets(&self) -> bool {
+        !self.credentials.is_empty() || self.generator.generated.is_some() || self.viewer.is_open() || self.sessions.has_secrets()
+    }
+
+    /// Start with the saved parameters, output folder and recent files
+    pub fn load_settings(&mut self, settings: &Settings) {
+        self.argon_params = settings.argon_params.clone();
+        self.preset = Argon2Preset::from_params(&self.argon_params);
+        self.output_dir = settings.output_dir.clone();
+        self.queue.overwrite = settings.overwrite;
+        self.recent = settings.recent_files.clone();
+    }
+
+    pub fn store_settings(&self, settings: &mut Settings) {
+        settings.argon_params = self.argon_params.clone();
+        settings.output_dir = self.output_dir.clone();
+        settings.overwrite = self.queue.overwrite;
+        settings.recent_files = self.recent.clone();
+    }
+
+    pub fn show(&mut self, ui: &mut Ui, jobs: &JobManager) {
+        // Stays open on the other tabs until closed
+        self.viewer.show(ui);
+        self.sessions.update(ui, jobs);
+
         if !self.open {
             return;
         }
 
-            self.open_file_button(ui);
-            self.credentials_input(ui);
+        self.mode_selector(ui);
+        self.open_file_button(ui);
+        self.queue_ui(ui, jobs);
 
-            ui.horizontal(|ui| {
-                ui.add_space(160.0);
-                self.encrypt(ui);
+        match self.mode {
+            Operation::Encrypt => {
+                self.credentials_input(ui);
+                self.remove_original_checkbox(ui);
 
-                ui.add_space(15.0);
+                ui.horizontal(|ui| {
+                    ui.add_space(200.0);
+                    self.run(ui);
+                });
+            }
+            Operation::Decrypt => {
+                self.credentials_input(ui);
 
-                self.decrypt(ui);
-            });
+                ui.horizontal(|ui| {
+                    ui.add_space(120.0);
+                    self.run(ui);
+
+                    ui.add_space(15.0);
+
+                    self.verify(ui);
+
+                    ui.add_space(15.0);
+
+                    self.verify_files(ui);
+                });
+            }
+        }
+    }
+
+    fn mode_selector(&mut self, ui: &mut Ui) {
+        ui.horizontal(|ui| {
+            ui.add_space(150.0);
+
+            let encrypt = ui.selectable_label(self.mode == Operation::Encrypt, rich_text("Encrypt Files"));
+            if encrypt.clicked() {
+                self.select_mode(Operation::Encrypt);
+            }
+
+            ui.add_space(15.0);
+
+            let decrypt = ui.selectable_label(self.mode == Operation::Decrypt, rich_text("Decrypt Files"));
+            if decrypt.clicked() {
+                self.select_mode(Operation::Decrypt);
+            }
+        });
+
+        ui.add_space(15.0);
+    }
+
+    /// Show the form of `mode` and run every file that has not run yet with it
+    fn select_mode(&mut self, mode: Operation) {
+        for entry in self.queue.entries.iter_mut().filter(|entry| entry.is_ready()) {
+            entry.operation = mode;
+        }
+
+        self.set_mode(mode);
     }
 
-    fn encrypt(&mut self, ui: &mut Ui) {
-        let text = rich_text("Encrypt").color(Color32::BLACK);
+    fn set_mode(&mut self, mode: Operation) {
+        // The confirmation is only typed for encryption, don't keep it around in the decrypt form
+        if mode == Operation::Decrypt {
+            self.credentials.confirm_passwd_mut().clear();
+        }
+
+        self.strength = (mode == Operation::Encrypt && !self.credentials.password().is_empty())
+            .then(|| self.policy.estimate(&self.credentials));
+
+        self.mode = mode;
+    }
+
+    /// Add files to the queue, switching to the decrypt form if they are all encrypted
+    pub fn add_files(&mut self, paths: Vec<PathBuf>) {
+        self.recent.add(&paths);
+        self.queue.add(paths);
+        self.sync_mode();
+    }
+
+    /// Show the form the files waiting in the queue need
+    fn sync_mode(&mut self) {
+        let ready = self.queue.ready();
+
+        if ready.is_empty() {
+            return;
+        }
+
+        let encrypting = ready.iter().any(|i| self.queue.entries[*i].operation == Operation::Encrypt);
+        self.set_mode(if encrypting { Operation::Encrypt } else { Operation::Decrypt });
+    }
+
+    fn queue_ui(&mut self, ui: &mut Ui, jobs: &JobManager) {
+        let benchmark = self.calibration.read().unwrap().benchmark;
+        let changes = self.queue.show(ui, jobs, benchmark);
+
+        if let Some(i) = changes.preview {
+            self.preview(i);
+        } else if let Some(i) = changes.open_in_app {
+            self.open_in_app(i);
+        } else if !changes.retry.is_empty() {
+            self.start(changes.retry);
+        } else if changes.operation {
+            self.sync_mode();
+        }
+
+        ui.add_space(15.0);
+
+        self.sessions.show(ui, jobs);
+    }
+
+    /// Run every file waiting in the queue
+    fn run(&mut self, ui: &mut Ui) {
+        let ready = self.queue.ready();
+        let operations: Vec<Operation> = ready.iter().map(|i| self.queue.entries[*i].operation).collect();
+
+        let text = if operations.is_empty() || operations.iter().all(|operation| *operation == self.mode) {
+            self.mode.to_string()
+        } else {
+            "Run All".to_string()
+        };
+
+        if ui.add(button(rich_text(text).color(Color32::BLACK))).clicked() {
+            if ready.is_empty() {
+                self.pop_msg.write().unwrap().show("No files to run", "Choose files first");
+            } else {
+                self.start(ready);
+            }
+        }
+    }
+
+    fn verify(&mut self, ui: &mut Ui) {
+        let text = rich_text("Verify").color(Color32::BLACK);
         let button = button(text);
 
-        if ui.add(button).clicked() {
-            {
-                let mut pop_msg = self.pop_msg.write().unwrap();
-                pop_msg.open = true;
-                pop_msg.message = "Encrypting...".to_string();
-            }
-
-            let argon_params = self.argon_params.clone();
-            let file_path = self.file_path.clone();
-            let credentials = self.credentials.clone();
-            let pop_msg = self.pop_msg.clone();
-
-            std::thread::spawn(move || {
-                let data = match std::fs::read(file_path.clone()) {
-                    Ok(data) => data,
-                    Err(e) => {
-                        let mut pop_msg = pop_msg.write().unwrap();
-                        pop_msg.open = true;
-                        pop_msg.title = "Failed to read file".to_string();
-                        pop_msg.message = format!("{:?}", e);
-                        return;
-                    }
-                };
-
-                let encrypted_data = match encrypt_data(argon_params.clone(), data, credentials) {
-                    Ok(data) => data,
-                    Err(e) => {
-                        let mut pop_msg = pop_msg.write().unwrap();
-                        pop_msg.open = true;
-                        pop_msg.title = "Failed to encrypt file".to_string();
-                        pop_msg.message = format!("{:?}", e);
-                        return;
-                    }
-                };
+        let response = ui.add(button).on_hover_text("Check that the files to decrypt decrypt without writing anything");
 
-                let new_file_path = format!("{}{}", file_path, FILE_EXTENSION);
+        if response.clicked() {
+            let files: Vec<PathBuf> = self.queue.entries
+                .iter()
+                .filter(|entry| entry.operation == Operation::Decrypt)
+                .map(|entry| entry.path.clone())
+                .collect();
 
-                match std::fs::write(&new_file_path, &encrypted_data) {
-                    Ok(_) => {
-                        let mut pop_msg = pop_msg.write().unwrap();
-                        pop_msg.open = true;
-                        pop_msg.title = "Success".to_string();
-                        pop_msg.message =
-                            format!("File encrypted successfully to: {}", new_file_path);
-                    }
-                    Err(e) => {
-                        let mut pop_msg = pop_msg.write().unwrap();
-                        pop_msg.open = true;
-                        pop_msg.title = "Failed to save the encrypted file".to_string();
-                        pop_msg.message = format!("{:?}", e);
-                    }
+            if files.is_empty() {
+                self.pop_msg.write().unwrap().show("No files to verify", "Choose encrypted files first");
+            } else {
+                self.start_verify(files);
+            }
+        }
+    }
+
+    fn verify_files(&mut self, ui: &mut Ui) {
+        let text = rich_text("Verify Files").color(Color32::BLACK);
+
+        if ui.add(button(text)).on_hover_text("Check several encrypted files at once").clicked() {
+            let files = rfd::FileDialog::new()
+                .add_filter("Encrypted files", &[file::FILE_EXTENSION])
+                .pick_files();
+
+            if let Some(files) = files.filter(|files| !files.is_empty()) {
+                self.start_verify(files);
+            }
+        }
+    }
+
+    /// Verify the files one after the other and show a summary
+    ///
+    /// Every file is fully authenticated but the plaintext is never produced
+    fn start_verify(&mut self, files: Vec<PathBuf>) {
+        let title = match files.as_slice() {
+            [path] => format!("Verifying {}", file_name(path)),
+            files => format!("Verifying {} files", files.len()),
+        };
+
+        let credentials = std::mem::take(&mut self.credentials);
+        self.strength = None;
+
+        self.jobs.submit(title, move |ctx| {
+            let total = files.len();
+            let mut failed = Vec::new();
+            let mut verified = 0;
+
+            for (i, path) in files.iter().enumerate() {
+                ctx.cancel_token().check()?;
+
+                if total > 1 {
+                    ctx.set_status(format!("File {}/{}", i + 1, total));
                 }
-            });
+
+                let mut progress = ctx.progress();
+
+                let result = std::fs::read(path)
+                    .map_err(|e| e.into())
+                    .and_then(|data| verify_data_with(&data, credentials.duplicate(), &mut progress, ctx.cancel_token()));
+
+                match result {
+                    Ok(()) => verified += 1,
+                    Err(e) if e.is::<Cancelled>() => return Err(e),
+                    Err(e) => failed.push((path.clone(), e)),
+                }
+            }
+
+            let summary = verify_summary(total, verified, &failed);
+
+            if failed.is_empty() {
+                Ok(summary)
+            } else {
+                Err(anyhow::anyhow!(summary))
+            }
+        });
+    }
+
+    /// Decrypt an entry of the queue in memory and open it in the viewer
+    ///
+    /// The credentials are kept so the file can be decrypted to disk afterwards
+    fn preview(&mut self, entry: usize) {
+        if let Err(e) = self.credentials.is_valid() {
+            self.pop_msg.write().unwrap().show("Invalid credentials", e);
+            return;
         }
+
+        let path = self.queue.entries[entry].path.clone();
+        let name = file_name(&path);
+        let credentials = self.credentials.duplicate();
+        let pending = self.viewer.pending.clone();
+
+        self.jobs.submit(format!("Previewing {}", name), move |ctx| {
+            let data = std::fs::read(&path).map_err(|e| anyhow::Error::from(e).context("Failed to read file"))?;
+
+            let decrypted_data = decrypt_data_with(data, credentials, &mut ctx.progress(), ctx.cancel_token())
+                .map_err(|e| e.context("Failed to decrypt file"))?;
+
+            ctx.cancel_token().check()?;
+            ctx.set_status("Detecting the content");
+            *pending.write().unwrap() = Some(Preview::new(name.clone(), decrypted_data));
+
+            Ok(format!("{} decrypted in memory", name))
+        });
     }
 
-    fn decrypt(&mut self, ui: &mut Ui) {
-        let text = rich_text("Decrypt").color(Color32::BLACK);
-        let button = button(text);
+    /// Decrypt an entry of the queue to a private folder and open it with the default app
+    ///
+    /// Like a preview the credentials are kept
+    fn open_in_app(&mut self, entry: usize) {
+        if let Err(e) = self.credentials.is_valid() {
+            self.pop_msg.write().unwrap().show("Invalid credentials", e);
+            return;
+        }
 
-        if ui.add(button).clicked() {
-            {
-                let mut pop_msg = self.pop_msg.write().unwrap();
-                pop_msg.open = true;
-                pop_msg.message = "Decrypting...".to_string();
-            }
-
-            let file_path = self.file_path.clone();
-            let credentials = self.credentials.clone();
-            let pop_msg = self.pop_msg.clone();
-
-            std::thread::spawn(move || {
-                let data = match std::fs::read(file_path.clone()) {
-                    Ok(data) => data,
-                    Err(e) => {
-                        let mut pop_msg = pop_msg.write().unwrap();
-                        pop_msg.open = true;
-                        pop_msg.title = "Failed to read file".to_string();
-                        pop_msg.message = format!("{:?}", e);
-                        return;
-                    }
-                };
-
-                let decrypted_data = match decrypt_data(data, credentials) {
-                    Ok(data) => data,
-                    Err(e) => {
-                        let mut pop_msg = pop_msg.write().unwrap();
-                        pop_msg.open = true;
-                        pop_msg.title = "Failed to decrypt file".to_string();
-                        pop_msg.message = format!("{:?}", e);
-                        return;
-                    }
-                };
-
-                // remove the extension
-                let new_file_path = file_path.replace(FILE_EXTENSION, "");
-
-                match std::fs::write(&new_file_path, &decrypted_data) {
-                    Ok(_) => {
-                        let mut pop_msg = pop_msg.write().unwrap();
-                        pop_msg.open = true;
-                        pop_msg.title = "Success".to_string();
-                        pop_msg.message =
-                            format!("File decrypted successfully to: {}", new_file_path);
-                    }
-                    Err(e) => {
-                        let mut pop_msg = pop_msg.write().unwrap();
-                        pop_msg.open = true;
-                        pop_msg.title = "Failed to save decrypted file".to_string();
-                        pop_msg.message = format!("{:?}", e);
-                    }
+        let path = self.queue.entries[entry].path.clone();
+        self.sessions.open(path, self.credentials.duplicate());
+    }
+
+    fn remove_original_checkbox(&mut self, ui: &mut Ui) {
+        let text = rich_text("Remove the original file after encrypting");
+        ui.checkbox(&mut self.remove_original, text)
+            .on_hover_text("The encrypted file is verified first, then the original is overwritten and deleted");
+
+        if self.remove_original {
+            ui.label(
+                rich_text("Overwriting is not reliable on SSDs and copy-on-write filesystems,\nthe original may still be recoverable")
+                    .color(Color32::from_rgb(255, 200, 0))
+            );
+        }
+    }
+
+    /// Submit a job for each of the `entries` of the queue
+    ///
+    /// The credentials are checked once here instead of failing every job
+    fn start(&mut self, entries: Vec<usize>) {
+        let encrypting = entries.iter().any(|i| self.queue.entries[*i].operation == Operation::Encrypt);
+
+        let check = if encrypting {
+            self.credentials.is_confirmed().and_then(|_| self.policy.check(&self.credentials))
+        } else {
+            self.credentials.is_valid()
+        };
+
+        if let Err(e) = check {
+            self.pop_msg.write().unwrap().show("Invalid credentials", e);
+            return;
+        }
+
+        let credentials = std::mem::take(&mut self.credentials);
+        self.strength = None;
+
+        // Outputs are chosen for the whole run first, so two files never get the same output and no job
+        // replaces a file another job of the run reads or writes
+        let mut reserved: HashSet<PathBuf> = entries.iter().map(|i| self.queue.entries[*i].path.clone()).collect();
+
+        for i in entries {
+            let entry = &self.queue.entries[i];
+            let (operation, input) = (entry.operation, entry.path.clone());
+
+            let state = match self.output_path(operation, &input, &reserved) {
+                Ok(Some((output, overwrite))) => {
+                    reserved.insert(output.clone());
+                    EntryState::Submitted(self.submit(operation, input, output, overwrite, credentials.duplicate()))
                 }
-            });
+                Ok(None) => EntryState::Skipped,
+                Err(e) => EntryState::Finished { state: JobState::Failed, message: format!("{:#}", e) },
+            };
+
+            self.queue.entries[i].state = state;
         }
     }
 
-    fn open_file_button(&mut self, ui: &mut Ui) {
-        let text = rich_text("Choose a File").color(Color32::BLACK);
-        let button = button(text);
+    /// Where to write the output of `input` and whether to replace it, `None` to skip the file
+    ///
+    /// An output `reserved` by the run is always renamed, whatever the overwrite setting
+    fn output_path(&self, operation: Operation, input: &Path, reserved: &HashSet<PathBuf>) -> Result<Option<(PathBuf, bool)>, anyhow::Error> {
+        let output_dir = self.output_dir.as_deref();
+        let output = match operation {
+            Operation::Encrypt => file::encrypted_path(input, output_dir)?,
+            Operation::Decrypt => file::decrypted_path(input, output_dir)?,
+        };
 
+        if reserved.contains(&output) {
+            return Ok(Some((file::unique_path_among(&output, reserved), false)));
+        }
+
+        if !output.exists() {
+            return Ok(Some((output, false)));
+        }
+
+        Ok(match self.queue.overwrite {
+            Overwrite::Replace => Some((output, true)),
+            Overwrite::Rename => Some((file::unique_path_among(&output, reserved), false)),
+            Overwrite::Skip => None,
+        })
+    }
+
+    fn submit(&self, operation: Operation, input: PathBuf, output: PathBuf, overwrite: bool, credentials: Credentials) -> JobId {
+        let title = format!("{} {}", operation.title(), file_name(&input));
+
+        let argon_params = self.argon_params.clone();
+        let policy = self.policy.clone();
+        let remove_original = operation == Operation::Encrypt && self.remove_original;
+
+        self.jobs.submit(title, move |ctx| {
+            match operation {
+                Operation::Encrypt => {
+                    let options = EncryptOptions { overwrite, remove_original, policy, argon_params };
+                    encrypt_file(&input, &output, options, credentials, ctx)?;
+                }
+                Operation::Decrypt => decrypt_file(&input, &output, overwrite, credentials, ctx)?,
+            }
+
+            let mut message = format!("File {} successfully to: {}", operation.past_tense(), output.display());
+
+            if remove_original {
+                message.push_str("\nThe original file was removed");
+            }
+
+            Ok(message)
+        })
+    }
+
+    fn open_file_button(&mut self, ui: &mut Ui) {
         ui.spacing_mut().item_spacing.y = 15.0;
 
-        if ui.add(button).clicked() {
-            if let Some(path) = rfd::FileDialog::new().pick_file() {
-                self.file_path = path.to_str().unwrap().to_string();
+        ui.horizontal(|ui| {
+            ui.add_space(130.0);
+
+            let text = rich_text("Choose Files").color(Color32::BLACK);
+
+            let response = ui.add(button(text)).on_hover_text("Files and folders can also be dropped on the window");
+
+            if response.clicked() {
+                if let Some(paths) = rfd::FileDialog::new().pick_files() {
+                    self.add_files(paths);
+                }
             }
-        }
 
-        let file_text = rich_text(format!("File: {}", self.file_path));
-        ui.label(file_text);
+            let text = rich_text("Output Folder").color(Color32::BLACK);
+
+            let response = ui.add(button(text)).on_hover_text("Right click to use the folder of the file");
+
+            if response.clicked() {
+                if let Some(path) = rfd::FileDialog::new().pick_folder() {
+                    self.output_dir = Some(path);
+                }
+            }
+
+            if response.secondary_clicked() {
+                self.output_dir = None;
+            }
+
+            self.recent_menu(ui);
+
+        });
+
+        let output = match &self.output_dir {
+            Some(dir) => dir.display().to_string(),
+            None => "Same folder as the file".to_string(),
+        };
+        ui.label(rich_text(format!("Output: {}", output)));
 
         ui.add_space(15.0);
     }
 
+    fn recent_menu(&mut self, ui: &mut Ui) {
+        if self.recent.paths().is_empty() {
+            return;
+        }
+
+        let mut selected = None;
+
+        ui.menu_button(rich_text("Recent").color(Color32::BLACK), |ui| {
+            for path in self.recent.paths() {
+                if ui.button(rich_text(path.display().to_string())).clicked() {
+                    selected = Some(path.clone());
+                    ui.close_menu();
+                }
+            }
+        });
+
+        if let Some(path) = selected {
+            self.add_files(vec![path]);
+        }
+    }
+
     fn credentials_input(&mut self, ui: &mut Ui) {
         ui.spacing_mut().item_spacing.y = 15.0;
 
@@ -194,52 +648,413 @@ impl FileEncryptionUi {
         ui.label(rich_text("Username:"));
 
         // username input
-        ui.add(text_edit(self.credentials.user_mut()));
+        let username = ui.add(secret_edit(&mut SecretText(self.credentials.user_mut())));
 
         ui.label(rich_text("Password:"));
 
         // password input
-        ui.add(text_edit(self.credentials.passwd_mut()).password(true));
+        let password = password_edit(ui, self.credentials.passwd_mut(), &self.clipboard).unwrap_or_else(|e| {
+            self.pop_msg.write().unwrap().show("Failed to paste", e);
+            false
+        });
+
+        if self.mode == Operation::Encrypt {
+            if username.changed() || password {
+                self.strength = (!self.credentials.password().is_empty())
+                    .then(|| self.policy.estimate(&self.credentials));
+            }
+
+            self.strength_meter(ui);
+
+            ui.label(rich_text("Confirm Password:"));
 
-        ui.label(rich_text("Confrim Password:"));
+            // confirm password input
+            ui.add(secret_edit(&mut SecretText(self.credentials.confirm_passwd_mut())).password(true));
+
+            if ui.add(button(rich_text("Generate...").color(Color32::BLACK))).clicked() {
+                self.generator.open = true;
+            }
 
-        // confirm password input
-        ui.add(text_edit(self.credentials.confirm_passwd_mut()).password(true));
+            if self.generator.show(ui, &mut self.credentials, &self.clipboard) {
+                self.strength = Some(self.policy.estimate(&self.credentials));
+            }
+        }
 
         ui.add_space(15.0);
     }
 
-    pub fn argon_params_ui(&mut self, ui: &mut Ui) {
+    /// How hard the password is to guess, and how long an attacker needs at the current Argon2 cost
+    fn strength_meter(&mut self, ui: &mut Ui) {
+        let Some(strength) = self.strength else {
+            return;
+        };
+
+        let color = match strength.score {
+            0 | 1 => Color32::from_rgb(220, 53, 69),
+            2 => Color32::from_rgb(255, 200, 0),
+            _ => Color32::from_rgb(40, 167, 69),
+        };
+
+        ui.add(
+            ProgressBar::new((strength.score as f32 + 1.0) / 5.0)
+                .desired_width(250.0)
+                .fill(color)
+                .text(rich_text(strength.label()).color(Color32::BLACK))
+        );
+
+        if let Some(benchmark) = self.calibration.read().unwrap().benchmark {
+            let guess_time = benchmark.guess_time(&self.argon_params).div_f64(ATTACKER_SPEEDUP);
+            let crack_time = strength.crack_time(guess_time);
+
+            ui.label(rich_text(format!("Time to crack: {}", format_duration(crack_time))))
+                .on_hover_text(format!(
+                    "Average time for an attacker {} times faster than this machine, with the current Argon2 parameters",
+                    ATTACKER_SPEEDUP
+                ));
+        }
+
+        if let Some(warning) = strength.warning {
+            ui.label(rich_text(warning).color(Color32::from_rgb(255, 200, 0)));
+        }
+
+        if strength.score < self.policy.min_score {
+            ui.label(rich_text("Too weak to encrypt with").color(Color32::from_rgb(220, 53, 69)));
+        }
+    }
+
+    /// The Argon2 parameters, a note instead when `decrypting` since the file stores its own
+    pub fn argon_params_ui(&mut self, ui: &mut Ui, decrypting: bool) {
+        self.apply_calibration();
+
+        if decrypting {
+            ui.vertical_centered(|ui| {
+                ui.label(rich_text("Decryption uses the parameters\nstored with the encrypted data"));
+            });
+            return;
+        }
+
         ui.vertical_centered(|ui| {
             ui.spacing_mut().item_spacing.y = 15.0;
 
+            self.select_preset(ui);
+
+            let custom = self.preset == Argon2Preset::Custom;
+
             ui.label(rich_text("Memory Cost (kB)"));
 
-            ui.add(
-                Slider::new(&mut self.argon_params.m_cost, 2048..=10_000_000)
+            ui.add_enabled(
+                custom,
+                Slider::new(&mut self.argon_params.m_cost, M_COST_RANGE)
                     .drag_value_speed(100.0)
                     .custom_formatter(|v, _ctx| {
                         let v_as_int = v.round() as u32;
-                        let formatted = v_as_int.to_formatted_string(&Locale::en);
-                        format!("{}", formatted)
+                        v_as_int.to_formatted_string(&Locale::en)
                     })
             );
 
             ui.label(rich_text("Iterations"));
 
-            ui.add(
-                Slider::new(&mut self.argon_params.t_cost, 1..=5000)
+            ui.add_enabled(
+                custom,
+                Slider::new(&mut self.argon_params.t_cost, T_COST_RANGE)
                     .drag_value_speed(100.0)
                     .custom_formatter(|v, _ctx| {
                         let v_as_int = v.round() as u32;
-                        let formatted = v_as_int.to_formatted_string(&Locale::en);
-                        format!("{}", formatted)
+                        v_as_int.to_formatted_string(&Locale::en)
                     })
             );
 
             ui.label(rich_text("Parallelism"));
 
-            ui.add(Slider::new(&mut self.argon_params.p_cost, 1..=64));
+            ui.add_enabled(custom, Slider::new(&mut self.argon_params.p_cost, P_COST_RANGE));
+
+            self.cost_estimates(ui);
+
+            ui.label(rich_text("Target Unlock Time (s)"));
+
+            ui.add(Slider::new(&mut self.target_time, 0.5..=10.0).fixed_decimals(1));
+
+            self.calibrate(ui);
         });
     }
+
+    fn select_preset(&mut self, ui: &mut Ui) {
+        ui.label(rich_text("Preset"));
+
+        ComboBox::from_id_salt("argon2_preset")
+            .selected_text(self.preset.to_string())
+            .show_ui(ui, |ui| {
+                for preset in Argon2Preset::to_vec() {
+                    let value = ui.selectable_value(&mut self.preset, preset, preset.to_string());
+
+                    if value.clicked() {
+                        if let Some(params) = preset.params() {
+                            self.argon_params = params;
+                        }
+                    }
+                }
+            });
+    }
+
+    /// Show the memory usage and unlock time of the current parameters
+    fn cost_estimates(&mut self, ui: &mut Ui) {
+        let (benchmark, available_memory) = {
+            let calibration = self.calibration.read().unwrap();
+            (calibration.benchmark, calibration.available_memory)
+        };
+
+        let memory = self.argon_params.memory_usage();
+        ui.label(rich_text(format!("Memory usage: {}", format_bytes(memory))));
+
+        match benchmark {
+            Some(benchmark) => {
+                let estimate = benchmark.estimate(&self.argon_params);
+                ui.label(rich_text(format!("Estimated unlock time: {:.2} s", estimate.as_secs_f64())))
+                    .on_hover_text("Measured on this machine");
+            }
+            None => {
+                ui.label(rich_text("Estimated unlock time: measuring..."));
+            }
+        }
+
+        if available_memory > 0 && memory > available_memory * 1024 {
+            ui.label(
+                rich_text(format!(
+                    "Warning: exceeds the available memory ({})",
+                    format_bytes(available_memory * 1024)
+                ))
+                .color(Color32::from_rgb(255, 200, 0))
+            );
+        }
+    }
+
+    /// Apply the parameters picked by the last calibration
+    fn apply_calibration(&mut self) {
+        let params = self.calibration.write().unwrap().params.take();
+
+        if let Some(params) = params {
+            self.argon_params = params;
+            self.preset = Argon2Preset::Custom;
+        }
+    }
+
+    fn calibrate(&mut self, ui: &mut Ui) {
+        let running = self.calibration.read().unwrap().running;
+
+        let text = rich_text(if running { "Calibrating..." } else { "Calibrate" }).color(Color32::BLACK);
+        let button = button(text);
+
+        if ui.add_enabled(!running, button).clicked() {
+            let target = Duration::from_secs_f64(self.target_time);
+            let calibration = self.calibration.clone();
+            let running = CalibrationRunning::start(calibration.clone());
+
+            self.jobs.submit("Calibrating Argon2", move |_| {
+                // Also cleared if the job is cancelled before it starts
+                let _running = running;
+
+                let result = encryption::calibrate::calibrate(target, MAX_M_COST)?;
+                let available_memory = encryption::calibrate::available_memory();
+
+                let message = format!(
+                    "{} memory and {} iterations take about {:.2} s on this machine",
+                    format_bytes(result.params.memory_usage()),
+                    result.params.t_cost,
+                    result.benchmark.estimate(&result.params).as_secs_f64()
+                );
+
+                let mut calibration = calibration.write().unwrap();
+                calibration.available_memory = available_memory;
+                calibration.benchmark = Some(result.benchmark);
+                calibration.params = Some(result.params);
+
+                Ok(message)
+            });
+        }
+    }
+}
+
+struct EncryptOptions {
+    /// Replace the output file if it exists
+    overwrite: bool,
+
+    /// Verify the encrypted file and securely delete the original
+    remove_original: bool,
+
+    /// Minimum password strength
+    policy: PasswordPolicy,
+
+    argon_params: Argon2Params,
+}
+
+/// Encrypt `input` to `output`
+///
+/// Errors say which step failed
+fn encrypt_file(
+    input: &Path,
+    output: &Path,
+    options: EncryptOptions,
+    credentials: Credentials,
+    ctx: &JobContext,
+) -> Result<(), anyhow::Error> {
+    let cancel = ctx.cancel_token();
+    let data = std::fs::read(input).map_err(|e| anyhow::Error::from(e).context("Failed to read file"))?;
+
+    let verify_credentials = options.remove_original.then(|| credentials.duplicate());
+
+    let encrypted_data = encrypt_data_with(options.argon_params, data, credentials, &options.policy, &mut ctx.progress(), cancel)
+        .map_err(|e| e.context("Failed to encrypt file"))?;
+
+    file::write_atomic(output, &encrypted_data, options.overwrite, cancel)
+        .map_err(|e| e.context("Failed to save the encrypted file"))?;
+
+    let Some(verify_credentials) = verify_credentials else {
+        return Ok(());
+    };
+
+    ctx.set_status("Verifying");
+    verify_encrypted_file(input, output, verify_credentials, &mut ctx.progress(), cancel)
+        .map_err(|e| e.context("Verification failed, the original file was kept"))?;
+
+    ctx.set_status("Removing the original file");
+    file::secure_delete(input).map_err(|e| e.context("Failed to remove the original file"))
+}
+
+/// Check that `output` decrypts to the same content as `input`
+fn verify_encrypted_file(
+    input: &Path,
+    output: &Path,
+    credentials: Credentials,
+    progress: &mut dyn FnMut(Progress),
+    cancel: &CancelToken,
+) -> Result<(), anyhow::Error> {
+    let encrypted_data = std::fs::read(output)?;
+    let decrypted_data = decrypt_data_with(encrypted_data, credentials, progress, cancel)?;
+
+    let original = SecretBytes::from(std::fs::read(input)?);
+
+    if original.expose() != decrypted_data.expose() {
+        return Err(anyhow::anyhow!("The decrypted content does not match the original file"));
+    }
+
+    Ok(())
+}
+
+/// Decrypt `input` to `output`
+///
+/// Errors say which step failed
+fn decrypt_file(
+    input: &Path,
+    output: &Path,
+    overwrite: bool,
+    credentials: Credentials,
+    ctx: &JobContext,
+) -> Result<(), anyhow::Error> {
+    let cancel = ctx.cancel_token();
+    let data = std::fs::read(input).map_err(|e| anyhow::Error::from(e).context("Failed to read file"))?;
+
+    let decrypted_data = decrypt_data_with(data, credentials, &mut ctx.progress(), cancel)
+        .map_err(|e| e.context("Failed to decrypt file"))?;
+
+    file::write_atomic(output, decrypted_data.expose(), overwrite, cancel)
+        .map_err(|e| e.context("Failed to save decrypted file"))
+}
+
+/// Summary of a verification, lists every file that failed
+fn verify_summary(total: usize, verified: usize, failed: &[(PathBuf, anyhow::Error)]) -> String {
+    let mut message = if total == 1 && failed.is_empty() && verified == 1 {
+        "The file is intact and the credentials are correct".to_string()
+    } else {
+        let mut message = format!("{} of {} files verified", verified, total);
+        if !failed.is_empty() {
+            message.push_str(&format!(", {} failed", failed.len()));
+        }
+        message
+    };
+
+    for (path, e) in failed {
+        message.push_str(&format!("\n\n{}\n{}", path.display(), e));
+    }
+
+    message
+}
+
+/// Marks the calibration as running until dropped
+struct CalibrationRunning(Arc<RwLock<CalibrationState>>);
+
+impl CalibrationRunning {
+    fn start(calibration: Arc<RwLock<CalibrationState>>) -> Self {
+        calibration.write().unwrap().running = true;
+        Self(calibration)
+    }
+}
+
+impl Drop for CalibrationRunning {
+    fn drop(&mut self) {
+        // Poisoned if the calibration panicked, it still has to stop showing as running
+        let mut calibration = self.0.write().unwrap_or_else(|e| e.into_inner());
+        calibration.running = false;
+    }
+}
+
+/// Measure the speed and the available memory of this machine in the background
+fn measure_machine(calibration: Arc<RwLock<CalibrationState>>, repaint: &Repaint) {
+    let wake = repaint.clone();
+
+    repaint.spawn(move || {
+        let available_memory = encryption::calibrate::available_memory();
+        calibration.write().unwrap().available_memory = available_memory;
+        wake.request();
+
+        if let Ok(benchmark) = Benchmark::run() {
+            let mut calibration = calibration.write().unwrap();
+            if calibration.benchmark.is_none() {
+                calibration.benchmark = Some(benchmark);
+            }
+        }
+    });
+}
+
+/// Format a duration in the largest unit that fits, like zxcvbn does
+fn format_duration(duration: Duration) -> String {
+    const MINUTE: f64 = 60.0;
+    const HOUR: f64 = MINUTE * 60.0;
+    const DAY: f64 = HOUR * 24.0;
+    const MONTH: f64 = DAY * 31.0;
+    const YEAR: f64 = MONTH * 12.0;
+    const CENTURY: f64 = YEAR * 100.0;
+
+    let seconds = duration.as_secs_f64();
+
+    let (amount, unit) = if seconds < 1.0 {
+        return "less than a second".to_string();
+    } else if seconds < MINUTE {
+        (seconds, "second")
+    } else if seconds < HOUR {
+        (seconds / MINUTE, "minute")
+    } else if seconds < DAY {
+        (seconds / HOUR, "hour")
+    } else if seconds < MONTH {
+        (seconds / DAY, "day")
+    } else if seconds < YEAR {
+        (seconds / MONTH, "month")
+    } else if seconds < CENTURY {
+        (seconds / YEAR, "year")
+    } else {
+        return "centuries".to_string();
+    };
+
+    let amount = amount.round() as u64;
+    format!("{} {}{}", amount, unit, if amount == 1 { "" } else { "s" })
+}
+
+/// Format a size in bytes as MB or GB
+pub(super) fn format_bytes(bytes: u64) -> String {
+    let mb = bytes as f64 / (1024.0 * 1024.0);
+
+    if mb >= 1024.0 {
+        format!("{:.2} GB", mb / 1024.0)
+    } else {
+        format!("{:.1} MB", mb)
+    }
 }
@@ -0,0 +1,331 @@
+use eframe::egui::{ vec2, Color32, ComboBox, Label, ProgressBar, ScrollArea, Ui };
+use std::path::PathBuf;
+use encryption::{ file, prelude::* };
+use super::*;
+use super::file_encryption::{ format_bytes, Operation, Overwrite };
+use super::jobs::state_color;
+use crate::jobs::{ JobId, JobManager, JobState };
+
+/// Where a file of the queue is
+pub enum EntryState {
+    /// Waits for the user to run the queue
+    Ready,
+
+    /// The output file already existed and the queue skips existing files
+    Skipped,
+
+    /// Handed to the worker pool, queued or running
+    Submitted(JobId),
+
+    /// How the job ended, kept when the job is cleared from the jobs window
+    Finished { state: JobState, message: String },
+}
+
+pub struct QueueEntry {
+    pub path: PathBuf,
+
+    pub operation: Operation,
+
+    /// Metadata read from the header, `None` if it is not an encrypted file
+    pub info: Option<EncryptedInfo>,
+
+    pub state: EntryState,
+}
+
+impl QueueEntry {
+    /// Encrypted files and `.ncrypt` files are decrypted, everything else is encrypted
+    fn new(path: PathBuf) -> Self {
+        let info = file::read_info(&path).ok();
+        let encrypted = info.is_some() || file::is_encrypted_path(&path);
+        let operation = if encrypted { Operation::Decrypt } else { Operation::Encrypt };
+
+        Self { path, operation, info, state: EntryState::Ready }
+    }
+
+    pub fn is_ready(&self) -> bool {
+        matches!(self.state, EntryState::Ready)
+    }
+
+    pub fn is_submitted(&self) -> bool {
+        matches!(self.state, EntryState::Submitted(_))
+    }
+
+    /// Failed or cancelled, the job can be run again
+    pub fn can_retry(&self) -> bool {
+        matches!(self.state, EntryState::Finished { state: JobState::Failed | JobState::Cancelled, .. })
+    }
+
+    fn is_done(&self) -> bool {
+        matches!(self.state, EntryState::Skipped | EntryState::Finished { .. })
+    }
+
+    fn name(&self) -> String {
+        self.path.file_name().unwrap_or(self.path.as_os_str()).to_string_lossy().to_string()
+    }
+
+    /// The full path, and the Argon2 parameters of an encrypted file
+    fn details(&self, benchmark: Option<Benchmark>) -> String {
+        let mut details = self.path.display().to_string();
+
+        if let Some(info) = &self.info {
+            let params = &info.argon2_params;
+            details.push_str(&format!(
+                "\nArgon2: {} memory, {} iterations, {} lanes",
+                format_bytes(params.memory_usage()),
+                params.t_cost,
+                params.p_cost
+            ));
+
+            if let Some(benchmark) = benchmark {
+                details.push_str(&format!("\nEstimated unlock time: {:.2} s", benchmark.estimate(params).as_secs_f64()));
+            }
+        }
+
+        details
+    }
+}
+
+/// Files waiting to be encrypted or decrypted, each one runs as its own job
+pub struct FileQueue {
+    pub entries: Vec<QueueEntry>,
+
+    /// What to do when the output of a file already exists
+    pub overwrite: Overwrite,
+}
+
+impl Default for FileQueue {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl FileQueue {
+    pub fn new() -> Self {
+        Self { entries: Vec::new(), overwrite: Overwrite::Rename }
+    }
+
+    /// Add the files that are not in the queue yet
+    pub fn add(&mut self, paths: impl IntoIterator<Item = PathBuf>) {
+        for path in paths {
+            if !self.entries.iter().any(|entry| entry.path == path) {
+                self.entries.push(QueueEntry::new(path));
+            }
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    /// Indices of the entries the next run starts
+    pub fn ready(&self) -> Vec<usize> {
+        (0..self.entries.len()).filter(|i| self.entries[*i].is_ready()).collect()
+    }
+
+    /// Record how the finished jobs of the queue ended
+    pub fn update(&mut self, jobs: &JobManager, finished: &[JobId]) {
+        for entry in &mut self.entries {
+            let EntryState::Submitted(id) = entry.state else {
+                continue;
+            };
+
+            if !finished.contains(&id) {
+                continue;
+            }
+
+            if let Some(job) = jobs.get(id) {
+                entry.state = EntryState::Finished { state: job.state, message: job.message.clone() };
+            }
+        }
+    }
+
+    /// What the last run did, `None` until every entry is done
+    pub fn summary(&self) -> Option<String> {
+        if self.entries.is_empty() || !self.entries.iter().all(|entry| entry.is_done()) {
+            return None;
+        }
+
+        let (mut encrypted, mut decrypted, mut failed, mut cancelled, mut skipped) = (0, 0, 0, 0, 0);
+
+        for entry in &self.entries {
+            match (&entry.state, entry.operation) {
+                (EntryState::Finished { state: JobState::Succeeded, .. }, Operation::Encrypt) => encrypted += 1,
+                (EntryState::Finished { state: JobState::Succeeded, .. }, Operation::Decrypt) => decrypted += 1,
+                (EntryState::Finished { state: JobState::Cancelled, .. }, _) => cancelled += 1,
+                (EntryState::Finished { .. }, _) => failed += 1,
+                (_, _) => skipped += 1,
+            }
+        }
+
+        let parts: Vec<String> = [
+            (encrypted, "encrypted"),
+            (decrypted, "decrypted"),
+            (failed, "failed"),
+            (cancelled, "cancelled"),
+            (skipped, "skipped"),
+        ]
+        .iter()
+        .filter(|(count, _)| *count > 0)
+        .map(|(count, label)| format!("{} {}", count, label))
+        .collect();
+
+        Some(format!("Finished: {}", parts.join(", ")))
+    }
+
+    /// The list of files with their state, returns what the user changed
+    ///
+    /// ### Arguments
+    ///
+    /// - `jobs` - Where the progress of the submitted entries comes from
+    /// - `benchmark` - Used to estimate the unlock time of encrypted files
+    pub fn show(&mut self, ui: &mut Ui, jobs: &JobManager, benchmark: Option<Benchmark>) -> QueueChanges {
+        let mut changes = QueueChanges::default();
+
+        if self.entries.is_empty() {
+            ui.label(rich_text("No files selected"));
+            return changes;
+        }
+
+        let mut remove = None;
+
+        ScrollArea::vertical().id_salt("file_queue").max_height(130.0).show(ui, |ui| {
+            for (i, entry) in self.entries.iter_mut().enumerate() {
+                ui.horizontal(|ui| {
+                    entry_state(ui, jobs, entry);
+
+                    ui.add_sized(vec2(180.0, 18.0), Label::new(rich_text(entry.name())).truncate())
+                        .on_hover_text(entry.details(benchmark));
+
+                    let editable = entry.is_ready() || entry.can_retry();
+                    ui.add_enabled_ui(editable, |ui| {
+                        ComboBox::from_id_salt(("queue_operation", i))
+                            .width(80.0)
+                            .selected_text(entry.operation.to_string())
+                            .show_ui(ui, |ui| {
+                                for operation in [Operation::Encrypt, Operation::Decrypt] {
+                                    if ui.selectable_value(&mut entry.operation, operation, operation.to_string()).clicked() {
+                                        changes.operation = true;
+                                    }
+                                }
+                            });
+                    });
+
+                    if entry.operation == Operation::Decrypt {
+                        let preview = ui.add_enabled(!entry.is_submitted(), button(rich_text("Preview").color(Color32::BLACK)))
+                            .on_hover_text("Decrypt in memory and show the content, nothing is written to disk");
+
+                        if preview.clicked() {
+                            changes.preview = Some(i);
+                        }
+
+                        let open = ui.add_enabled(!entry.is_submitted(), button(rich_text("Open in App").color(Color32::BLACK)))
+                            .on_hover_text("Decrypt to a private folder, open it with the default app and encrypt it back when it is saved");
+
+                        if open.clicked() {
+                            changes.open_in_app = Some(i);
+                        }
+                    }
+
+                    if entry.can_retry() && ui.add(button(rich_text("Retry").color(Color32::BLACK))).clicked() {
+                        changes.retry.push(i);
+                    }
+
+                    if ui.add_enabled(!entry.is_submitted(), button(rich_text("Remove").color(Color32::BLACK))).clicked() {
+                        remove = Some(i);
+                    }
+                });
+
+                entry_details(ui, jobs, entry);
+            }
+        });
+
+        if let Some(i) = remove {
+            self.entries.remove(i);
+            changes.operation = true;
+        }
+
+        ui.horizontal(|ui| {
+            ui.label(rich_text("If the output exists:"));
+
+            ComboBox::from_id_salt("queue_overwrite")
+                .selected_text(self.overwrite.to_string())
+                .show_ui(ui, |ui| {
+                    for option in [Overwrite::Replace, Overwrite::Rename, Overwrite::Skip] {
+                        ui.selectable_value(&mut self.overwrite, option, option.to_string());
+                    }
+                });
+
+            let retry: Vec<usize> = (0..self.entries.len()).filter(|i| self.entries[*i].can_retry()).collect();
+            if ui.add_enabled(!retry.is_empty(), button(rich_text("Retry Failed").color(Color32::BLACK))).clicked() {
+                changes.retry = retry;
+            }
+
+            let done = self.entries.iter().any(|entry| entry.is_done());
+            if ui.add_enabled(done, button(rich_text("Clear Finished").color(Color32::BLACK))).clicked() {
+                self.entries.retain(|entry| !entry.is_done());
+                changes.operation = true;
+            }
+        });
+
+        if let Some(summary) = self.summary() {
+            ui.label(rich_text(summary));
+        }
+
+        changes
+    }
+}
+
+/// What the user changed in the queue this frame
+#[derive(Default)]
+pub struct QueueChanges {
+    /// An operation was changed or entries were removed
+    pub operation: bool,
+
+    /// Entries to run again
+    pub retry: Vec<usize>,
+
+    /// Entry to decrypt in memory and show in the viewer
+    pub preview: Option<usize>,
+
+    /// Entry to edit in the app the system opens it with
+    pub open_in_app: Option<usize>,
+}
+
+fn entry_state(ui: &mut Ui, jobs: &JobManager, entry: &QueueEntry) {
+    let (text, color) = match &entry.state {
+        EntryState::Ready => ("Ready".to_string(), Color32::WHITE),
+        EntryState::Skipped => ("Skipped".to_string(), Color32::GRAY),
+        EntryState::Submitted(id) => {
+            let state = jobs.get(*id).map(|job| job.state).unwrap_or(JobState::Queued);
+            (state.to_string(), state_color(state))
+        }
+        EntryState::Finished { state, .. } => (state.to_string(), state_color(*state)),
+    };
+
+    let response = ui.add_sized(vec2(70.0, 18.0), Label::new(rich_text(text).color(color)));
+
+    if let EntryState::Finished { state: JobState::Succeeded, message } = &entry.state {
+        response.on_hover_text(message);
+    }
+}
+
+/// The progress of a running entry, the error of a failed one
+fn entry_details(ui: &mut Ui, jobs: &JobManager, entry: &QueueEntry) {
+    match &entry.state {
+        EntryState::Submitted(id) => {
+            let Some(job) = jobs.get(*id).filter(|job| job.state == JobState::Running) else {
+                return;
+            };
+
+            let bar = match job.progress.and_then(|p| p.fraction()) {
+                Some(fraction) => ProgressBar::new(fraction).show_percentage(),
+                None => ProgressBar::new(0.0).animate(true),
+            };
+            ui.add(bar.desired_width(400.0).desired_height(10.0));
+        }
+        EntryState::Finished { state: JobState::Failed, message } => {
+            ui.add(Label::new(rich_text(message).size(12.0).color(state_color(JobState::Failed))).wrap());
+        }
+        _ => {}
+    }
+}
@@ -0,0 +1,233 @@
+use eframe::egui::{ vec2, Align2, Color32, ComboBox, DragValue, Frame, Label, RichText, Ui, Slider, Vec2b, Window };
+use encryption::generate::{ self, Generated, KeyEncoding, PassphraseOptions, PasswordOptions };
+use encryption::prelude::Credentials;
+use std::fmt;
+use super::*;
+
+/// What the generator makes
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub enum SecretKind {
+    Passphrase,
+    Password,
+    Key,
+}
+
+impl SecretKind {
+    pub fn to_vec() -> Vec<Self> {
+        vec![Self::Passphrase, Self::Password, Self::Key]
+    }
+}
+
+impl fmt::Display for SecretKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            SecretKind::Passphrase => "Passphrase",
+            SecretKind::Password => "Password",
+            SecretKind::Key => "Random Key",
+        };
+        write!(f, "{}", name)
+    }
+}
+
+/// Window that generates passphrases, passwords and keys
+pub struct GeneratorUi {
+    pub open: bool,
+
+    pub kind: SecretKind,
+
+    pub passphrase: PassphraseOptions,
+
+    pub password: PasswordOptions,
+
+    /// Length of a random key in bytes
+    pub key_bytes: usize,
+
+    pub key_encoding: KeyEncoding,
+
+    /// The last generated secret, zeroized when the window closes
+    pub generated: Option<Generated>,
+
+    pub error: Option<String>,
+}
+
+impl Default for GeneratorUi {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl GeneratorUi {
+    pub fn new() -> Self {
+        Self {
+            open: false,
+            kind: SecretKind::Passphrase,
+            passphrase: PassphraseOptions::default(),
+            password: PasswordOptions::default(),
+            key_bytes: 32,
+            key_encoding: KeyEncoding::Hex,
+            generated: None,
+            error: None,
+        }
+    }
+
+    /// Show the generator window
+    ///
+    /// Returns true when the generated secret has been put in the credentials
+    pub fn show(&mut self, ui: &mut Ui, credentials: &mut Credentials, clipboard: &Clipboard) -> bool {
+        if !self.open {
+            return false;
+        }
+
+        let mut open = self.open;
+        let mut used = false;
+
+        Window::new(rich_text("Generate a Secret").size(16.0))
+            .open(&mut open)
+            .anchor(Align2::CENTER_CENTER, [0.0, 0.0])
+            .collapsible(false)
+            .resizable(Vec2b::new(false, false))
+            .frame(Frame::window(&ui.style().clone()).fill(ui.visuals().window_fill))
+            .show(ui.ctx(), |ui| {
+                ui.set_min_size(vec2(350.0, 200.0));
+
+                ui.vertical_centered(|ui| {
+                    ui.spacing_mut().item_spacing.y = 10.0;
+
+                    self.select_kind(ui);
+
+                    match self.kind {
+                        SecretKind::Passphrase => self.passphrase_options(ui),
+                        SecretKind::Password => self.password_options(ui),
+                        SecretKind::Key => self.key_options(ui),
+                    }
+
+                    if ui.add(button(rich_text("Generate").color(Color32::BLACK))).clicked() {
+                        self.generate();
+                    }
+
+                    used = self.generated_secret(ui, credentials, clipboard);
+                });
+            });
+
+        if !open || used {
+            self.close();
+        }
+
+        used
+    }
+
+    /// Close the window and zeroize the generated secret
+    pub fn close(&mut self) {
+        self.open = false;
+        self.generated = None;
+        self.error = None;
+    }
+
+    fn select_kind(&mut self, ui: &mut Ui) {
+        ComboBox::from_id_salt("secret_kind")
+            .selected_text(self.kind.to_string())
+            .show_ui(ui, |ui| {
+                for kind in SecretKind::to_vec() {
+                    if ui.selectable_value(&mut self.kind, kind, kind.to_string()).clicked() {
+                        self.generated = None;
+                    }
+                }
+            });
+    }
+
+    fn passphrase_options(&mut self, ui: &mut Ui) {
+        ui.label(rich_text("Words"));
+        ui.add(Slider::new(&mut self.passphrase.words, 3..=16));
+
+        ui.horizontal(|ui| {
+            ui.label(rich_text("Separator"));
+            ui.add(text_edit(&mut self.passphrase.separator).desired_width(40.0));
+            ui.checkbox(&mut self.passphrase.capitalize, rich_text("Capitalize"));
+        });
+    }
+
+    fn password_options(&mut self, ui: &mut Ui) {
+        ui.label(rich_text("Length"));
+        ui.add(Slider::new(&mut self.password.length, 8..=128));
+
+        ui.horizontal(|ui| {
+            ui.checkbox(&mut self.password.lowercase, rich_text("a-z"));
+            ui.checkbox(&mut self.password.uppercase, rich_text("A-Z"));
+            ui.checkbox(&mut self.password.digits, rich_text("0-9"));
+            ui.checkbox(&mut self.password.symbols, rich_text("!@#$"));
+        });
+
+        ui.checkbox(&mut self.password.exclude_ambiguous, rich_text("Exclude look-alikes (0 O 1 l I)"));
+    }
+
+    fn key_options(&mut self, ui: &mut Ui) {
+        ui.horizontal(|ui| {
+            ui.label(rich_text("Bytes"));
+            ui.add(DragValue::new(&mut self.key_bytes).range(16..=128));
+
+            ComboBox::from_id_salt("key_encoding")
+                .selected_text(self.key_encoding.to_string())
+                .show_ui(ui, |ui| {
+                    for encoding in KeyEncoding::to_vec() {
+                        ui.selectable_value(&mut self.key_encoding, encoding, encoding.to_string());
+                    }
+                });
+        });
+    }
+
+    fn generate(&mut self) {
+        let result = match self.kind {
+            SecretKind::Passphrase => generate::passphrase(&self.passphrase),
+            SecretKind::Password => generate::password(&self.password),
+            SecretKind::Key => generate::key(self.key_bytes, self.key_encoding),
+        };
+
+        match result {
+            Ok(generated) => {
+                self.generated = Some(generated);
+                self.error = None;
+            }
+            Err(e) => {
+                self.generated = None;
+                self.error = Some(format!("{}", e));
+            }
+        }
+    }
+
+    /// Show the generated secret, returns true when it has been put in the credentials
+    fn generated_secret(&mut self, ui: &mut Ui, credentials: &mut Credentials, clipboard: &Clipboard) -> bool {
+        if let Some(error) = &self.error {
+            ui.label(rich_text(error).color(Color32::from_rgb(255, 200, 0)));
+        }
+
+        let Some(generated) = &self.generated else {
+            return false;
+        };
+
+        ui.add(
+            Label::new(RichText::new(generated.secret.expose()).monospace().size(14.0).color(Color32::WHITE))
+                .wrap()
+        );
+
+        ui.label(rich_text(format!("Entropy: {:.0} bits", generated.entropy)));
+
+        if ui.add(button(rich_text("Use as Password").color(Color32::BLACK))).clicked() {
+            let password = credentials.passwd_mut();
+            password.clear();
+            password.push_str(generated.secret.expose());
+            credentials.copy_passwd_to_confirm();
+            return true;
+        }
+
+        let copy = ui.add(button(rich_text("Copy").color(Color32::BLACK)))
+            .on_hover_text("The clipboard is cleared after the timeout set in the settings");
+
+        if copy.clicked() {
+            if let Err(e) = clipboard.copy_secret(generated.secret.expose()) {
+                self.error = Some(format!("{}", e));
+            }
+        }
+
+        false
+    }
+}
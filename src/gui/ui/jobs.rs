@@ -0,0 +1,111 @@
+use eframe::egui::{ vec2, Align2, Color32, Frame, Label, ProgressBar, ScrollArea, Ui, Window };
+use crate::jobs::{ Job, JobId, JobManager, JobState };
+use super::*;
+
+/// Window listing every job with its progress or outcome
+pub struct JobsUi {
+    pub open: bool,
+}
+
+impl Default for JobsUi {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl JobsUi {
+    pub fn new() -> Self {
+        Self { open: false }
+    }
+
+    pub fn show(&mut self, ui: &mut Ui, jobs: &mut JobManager) {
+        if !self.open {
+            return;
+        }
+
+        let mut open = self.open;
+        let mut clear = false;
+        let mut cancel = None;
+
+        Window::new(rich_text("Jobs").size(16.0))
+            .open(&mut open)
+            .anchor(Align2::RIGHT_BOTTOM, [-10.0, -10.0])
+            .collapsible(true)
+            .resizable(false)
+            .frame(Frame::window(&ui.style().clone()).fill(ui.visuals().window_fill))
+            .show(ui.ctx(), |ui| {
+                ui.set_width(340.0);
+
+                if jobs.jobs().is_empty() {
+                    ui.label(rich_text("Nothing is running"));
+                    return;
+                }
+
+                ScrollArea::vertical().max_height(260.0).show(ui, |ui| {
+                    // Newest first
+                    for job in jobs.jobs().iter().rev() {
+                        job_row(ui, job, &mut cancel);
+                        ui.separator();
+                    }
+                });
+
+                let finished = jobs.jobs().iter().any(|job| job.state.is_finished());
+                if ui.add_enabled(finished, button(rich_text("Clear Finished").color(Color32::BLACK))).clicked() {
+                    clear = true;
+                }
+            });
+
+        if let Some(id) = cancel {
+            jobs.cancel(id);
+        }
+
+        if clear {
+            jobs.clear_finished();
+        }
+
+        self.open = open;
+    }
+}
+
+fn job_row(ui: &mut Ui, job: &Job, cancel: &mut Option<JobId>) {
+    ui.horizontal(|ui| {
+        ui.label(rich_text(job.state.to_string()).color(state_color(job.state)));
+        ui.add(Label::new(rich_text(&job.title)).truncate());
+
+        if !job.state.is_finished() && ui.add(button(rich_text("Cancel").color(Color32::BLACK))).clicked() {
+            *cancel = Some(job.id);
+        }
+    });
+
+    if job.state == JobState::Running {
+        if !job.status.is_empty() {
+            ui.label(rich_text(&job.status));
+        }
+
+        let bar = match job.progress.and_then(|p| p.fraction()) {
+            Some(fraction) => ProgressBar::new(fraction).show_percentage(),
+            None => ProgressBar::new(0.0).animate(true),
+        };
+        ui.add(bar.desired_width(320.0).desired_height(12.0));
+
+        if let Some(progress) = job.progress {
+            ui.label(rich_text(progress.to_string()).size(12.0));
+        }
+    }
+
+    if !job.message.is_empty() {
+        ScrollArea::vertical().id_salt(job.id).max_height(80.0).show(ui, |ui| {
+            ui.add_sized(vec2(320.0, 0.0), Label::new(rich_text(&job.message).size(12.0)).wrap());
+        });
+    }
+}
+
+pub(super) fn state_color(state: JobState) -> Color32 {
+    match state {
+        JobState::Queued => Color32::GRAY,
+        JobState::Running => Color32::from_rgb(0, 123, 255),
+        JobState::Succeeded => Color32::from_rgb(40, 167, 69),
+        JobState::Failed => Color32::from_rgb(220, 53, 69),
+        JobState::Cancelled => Color32::from_rgb(255, 200, 0),
+    }
+}
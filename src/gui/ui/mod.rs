@@ -1,25 +1,41 @@
-use eframe::egui::{Button, Color32, TextEdit, RichText, Sense, WidgetText};
+use eframe::egui::{Button, Color32, Context, TextBuffer, TextEdit, RichText, Sense, Ui, WidgetText, text_edit::TextEditState};
+use encryption::{progress::Progress, secret::SecretString, zeroize::Zeroize};
+use std::fmt;
+use std::ops::Range;
+use std::path::Path;
+use crate::gui::clipboard::Clipboard;
 
+pub mod edit_sessions;
 pub mod file_encryption;
+pub mod file_queue;
+pub mod generator;
+pub mod jobs;
+pub mod notes;
+pub mod preview;
+pub mod settings;
 pub mod text_hashing;
+pub mod text_encryption;
 
+#[derive(Default)]
 pub struct WindowMsg {
     pub open: bool,
     pub message: String,
     pub title: String,
 }
 
-impl Default for WindowMsg {
-    fn default() -> Self {
-        Self {
-            open: false,
-            message: String::new(),
-            title: String::new()
-        }
+impl WindowMsg {
+    /// Open the window with `message`, replacing the message shown
+    pub fn show(&mut self, title: impl Into<String>, message: impl fmt::Display) {
+        self.open = true;
+        self.title = title.into();
+        self.message = message.to_string();
     }
 }
 
-
+/// The name of the file, the whole path if it has none
+pub fn file_name(path: &Path) -> String {
+    path.file_name().unwrap_or(path.as_os_str()).to_string_lossy().to_string()
+}
 
 pub fn rich_text(text: impl Into<String>) -> RichText {
     RichText::new(text)
@@ -36,4 +52,70 @@ pub fn text_edit(text: &mut String) -> TextEdit<'_> {
     TextEdit::singleline(text)
         .desired_width(200.0)
         .text_color(Color32::WHITE)
+}
+
+/// A [TextEdit] that edits a [SecretString] in place
+pub fn secret_edit<'a>(text: &'a mut SecretText<'_>) -> TextEdit<'a> {
+    TextEdit::singleline(text)
+        .desired_width(200.0)
+        .text_color(Color32::WHITE)
+}
+
+/// Drop the state of every [TextEdit], with the undo history that holds copies of their text
+pub fn forget_text_edits(ctx: &Context) {
+    ctx.data_mut(|data| data.remove_by_type::<TextEditState>());
+}
+
+/// A password field with a paste button on its right, returns whether the password changed
+///
+/// The field stays centered like the fields above and below it
+pub fn password_edit(ui: &mut Ui, password: &mut SecretString, clipboard: &Clipboard) -> Result<bool, anyhow::Error> {
+    ui.horizontal(|ui| {
+        ui.add_space(((ui.available_width() - 200.0) / 2.0).max(0.0));
+
+        let changed = ui.add(secret_edit(&mut SecretText(password)).password(true)).changed();
+
+        let paste = ui.add(button(rich_text("Paste").color(Color32::BLACK)))
+            .on_hover_text("The clipboard is cleared after the timeout set in the settings");
+
+        if !paste.clicked() {
+            return Ok(changed);
+        }
+
+        let mut text = clipboard.paste_secret()?;
+        password.clear();
+        password.push_str(text.trim_end_matches(['\r', '\n']));
+        text.zeroize();
+
+        Ok(true)
+    })
+    .inner
+}
+
+/// Lets a [TextEdit] edit a [SecretString] in place
+///
+/// egui still copies the text while it is edited, once per frame and in the undo history kept in the
+/// [TextEditState] of the field. [forget_text_edits] drops that history when the secrets are wiped.
+pub struct SecretText<'a>(pub &'a mut SecretString);
+
+impl TextBuffer for SecretText<'_> {
+    fn is_mutable(&self) -> bool {
+        true
+    }
+
+    fn as_str(&self) -> &str {
+        self.0.expose()
+    }
+
+    fn insert_text(&mut self, text: &str, char_index: usize) -> usize {
+        let byte_index = self.byte_index_from_char_index(char_index);
+        self.0.insert_str(byte_index, text);
+        text.chars().count()
+    }
+
+    fn delete_char_range(&mut self, char_range: Range<usize>) {
+        let start = self.byte_index_from_char_index(char_range.start);
+        let end = self.byte_index_from_char_index(char_range.end);
+        self.0.remove_range(start..end);
+    }
 }
\ No newline at end of file
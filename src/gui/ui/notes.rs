@@ -0,0 +1,648 @@
+use eframe::egui::{ text_edit::TextEditState, vec2, Align2, Color32, Frame, Id, Label, ScrollArea, TextEdit, TextStyle, Ui, ViewportCommand, Window };
+use std::path::PathBuf;
+use std::sync::{ Arc, RwLock };
+use encryption::{ file, prelude::*, strength::PasswordPolicy };
+use super::*;
+use crate::gui::clipboard::Clipboard;
+use crate::hashing;
+use crate::jobs::{ JobId, JobManager, JobSender };
+
+/// What the user asked for while the note had unsaved changes
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub enum GuardedAction {
+    New,
+    Open,
+    Close,
+
+    /// Close the app
+    Exit,
+}
+
+/// Sent by the open and save jobs, applied on the next frame
+enum NoteEvent {
+    Opened {
+        path: PathBuf,
+        text: SecretString,
+        argon_params: Argon2Params,
+        credentials: Credentials,
+    },
+    Saved {
+        path: PathBuf,
+
+        /// Hash of the text that was saved, the user may have kept typing since
+        hash: blake3::Hash,
+        argon_params: Argon2Params,
+        credentials: Credentials,
+    },
+
+    /// The unsaved changes were encrypted after a wipe
+    Stashed(Stash),
+
+    /// The stashed changes were decrypted
+    Restored {
+        path: Option<PathBuf>,
+        text: SecretString,
+        file_params: Option<Argon2Params>,
+        saved: blake3::Hash,
+        credentials: Credentials,
+    },
+
+    /// The typed credentials of a job that failed, given back so they can be corrected
+    Failed { credentials: Credentials },
+}
+
+/// Unsaved changes of a note, encrypted when the secrets were wiped
+struct Stash {
+    /// The file of the note, `None` for a new note
+    path: Option<PathBuf>,
+
+    file_params: Option<Argon2Params>,
+
+    /// Fingerprint of the note as it was last saved
+    saved: blake3::Hash,
+
+    /// Encrypted with the credentials of the note, `None` if a new note had no credentials and was lost
+    encrypted: Option<Vec<u8>>,
+}
+
+/// Edit a text file encrypted with nCrypt, the plaintext only lives in memory
+pub struct NotesUi {
+    pub open: bool,
+
+    /// The encrypted file, `None` for a note that was never saved
+    pub path: Option<PathBuf>,
+
+    pub text: SecretString,
+
+    /// Typed to open a note or to save a new one
+    pub credentials: Credentials,
+
+    /// The credentials the note is encrypted with, set once it is opened or saved
+    note_credentials: Option<Credentials>,
+
+    /// Argon2 parameters of the file, `None` for a new note
+    file_params: Option<Argon2Params>,
+
+    /// Save with the parameters of the encryption tab instead of those of the file
+    pub update_params: bool,
+
+    /// Hash of the text as it was opened or last saved
+    saved: blake3::Hash,
+
+    pub modified: bool,
+
+    /// Waits for the user to save or discard the changes
+    guard: Option<GuardedAction>,
+
+    /// The open or save job running
+    job: Option<JobId>,
+
+    /// The changes were saved or discarded before closing the app
+    exit: bool,
+
+    /// The note was closed, the undo history of the editor still holds its text
+    forget: bool,
+
+    /// Unsaved changes of the note open when the secrets were wiped
+    stash: Option<Stash>,
+
+    events: Arc<RwLock<Option<NoteEvent>>>,
+
+    pub pop_msg: Arc<RwLock<WindowMsg>>,
+
+    pub clipboard: Clipboard,
+
+    pub jobs: JobSender,
+}
+
+impl NotesUi {
+    pub fn new(pop_msg: Arc<RwLock<WindowMsg>>, jobs: JobSender, clipboard: Clipboard) -> Self {
+        Self {
+            open: false,
+            path: None,
+            text: SecretString::new(),
+            credentials: Credentials::default(),
+            note_credentials: None,
+            file_params: None,
+            update_params: false,
+            saved: hashing::fingerprint(b""),
+            modified: false,
+            guard: None,
+            job: None,
+            exit: false,
+            forget: false,
+            stash: None,
+            events: Arc::new(RwLock::new(None)),
+            pop_msg,
+            clipboard,
+            jobs,
+        }
+    }
+
+    /// Wipe the note and the credentials
+    ///
+    /// Unsaved changes are first encrypted by a job with the credentials of the note, they can be restored
+    /// with the same credentials. The changes of a new note are lost if no valid credentials were typed yet.
+    pub fn clear(&mut self, policy: &PasswordPolicy) {
+        // Jobs started before the wipe can't bring their note or credentials back
+        self.events = Arc::new(RwLock::new(None));
+
+        self.stash_changes(policy);
+        self.credentials.destroy();
+        self.close_note();
+    }
+
+    /// Whether credentials or the text of a note are in memory
+    pub fn has_secrets(&self) -> bool {
+        !self.credentials.is_empty() || self.note_credentials.is_some() || !self.text.is_empty()
+    }
+
+    /// Ask to save the changes before `action`, true if the note has none and `action` can go ahead
+    pub fn guard(&mut self, action: GuardedAction) -> bool {
+        if !self.modified {
+            return true;
+        }
+
+        self.guard = Some(action);
+        false
+    }
+
+    pub fn show(&mut self, ui: &mut Ui, jobs: &JobManager, argon_params: &Argon2Params, policy: &PasswordPolicy) {
+        self.apply_events();
+
+        if std::mem::take(&mut self.exit) {
+            ui.ctx().send_viewport_cmd(ViewportCommand::Close);
+        }
+
+        if std::mem::take(&mut self.forget) {
+            ui.ctx().data_mut(|data| data.remove::<TextEditState>(Id::new("note_editor")));
+        }
+
+        let busy = self.job.and_then(|id| jobs.get(id)).is_some_and(|job| !job.state.is_finished());
+
+        // Shown on every tab, an exit can be guarded from anywhere
+        self.guard_window(ui, busy, argon_params, policy);
+
+        if !self.open {
+            return;
+        }
+
+        ui.spacing_mut().item_spacing.y = 10.0;
+
+        ui.add_enabled_ui(!busy, |ui| {
+            self.toolbar(ui, argon_params, policy);
+
+            if self.note_credentials.is_none() {
+                self.credentials_input(ui);
+            } else {
+                self.params_input(ui, argon_params);
+            }
+        });
+
+        self.stash_banner(ui, busy);
+        self.editor(ui);
+    }
+
+    /// Encrypt the unsaved changes in a job, they arrive in [Self::stash]
+    fn stash_changes(&mut self, policy: &PasswordPolicy) {
+        if !self.modified {
+            return;
+        }
+
+        let credentials = match &self.note_credentials {
+            Some(credentials) => {
+                let mut credentials = credentials.duplicate();
+                credentials.copy_passwd_to_confirm();
+                credentials
+            }
+            // Saving a new note checks the same
+            None if self.credentials.is_confirmed().and_then(|_| policy.check(&self.credentials)).is_ok() => {
+                self.credentials.duplicate()
+            }
+            None => {
+                self.stash = Some(Stash { path: None, file_params: None, saved: self.saved, encrypted: None });
+                return;
+            }
+        };
+
+        let mut stash = Stash {
+            path: self.path.clone(),
+            file_params: self.file_params.clone(),
+            saved: self.saved,
+            encrypted: None,
+        };
+        let argon_params = self.file_params.clone().unwrap_or_else(Argon2Params::fast);
+        let name = self.path.as_deref().map(file_name).unwrap_or_else(|| "the new note".to_string());
+
+        // Encrypted in place
+        let data = self.text.expose().as_bytes().to_vec();
+        let events = self.events.clone();
+
+        self.jobs.submit(format!("Keeping the changes of {} encrypted", name), move |ctx| {
+            let encrypted_data = encrypt_data_with(
+                argon_params,
+                data,
+                credentials,
+                &PasswordPolicy::none(),
+                &mut ctx.progress(),
+                ctx.cancel_token(),
+            )
+            .map_err(|e| e.context("Failed to encrypt the changes"))?;
+
+            ctx.cancel_token().check()?;
+            stash.encrypted = Some(encrypted_data);
+            *events.write().unwrap() = Some(NoteEvent::Stashed(stash));
+
+            Ok("Enter the credentials of the note in the Secure Notes tab to restore the changes".to_string())
+        });
+    }
+
+    fn apply_events(&mut self) {
+        let Some(event) = self.events.write().unwrap().take() else {
+            return;
+        };
+
+        match event {
+            NoteEvent::Opened { path, text, argon_params, credentials } => {
+                self.saved = hashing::fingerprint(text.expose().as_bytes());
+                self.modified = false;
+                self.text = text;
+                self.path = Some(path);
+                self.file_params = Some(argon_params);
+                self.note_credentials = Some(credentials);
+            }
+            NoteEvent::Saved { path, hash, argon_params, credentials } => {
+                self.saved = hash;
+                self.modified = hashing::fingerprint(self.text.expose().as_bytes()) != hash;
+                self.path = Some(path);
+                self.file_params = Some(argon_params);
+                self.note_credentials = Some(credentials);
+                self.update_params = false;
+
+                // The user chose to save before going on
+                if let Some(action) = self.guard.filter(|_| !self.modified) {
+                    self.guard = None;
+                    self.run(action);
+                }
+            }
+            NoteEvent::Stashed(stash) => self.stash = Some(stash),
+            NoteEvent::Restored { path, text, file_params, saved, credentials } => {
+                self.saved = saved;
+                self.modified = hashing::fingerprint(text.expose().as_bytes()) != saved;
+                self.text = text;
+                self.path = path;
+                self.file_params = file_params;
+                self.note_credentials = Some(credentials);
+                self.stash = None;
+            }
+            NoteEvent::Failed { credentials } => {
+                if self.credentials.is_empty() {
+                    self.credentials = credentials;
+                }
+            }
+        }
+    }
+
+    fn toolbar(&mut self, ui: &mut Ui, argon_params: &Argon2Params, policy: &PasswordPolicy) {
+        ui.horizontal(|ui| {
+            ui.add_space(90.0);
+
+            if ui.add(button(rich_text("New").color(Color32::BLACK))).clicked() && self.guard(GuardedAction::New) {
+                self.run(GuardedAction::New);
+            }
+
+            let open = ui.add_enabled(self.note_credentials.is_none(), button(rich_text("Open...").color(Color32::BLACK)))
+                .on_hover_text("Enter the credentials of the note, then choose the file")
+                .on_disabled_hover_text("Close this note first");
+
+            if open.clicked() && self.guard(GuardedAction::Open) {
+                self.run(GuardedAction::Open);
+            }
+
+            let can_save = self.path.is_some() && (self.modified || self.update_params);
+            if ui.add_enabled(can_save, button(rich_text("Save").color(Color32::BLACK))).clicked() {
+                self.save(self.path.clone(), argon_params, policy);
+            }
+
+            if ui.add(button(rich_text("Save As...").color(Color32::BLACK))).clicked() {
+                self.save(None, argon_params, policy);
+            }
+
+            let any = self.path.is_some() || !self.text.is_empty();
+            if ui.add_enabled(any, button(rich_text("Close").color(Color32::BLACK))).clicked() && self.guard(GuardedAction::Close) {
+                self.run(GuardedAction::Close);
+            }
+        });
+
+        let name = self.path.as_deref().map(file_name).unwrap_or_else(|| "New note".to_string());
+        let marker = if self.modified { " (modified)" } else { "" };
+        ui.label(rich_text(format!("{}{}", name, marker)).size(15.0));
+    }
+
+    fn credentials_input(&mut self, ui: &mut Ui) {
+        ui.label(rich_text("Username:"));
+        ui.add(secret_edit(&mut SecretText(self.credentials.user_mut())));
+
+        ui.label(rich_text("Password:"));
+        if let Err(e) = password_edit(ui, self.credentials.passwd_mut(), &self.clipboard) {
+            self.pop_msg.write().unwrap().show("Failed to paste", e);
+        }
+
+        ui.label(rich_text("Confirm Password (new notes only):"));
+        ui.add(secret_edit(&mut SecretText(self.credentials.confirm_passwd_mut())).password(true));
+    }
+
+    fn params_input(&mut self, ui: &mut Ui, argon_params: &Argon2Params) {
+        let Some(file_params) = &self.file_params else {
+            return;
+        };
+
+        ui.label(rich_text("Saved with the credentials it was opened with").color(Color32::GRAY));
+
+        if file_params != argon_params {
+            let preset = Argon2Preset::from_params(argon_params);
+            ui.checkbox(&mut self.update_params, rich_text(format!("Save with the parameters of the Encryption tab ({})", preset)))
+                .on_hover_text("The note keeps the Argon2 parameters it was encrypted with otherwise");
+        }
+    }
+
+    /// Offer to restore the changes stashed by a wipe, or tell they were lost
+    fn stash_banner(&mut self, ui: &mut Ui, busy: bool) {
+        let Some(stash) = &self.stash else {
+            return;
+        };
+
+        let name = stash.path.as_deref().map(file_name).unwrap_or_else(|| "the new note".to_string());
+
+        let message = if stash.encrypted.is_some() {
+            format!("The unsaved changes of {} were kept encrypted when the secrets were wiped", name)
+        } else {
+            format!("The unsaved changes of {} were lost when the secrets were wiped, it had no valid credentials yet", name)
+        };
+
+        ui.add(Label::new(rich_text(message).color(Color32::YELLOW)).wrap());
+
+        let restorable = stash.encrypted.is_some();
+        let no_note = self.path.is_none() && self.note_credentials.is_none() && self.text.is_empty();
+
+        ui.horizontal(|ui| {
+            ui.add_space(90.0);
+
+            if restorable {
+                let restore = ui.add_enabled(!busy && no_note, button(rich_text("Restore").color(Color32::BLACK)))
+                    .on_hover_text("Enter the credentials of the note first")
+                    .on_disabled_hover_text("Close this note first");
+
+                if restore.clicked() {
+                    self.restore();
+                }
+            }
+
+            let dismiss = if restorable { "Discard" } else { "Dismiss" };
+            if ui.add(button(rich_text(dismiss).color(Color32::BLACK))).clicked() {
+                self.stash = None;
+            }
+        });
+    }
+
+    /// Decrypt the stashed changes with the typed credentials
+    fn restore(&mut self) {
+        if let Err(e) = self.credentials.is_valid() {
+            self.pop_msg.write().unwrap().show("Invalid credentials", e);
+            return;
+        }
+
+        let Some(Stash { path, file_params, saved, encrypted: Some(encrypted) }) = &self.stash else {
+            return;
+        };
+
+        let (path, file_params, saved, encrypted) = (path.clone(), file_params.clone(), *saved, encrypted.clone());
+        let name = path.as_deref().map(file_name).unwrap_or_else(|| "the new note".to_string());
+
+        let mut credentials = std::mem::take(&mut self.credentials);
+        credentials.copy_passwd_to_confirm();
+        let events = self.events.clone();
+
+        let id = self.jobs.submit(format!("Restoring the changes of {}", name), move |ctx| {
+            let decrypted = decrypt_data_with(encrypted, credentials.duplicate(), &mut ctx.progress(), ctx.cancel_token());
+
+            let decrypted_data = match decrypted {
+                Ok(decrypted_data) => decrypted_data,
+                Err(e) => {
+                    if !ctx.cancel_token().is_cancelled() {
+                        *events.write().unwrap() = Some(NoteEvent::Failed { credentials });
+                    }
+                    return Err(e.context("Failed to decrypt the changes, use the credentials of the note"));
+                }
+            };
+
+            let text = SecretString::from(std::str::from_utf8(decrypted_data.expose())?);
+            ctx.cancel_token().check()?;
+            *events.write().unwrap() = Some(NoteEvent::Restored { path, text, file_params, saved, credentials });
+
+            Ok("Changes restored, save the note to keep them".to_string())
+        });
+
+        self.job = Some(id);
+    }
+
+    fn editor(&mut self, ui: &mut Ui) {
+        ScrollArea::vertical().id_salt("note_editor").max_height(300.0).show(ui, |ui| {
+            let interactive = self.guard.is_none();
+            let mut text = SecretText(&mut self.text);
+
+            let editor = TextEdit::multiline(&mut text)
+                .id(Id::new("note_editor"))
+                .desired_width(420.0)
+                .desired_rows(14)
+                .font(TextStyle::Monospace)
+                .text_color(Color32::WHITE)
+                .interactive(interactive);
+
+            if ui.add(editor).changed() {
+                self.modified = hashing::fingerprint(self.text.expose().as_bytes()) != self.saved;
+            }
+        });
+    }
+
+    /// Ask to save or discard the changes before going on with the guarded action
+    fn guard_window(&mut self, ui: &mut Ui, busy: bool, argon_params: &Argon2Params, policy: &PasswordPolicy) {
+        let Some(action) = self.guard else {
+            return;
+        };
+
+        Window::new(rich_text("Unsaved Changes").size(16.0))
+            .anchor(Align2::CENTER_CENTER, [0.0, 0.0])
+            .collapsible(false)
+            .resizable(false)
+            .frame(Frame::window(&ui.style().clone()).fill(ui.visuals().window_fill))
+            .show(ui.ctx(), |ui| {
+                ui.set_min_size(vec2(300.0, 100.0));
+
+                ui.vertical_centered(|ui| {
+                    ui.spacing_mut().item_spacing.y = 15.0;
+
+                    let name = self.path.as_deref().map(file_name).unwrap_or_else(|| "The new note".to_string());
+                    ui.add(Label::new(rich_text(format!("{} has changes that are not saved", name)).size(14.0)).wrap());
+
+                    ui.horizontal(|ui| {
+                        ui.add_enabled_ui(!busy, |ui| {
+                            let save = if busy { "Saving..." } else { "Save" };
+                            if ui.add(button(rich_text(save).color(Color32::BLACK))).clicked() {
+                                self.save(self.path.clone(), argon_params, policy);
+                            }
+
+                            if ui.add(button(rich_text("Discard").color(Color32::BLACK))).clicked() {
+                                self.guard = None;
+                                self.modified = false;
+                                self.run(action);
+                            }
+
+                            if ui.add(button(rich_text("Cancel").color(Color32::BLACK))).clicked() {
+                                self.guard = None;
+                            }
+                        });
+                    });
+                });
+            });
+    }
+
+    /// Go on with an action once the changes are saved or discarded
+    fn run(&mut self, action: GuardedAction) {
+        match action {
+            GuardedAction::New | GuardedAction::Close => self.close_note(),
+            GuardedAction::Open => self.open_file(),
+            GuardedAction::Exit => self.exit = true,
+        }
+    }
+
+    /// Forget the note and the credentials it is encrypted with
+    fn close_note(&mut self) {
+        self.text.clear();
+        self.path = None;
+        self.note_credentials = None;
+        self.file_params = None;
+        self.update_params = false;
+        self.saved = hashing::fingerprint(b"");
+        self.modified = false;
+        self.guard = None;
+        self.forget = true;
+    }
+
+    fn open_file(&mut self) {
+        if let Err(e) = self.credentials.is_valid() {
+            self.pop_msg.write().unwrap().show("Invalid credentials", e);
+            return;
+        }
+
+        let Some(path) = rfd::FileDialog::new().add_filter("Encrypted notes", &[file::FILE_EXTENSION]).pick_file() else {
+            return;
+        };
+
+        self.close_note();
+
+        let credentials = std::mem::take(&mut self.credentials);
+        let events = self.events.clone();
+
+        let id = self.jobs.submit(format!("Opening {}", file_name(&path)), move |ctx| {
+            let info = file::read_info(&path)?;
+            let data = std::fs::read(&path).map_err(|e| anyhow::Error::from(e).context("Failed to read file"))?;
+
+            let decrypted_data = decrypt_data_with(data, credentials.duplicate(), &mut ctx.progress(), ctx.cancel_token())
+                .map_err(|e| e.context("Failed to decrypt the note"))?;
+
+            let text = std::str::from_utf8(decrypted_data.expose())
+                .map_err(|_| anyhow::anyhow!("The file is not a text note, preview or decrypt it from the Encryption tab"))?;
+
+            let message = format!("{} opened", file_name(&path));
+            let text = SecretString::from(text);
+            ctx.cancel_token().check()?;
+            *events.write().unwrap() = Some(NoteEvent::Opened { path, text, argon_params: info.argon2_params, credentials });
+
+            Ok(message)
+        });
+
+        self.job = Some(id);
+    }
+
+    /// Encrypt the note to `path`, asks where to save it if `None`
+    ///
+    /// A note that was opened or saved before keeps its credentials, a new one takes the
+    /// typed credentials and must meet `policy`
+    fn save(&mut self, path: Option<PathBuf>, argon_params: &Argon2Params, policy: &PasswordPolicy) {
+        let (credentials, policy) = match &self.note_credentials {
+            Some(credentials) => {
+                let mut credentials = credentials.duplicate();
+                credentials.copy_passwd_to_confirm();
+                (credentials, PasswordPolicy::none())
+            }
+            None => {
+                if let Err(e) = self.credentials.is_confirmed().and_then(|_| policy.check(&self.credentials)) {
+                    self.pop_msg.write().unwrap().show("Invalid credentials", e);
+                    return;
+                }
+
+                (std::mem::take(&mut self.credentials), policy.clone())
+            }
+        };
+
+        let path = match path {
+            Some(path) => path,
+            None => {
+                let dialog = rfd::FileDialog::new()
+                    .add_filter("Encrypted notes", &[file::FILE_EXTENSION])
+                    .set_file_name(format!("note.{}", file::FILE_EXTENSION));
+
+                let Some(path) = dialog.save_file() else {
+                    // Give back the typed credentials of a new note
+                    if self.note_credentials.is_none() {
+                        self.credentials = credentials;
+                    }
+                    return;
+                };
+
+                path
+            }
+        };
+
+        let argon_params = match (&self.file_params, self.update_params) {
+            (Some(file_params), false) => file_params.clone(),
+            _ => argon_params.clone(),
+        };
+
+        let hash = hashing::fingerprint(self.text.expose().as_bytes());
+
+        // The typed credentials of a new note are given back if saving fails
+        let give_back = self.note_credentials.is_none();
+
+        // Encrypted in place or zeroized if the credentials are refused
+        let data = self.text.expose().as_bytes().to_vec();
+        let events = self.events.clone();
+
+        let id = self.jobs.submit(format!("Saving {}", file_name(&path)), move |ctx| {
+            let cancel = ctx.cancel_token();
+            let note_credentials = credentials.duplicate();
+
+            let saved = encrypt_data_with(argon_params.clone(), data, credentials, &policy, &mut ctx.progress(), cancel)
+                .map_err(|e| e.context("Failed to encrypt the note"))
+                .and_then(|encrypted_data| {
+                    file::write_atomic(&path, &encrypted_data, true, cancel).map_err(|e| e.context("Failed to save the note"))
+                });
+
+            if let Err(e) = saved {
+                if give_back && !cancel.is_cancelled() {
+                    *events.write().unwrap() = Some(NoteEvent::Failed { credentials: note_credentials });
+                }
+                return Err(e);
+            }
+
+            cancel.check()?;
+            let message = format!("Note saved to: {}", path.display());
+            *events.write().unwrap() = Some(NoteEvent::Saved { path, hash, argon_params, credentials: note_credentials });
+
+            Ok(message)
+        });
+
+        self.job = Some(id);
+    }
+}
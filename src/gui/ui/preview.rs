@@ -0,0 +1,163 @@
+use eframe::egui::{ vec2, Align2, Color32, ColorImage, Frame, Label, ScrollArea, TextEdit, TextStyle, TextureHandle, TextureOptions, Ui, Window };
+use std::sync::{ Arc, RwLock };
+use crate::preview::{ hex_row, Preview, PreviewContent };
+use super::*;
+use super::file_encryption::format_bytes;
+
+/// Longest text shown, the hex view still shows everything
+const MAX_TEXT_LEN: usize = 1_000_000;
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum View {
+    Content,
+    Hex,
+}
+
+/// Window that shows a file decrypted in memory, nothing is written to disk
+///
+/// The decrypted data is wiped when the window closes
+pub struct PreviewUi {
+    /// Set by the preview job, moved to `current` on the next frame
+    pub pending: Arc<RwLock<Option<Preview>>>,
+
+    current: Option<Preview>,
+
+    /// The image uploaded to the GPU, freed with the preview
+    texture: Option<TextureHandle>,
+
+    view: View,
+}
+
+impl Default for PreviewUi {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl PreviewUi {
+    pub fn new() -> Self {
+        Self {
+            pending: Arc::new(RwLock::new(None)),
+            current: None,
+            texture: None,
+            view: View::Content,
+        }
+    }
+
+    pub fn is_open(&self) -> bool {
+        self.current.is_some() || self.pending.read().unwrap().is_some()
+    }
+
+    /// Close the window and wipe the decrypted data
+    pub fn close(&mut self) {
+        self.current = None;
+        self.texture = None;
+        self.pending.write().unwrap().take();
+    }
+
+    pub fn show(&mut self, ui: &mut Ui) {
+        if let Some(preview) = self.pending.write().unwrap().take() {
+            self.current = Some(preview);
+            self.texture = None;
+            self.view = View::Content;
+        }
+
+        let Some(preview) = &self.current else {
+            return;
+        };
+
+        let mut open = true;
+
+        Window::new(rich_text(format!("Preview: {}", preview.name)).size(16.0))
+            .open(&mut open)
+            .anchor(Align2::CENTER_CENTER, [0.0, 0.0])
+            .collapsible(false)
+            .resizable(true)
+            .default_size(vec2(560.0, 420.0))
+            .frame(Frame::window(&ui.style().clone()).fill(ui.visuals().window_fill))
+            .show(ui.ctx(), |ui| {
+                ui.horizontal(|ui| {
+                    ui.selectable_value(&mut self.view, View::Content, rich_text("Content"));
+                    ui.selectable_value(&mut self.view, View::Hex, rich_text("Hex"));
+
+                    ui.add_space(10.0);
+                    ui.label(rich_text(describe(preview)).color(Color32::GRAY));
+                });
+
+                ui.separator();
+
+                match self.view {
+                    View::Content => content(ui, preview, &mut self.texture),
+                    View::Hex => hex(ui, preview),
+                }
+            });
+
+        if !open {
+            self.close();
+        }
+    }
+}
+
+/// Size and detected type, like `12.40 KB, text (UTF-8)`
+fn describe(preview: &Preview) -> String {
+    let kind = match &preview.content {
+        PreviewContent::Text { encoding, .. } => format!("text ({})", encoding),
+        PreviewContent::Image { width, height, .. } => format!("image {}x{}", width, height),
+        PreviewContent::Binary => "binary".to_string(),
+    };
+
+    format!("{}, {}", format_bytes(preview.data.len() as u64), kind)
+}
+
+fn content(ui: &mut Ui, preview: &Preview, texture: &mut Option<TextureHandle>) {
+    match &preview.content {
+        PreviewContent::Text { text, .. } => {
+            let text = text.expose();
+            let shown = match text.char_indices().nth(MAX_TEXT_LEN) {
+                Some((end, _)) => &text[..end],
+                None => text,
+            };
+
+            if shown.len() < text.len() {
+                ui.label(rich_text("Only the beginning of the text is shown, the hex view has everything").color(Color32::GRAY));
+            }
+
+            ScrollArea::both().id_salt("preview_text").show(ui, |ui| {
+                ui.add(
+                    TextEdit::multiline(&mut { shown })
+                        .font(TextStyle::Monospace)
+                        .desired_width(f32::INFINITY)
+                        .text_color(Color32::WHITE)
+                );
+            });
+        }
+        PreviewContent::Image { width, height, pixels } => {
+            let texture = texture.get_or_insert_with(|| {
+                let size = [*width as usize, *height as usize];
+                let image = ColorImage::from_rgba_unmultiplied(size, pixels.expose());
+                ui.ctx().load_texture("preview", image, TextureOptions::LINEAR)
+            });
+
+            ScrollArea::both().id_salt("preview_image").show(ui, |ui| {
+                let size = texture.size_vec2();
+                let scale = (ui.available_width() / size.x).min(1.0);
+                ui.image((texture.id(), size * scale));
+            });
+        }
+        PreviewContent::Binary => {
+            ui.label(rich_text("Not text or a supported image, see the hex view"));
+        }
+    }
+}
+
+fn hex(ui: &mut Ui, preview: &Preview) {
+    let row_height = ui.text_style_height(&TextStyle::Monospace);
+
+    // Only the visible rows are formatted
+    ScrollArea::both().id_salt("preview_hex").show_rows(ui, row_height, preview.hex_rows(), |ui, rows| {
+        for row in rows {
+            let line = hex_row(preview.data.expose(), row);
+            ui.add(Label::new(rich_text(line).monospace()).extend());
+        }
+    });
+}
@@ -0,0 +1,151 @@
+use eframe::egui::{ Color32, ComboBox, Label, ScrollArea, Slider, Ui };
+use crate::gui::lock::PANIC_WIPE;
+use crate::settings::{ Settings, Theme };
+use super::*;
+use super::file_encryption::{ FileEncryptionUi, Overwrite };
+
+/// Settings tab, everything here is saved when the app closes
+pub struct SettingsUi {
+    pub open: bool,
+}
+
+impl Default for SettingsUi {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl SettingsUi {
+    pub fn new() -> Self {
+        Self { open: false }
+    }
+
+    /// The settings that belong to the encryption tab are edited in place
+    pub fn show(&mut self, ui: &mut Ui, settings: &mut Settings, encryption: &mut FileEncryptionUi) {
+        if !self.open {
+            return;
+        }
+
+        ScrollArea::vertical().id_salt("settings").max_height(420.0).show(ui, |ui| {
+            ui.spacing_mut().item_spacing.y = 10.0;
+
+            heading(ui, "Encryption");
+            self.encryption(ui, encryption);
+
+            heading(ui, "Appearance");
+            self.appearance(ui, settings);
+
+            heading(ui, "Auto-Clear");
+            self.timeouts(ui, settings);
+
+            heading(ui, "Recent Files");
+            self.recent_files(ui, encryption);
+
+            ui.add_space(15.0);
+
+            if ui.add(button(rich_text("Reset to Defaults").color(Color32::BLACK))).clicked() {
+                *settings = Settings::default();
+                encryption.load_settings(settings);
+            }
+        });
+    }
+
+    fn encryption(&mut self, ui: &mut Ui, encryption: &mut FileEncryptionUi) {
+        ui.label(rich_text(format!(
+            "Argon2 preset: {}, set in the panel on the right and used on the next start",
+            encryption.preset
+        )));
+
+        ui.horizontal(|ui| {
+            let output = match &encryption.output_dir {
+                Some(dir) => dir.display().to_string(),
+                None => "Same folder as the file".to_string(),
+            };
+            ui.add(Label::new(rich_text(format!("Output: {}", output))).truncate());
+
+            if ui.add(button(rich_text("Change").color(Color32::BLACK))).clicked() {
+                if let Some(path) = rfd::FileDialog::new().pick_folder() {
+                    encryption.output_dir = Some(path);
+                }
+            }
+
+            if ui.add_enabled(encryption.output_dir.is_some(), button(rich_text("Reset").color(Color32::BLACK))).clicked() {
+                encryption.output_dir = None;
+            }
+        });
+
+        ui.horizontal(|ui| {
+            ui.label(rich_text("If the output exists:"));
+
+            ComboBox::from_id_salt("settings_overwrite")
+                .selected_text(encryption.queue.overwrite.to_string())
+                .show_ui(ui, |ui| {
+                    for option in [Overwrite::Replace, Overwrite::Rename, Overwrite::Skip] {
+                        ui.selectable_value(&mut encryption.queue.overwrite, option, option.to_string());
+                    }
+                });
+        });
+    }
+
+    fn appearance(&mut self, ui: &mut Ui, settings: &mut Settings) {
+        ui.horizontal(|ui| {
+            ui.label(rich_text("Theme:"));
+
+            ComboBox::from_id_salt("settings_theme")
+                .selected_text(settings.theme.to_string())
+                .show_ui(ui, |ui| {
+                    for theme in Theme::to_vec() {
+                        ui.selectable_value(&mut settings.theme, theme, theme.to_string());
+                    }
+                });
+        });
+
+        ui.checkbox(&mut settings.remember_window, rich_text("Remember the position and size of the window"));
+    }
+
+    fn timeouts(&mut self, ui: &mut Ui, settings: &mut Settings) {
+        ui.horizontal(|ui| {
+            ui.label(rich_text("Clear the clipboard after (s):"));
+            ui.add(Slider::new(&mut settings.clipboard_timeout, 0..=300))
+                .on_hover_text("0 keeps copied secrets in the clipboard");
+        });
+
+        ui.horizontal(|ui| {
+            ui.label(rich_text("Lock after inactivity (min):"));
+            ui.add(Slider::new(&mut settings.lock_timeout, 0..=120))
+                .on_hover_text("0 never locks");
+        });
+
+        ui.checkbox(&mut settings.wipe_on_minimize, rich_text("Wipe the secrets when the window is minimized or loses focus"));
+
+        let shortcut = ui.ctx().format_shortcut(&PANIC_WIPE);
+        ui.label(rich_text(format!("Panic wipe: {}, also cancels every job and shreds the files open in other apps", shortcut)));
+    }
+
+    fn recent_files(&mut self, ui: &mut Ui, encryption: &mut FileEncryptionUi) {
+        let mut enabled = encryption.recent.is_enabled();
+
+        let text = rich_text("Remember the files I open, only their paths are saved");
+        if ui.checkbox(&mut enabled, text).changed() {
+            encryption.recent.set_enabled(enabled);
+        }
+
+        if !enabled {
+            return;
+        }
+
+        for path in encryption.recent.paths() {
+            ui.add(Label::new(rich_text(path.display().to_string()).size(12.0)).truncate());
+        }
+
+        let any = !encryption.recent.paths().is_empty();
+        if ui.add_enabled(any, button(rich_text("Clear Recent Files").color(Color32::BLACK))).clicked() {
+            encryption.recent.clear();
+        }
+    }
+}
+
+fn heading(ui: &mut Ui, text: &str) {
+    ui.add_space(5.0);
+    ui.label(rich_text(text).size(15.0));
+}
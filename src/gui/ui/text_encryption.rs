@@ -0,0 +1,270 @@
+use eframe::egui::{ Color32, ScrollArea, TextEdit, Ui };
+use std::sync::{ Arc, RwLock };
+use encryption::{ armor, prelude::*, strength::PasswordPolicy };
+use super::*;
+use super::file_encryption::Operation;
+use crate::gui::clipboard::Clipboard;
+use crate::jobs::JobSender;
+
+/// Encrypt a snippet of text to an armored message that can be pasted in a chat or an email
+pub struct TextEncryptionUi {
+    pub open: bool,
+
+    /// Which form is shown, encrypting asks to confirm the password, decrypting does not
+    pub mode: Operation,
+
+    pub credentials: Credentials,
+
+    /// Minimum password strength required to encrypt
+    pub policy: PasswordPolicy,
+
+    /// The secret text, the input when encrypting and the output when decrypting
+    pub plaintext: Arc<RwLock<SecretString>>,
+
+    /// The armored message, the output when encrypting and the input when decrypting
+    pub armored: Arc<RwLock<String>>,
+
+    pub pop_msg: Arc<RwLock<WindowMsg>>,
+
+    pub clipboard: Clipboard,
+
+    pub jobs: JobSender,
+}
+
+impl TextEncryptionUi {
+    pub fn new(pop_msg: Arc<RwLock<WindowMsg>>, jobs: JobSender, clipboard: Clipboard) -> Self {
+        Self {
+            open: false,
+            mode: Operation::Encrypt,
+            credentials: Credentials::default(),
+            policy: PasswordPolicy::default(),
+            plaintext: Arc::new(RwLock::new(SecretString::new())),
+            armored: Arc::new(RwLock::new(String::new())),
+            pop_msg,
+            clipboard,
+            jobs,
+        }
+    }
+
+    /// Wipe the text and the credentials
+    pub fn clear(&mut self) {
+        self.credentials.destroy();
+        self.plaintext.write().unwrap().clear();
+        self.armored.write().unwrap().clear();
+    }
+
+    /// Whether credentials or the secret text are in memory
+    pub fn has_secrets(&self) -> bool {
+        !self.credentials.is_empty() || !self.plaintext.read().unwrap().is_empty()
+    }
+
+    pub fn show(&mut self, ui: &mut Ui, argon_params: &Argon2Params) {
+        if !self.open {
+            return;
+        }
+
+        ui.spacing_mut().item_spacing.y = 10.0;
+
+        self.mode_selector(ui);
+
+        match self.mode {
+            Operation::Encrypt => {
+                self.plaintext_input(ui);
+                self.credentials_input(ui);
+
+                if ui.add(button(rich_text("Encrypt").color(Color32::BLACK))).clicked() {
+                    self.encrypt(argon_params.clone());
+                }
+
+                self.armored_output(ui);
+            }
+            Operation::Decrypt => {
+                self.armored_input(ui);
+                self.credentials_input(ui);
+
+                if ui.add(button(rich_text("Decrypt").color(Color32::BLACK))).clicked() {
+                    self.decrypt();
+                }
+
+                self.plaintext_output(ui);
+            }
+        }
+    }
+
+    fn mode_selector(&mut self, ui: &mut Ui) {
+        ui.horizontal(|ui| {
+            ui.add_space(150.0);
+
+            if ui.selectable_label(self.mode == Operation::Encrypt, rich_text("Encrypt Text")).clicked() {
+                self.set_mode(Operation::Encrypt);
+            }
+
+            ui.add_space(15.0);
+
+            if ui.selectable_label(self.mode == Operation::Decrypt, rich_text("Decrypt Text")).clicked() {
+                self.set_mode(Operation::Decrypt);
+            }
+        });
+    }
+
+    fn set_mode(&mut self, mode: Operation) {
+        if mode != self.mode {
+            // Don't leave the plaintext of one form behind in the other
+            self.plaintext.write().unwrap().clear();
+            self.credentials.confirm_passwd_mut().clear();
+        }
+
+        self.mode = mode;
+    }
+
+    fn plaintext_input(&mut self, ui: &mut Ui) {
+        ui.label(rich_text("Text to encrypt:"));
+
+        let mut plaintext = self.plaintext.write().unwrap();
+        ScrollArea::vertical().id_salt("plaintext_input").max_height(120.0).show(ui, |ui| {
+            ui.add(secret_text_area(&mut SecretText(&mut plaintext)));
+        });
+    }
+
+    fn plaintext_output(&mut self, ui: &mut Ui) {
+        let mut plaintext = self.plaintext.write().unwrap();
+
+        if plaintext.is_empty() {
+            return;
+        }
+
+        ui.label(rich_text("Decrypted text:"));
+
+        ScrollArea::vertical().id_salt("plaintext_output").max_height(120.0).show(ui, |ui| {
+            ui.add(secret_text_area(&mut SecretText(&mut plaintext)));
+        });
+
+        ui.horizontal(|ui| {
+            ui.add_space(160.0);
+
+            let copy = ui.add(button(rich_text("Copy").color(Color32::BLACK)))
+                .on_hover_text("The clipboard is cleared after the timeout set in the settings");
+
+            if copy.clicked() {
+                if let Err(e) = self.clipboard.copy_secret(plaintext.expose()) {
+                    self.pop_msg.write().unwrap().show("Failed to copy", e);
+                }
+            }
+
+            if ui.add(button(rich_text("Clear").color(Color32::BLACK))).clicked() {
+                plaintext.clear();
+            }
+        });
+    }
+
+    fn armored_input(&mut self, ui: &mut Ui) {
+        ui.horizontal(|ui| {
+            ui.label(rich_text("Message to decrypt:"));
+
+            if ui.add(button(rich_text("Paste").color(Color32::BLACK))).clicked() {
+                match self.clipboard.paste() {
+                    Ok(text) => *self.armored.write().unwrap() = text,
+                    Err(e) => self.pop_msg.write().unwrap().show("Failed to paste", e),
+                }
+            }
+        });
+
+        let mut armored = self.armored.write().unwrap();
+        ScrollArea::vertical().id_salt("armored_input").max_height(160.0).show(ui, |ui| {
+            ui.add(armored_text_area(&mut armored));
+        });
+    }
+
+    fn armored_output(&mut self, ui: &mut Ui) {
+        let mut armored = self.armored.write().unwrap();
+
+        if armored.is_empty() {
+            return;
+        }
+
+        ui.label(rich_text("Encrypted message:"));
+
+        ScrollArea::vertical().id_salt("armored_output").max_height(160.0).show(ui, |ui| {
+            ui.add(armored_text_area(&mut armored).interactive(false));
+        });
+
+        if ui.add(button(rich_text("Copy").color(Color32::BLACK))).clicked() {
+            if let Err(e) = self.clipboard.copy(&armored) {
+                self.pop_msg.write().unwrap().show("Failed to copy", e);
+            }
+        }
+    }
+
+    fn credentials_input(&mut self, ui: &mut Ui) {
+        ui.label(rich_text("Username:"));
+        ui.add(secret_edit(&mut SecretText(self.credentials.user_mut())));
+
+        ui.label(rich_text("Password:"));
+        if let Err(e) = password_edit(ui, self.credentials.passwd_mut(), &self.clipboard) {
+            self.pop_msg.write().unwrap().show("Failed to paste", e);
+        }
+
+        if self.mode == Operation::Encrypt {
+            ui.label(rich_text("Confirm Password:"));
+            ui.add(secret_edit(&mut SecretText(self.credentials.confirm_passwd_mut())).password(true));
+        }
+    }
+
+    fn encrypt(&mut self, argon_params: Argon2Params) {
+        let credentials = std::mem::take(&mut self.credentials);
+        let policy = self.policy.clone();
+        let plaintext = self.plaintext.clone();
+        let armored = self.armored.clone();
+
+        self.jobs.submit("Encrypting text", move |ctx| {
+            // Encrypted in place or zeroized if the credentials are refused
+            let data = plaintext.read().unwrap().expose().as_bytes().to_vec();
+
+            let encrypted_data = encrypt_data_with(argon_params, data, credentials, &policy, &mut ctx.progress(), ctx.cancel_token())
+                .map_err(|e| e.context("Failed to encrypt text"))?;
+
+            ctx.cancel_token().check()?;
+            *armored.write().unwrap() = armor::armor(&encrypted_data);
+            plaintext.write().unwrap().clear();
+
+            Ok("The encrypted message is ready to copy".to_string())
+        });
+    }
+
+    fn decrypt(&mut self) {
+        let credentials = std::mem::take(&mut self.credentials);
+        let plaintext = self.plaintext.clone();
+        let armored = self.armored.read().unwrap().clone();
+
+        self.jobs.submit("Decrypting text", move |ctx| {
+            let data = armor::dearmor(&armored)?;
+            let decrypted = decrypt_data_with(data, credentials, &mut ctx.progress(), ctx.cancel_token())
+                .map_err(|e| e.context("Failed to decrypt text"))?;
+
+            let text = std::str::from_utf8(decrypted.expose())
+                .map_err(|_| anyhow::anyhow!("The message is not text, save it to a file and decrypt it as a file"))?;
+
+            ctx.cancel_token().check()?;
+            let mut plaintext = plaintext.write().unwrap();
+            plaintext.clear();
+            plaintext.push_str(text);
+
+            Ok("The message was decrypted".to_string())
+        });
+    }
+}
+
+fn secret_text_area<'a>(text: &'a mut SecretText<'_>) -> TextEdit<'a> {
+    TextEdit::multiline(text)
+        .desired_width(400.0)
+        .desired_rows(4)
+        .text_color(Color32::WHITE)
+}
+
+fn armored_text_area(text: &mut String) -> TextEdit<'_> {
+    TextEdit::multiline(text)
+        .desired_width(400.0)
+        .desired_rows(6)
+        .code_editor()
+        .text_color(Color32::WHITE)
+}
@@ -1,35 +1,19 @@
-use eframe::egui::{ Ui, ComboBox, TextEdit, Color32, FontSelection, FontId };
-use super::rich_text;
-use sha3::{ Digest, Sha3_224, Sha3_256, Sha3_384, Sha3_512 };
-
-#[derive(Clone, PartialEq, Eq)]
-pub enum HashAlgorithm {
-    Sha3_224,
-    Sha3_256,
-    Sha3_384,
-    Sha3_512,
-}
+use eframe::egui::{ Ui, ComboBox, DragValue, TextEdit, Color32, FontSelection, FontId, ScrollArea };
+use super::*;
+use crate::gui::clipboard::Clipboard;
+use crate::jobs::JobSender;
+use crate::hashing::{ self, CheckStatus, HashAlgorithm, InputEncoding, OutputEncoding };
+use encryption::{ file, prelude::{ SecretBytes, SecretString }, zeroize::Zeroize };
+use std::fmt::Display;
+use std::path::{ Path, PathBuf };
+use std::sync::{ Arc, RwLock };
 
-impl HashAlgorithm {
-    pub fn to_string(&self) -> String {
-        (
-            match self {
-                HashAlgorithm::Sha3_224 => "SHA3-224",
-                HashAlgorithm::Sha3_256 => "SHA3-256",
-                HashAlgorithm::Sha3_384 => "SHA3-384",
-                HashAlgorithm::Sha3_512 => "SHA3-512",
-            }
-        ).to_string()
-    }
-
-    pub fn to_vec(&self) -> Vec<HashAlgorithm> {
-        vec![
-            HashAlgorithm::Sha3_224,
-            HashAlgorithm::Sha3_256,
-            HashAlgorithm::Sha3_384,
-            HashAlgorithm::Sha3_512
-        ]
-    }
+/// The digest of the last hashed file
+#[derive(Default)]
+pub struct FileDigest {
+    pub path: PathBuf,
+    pub algorithm: Option<HashAlgorithm>,
+    pub digest: String,
 }
 
 pub struct TextHashingUi {
@@ -37,96 +21,446 @@ pub struct TextHashingUi {
     pub algorithm: HashAlgorithm,
     pub input_text: String,
     pub output_hash: String,
+
+    /// How `input_text` is read
+    pub input_encoding: InputEncoding,
+
+    /// How `output_hash` is written
+    pub output_encoding: OutputEncoding,
+
+    /// Hash with a key, HMAC, KMAC or keyed BLAKE3 depending on the algorithm
+    pub keyed: bool,
+
+    pub key: SecretString,
+
+    pub key_encoding: InputEncoding,
+
+    /// Customization string of KMAC
+    pub customization: String,
+
+    /// The digest or signature of `input_text` before encoding
+    pub output_digest: Vec<u8>,
+
+    /// Why `input_text` could not be hashed, like an invalid key
+    pub output_error: Option<String>,
+
+    /// Signature pasted by the user to compare `output_digest` against
+    pub expected_output: String,
+
+    /// Set when the input, the key or the algorithm changed, the hash is only recomputed then
+    pub hash_outdated: bool,
+
+    /// Set by the hashing thread
+    pub file_digest: Arc<RwLock<FileDigest>>,
+
+    /// Digest pasted by the user to compare the file digest against
+    pub expected_digest: String,
+
+    pub pop_msg: Arc<RwLock<WindowMsg>>,
+
+    pub clipboard: Clipboard,
+
+    pub jobs: JobSender,
 }
 
 impl TextHashingUi {
-    pub fn new() -> Self {
+    pub fn new(pop_msg: Arc<RwLock<WindowMsg>>, jobs: JobSender, clipboard: Clipboard) -> Self {
         Self {
             open: false,
             algorithm: HashAlgorithm::Sha3_224,
             input_text: String::new(),
             output_hash: String::new(),
+            input_encoding: InputEncoding::Utf8,
+            output_encoding: OutputEncoding::HexLower,
+            keyed: false,
+            key: SecretString::new(),
+            key_encoding: InputEncoding::Utf8,
+            customization: String::new(),
+            output_digest: Vec::new(),
+            output_error: None,
+            expected_output: String::new(),
+            hash_outdated: false,
+            file_digest: Arc::new(RwLock::new(FileDigest::default())),
+            expected_digest: String::new(),
+            pop_msg,
+            clipboard,
+            jobs,
         }
     }
 
+    /// Wipe the text, the key and the outputs
+    pub fn clear(&mut self) {
+        self.input_text.zeroize();
+        self.output_hash.zeroize();
+        self.output_digest.zeroize();
+        self.key.zeroize();
+        self.expected_output.zeroize();
+        self.output_error = None;
+    }
+
+    /// Whether text, a key or a signature are in memory
+    pub fn has_secrets(&self) -> bool {
+        !self.input_text.is_empty()
+            || !self.output_hash.is_empty()
+            || !self.key.is_empty()
+            || !self.expected_output.is_empty()
+    }
+
     pub fn show(&mut self, ui: &mut Ui) {
         if !self.open {
             return;
         }
 
-        let font = FontSelection::FontId(FontId::monospace(13.0));
-        let font_2 = FontSelection::FontId(FontId::monospace(13.0));
-
         ui.spacing_mut().item_spacing.y = 10.0;
 
         self.select_algorithm(ui);
 
-        ui.label(rich_text("Input Text").size(16.0));
+        ScrollArea::vertical().show(ui, |ui| {
+            self.text_hashing(ui);
+
+            ui.separator();
+
+            self.file_hashing(ui);
+        });
+    }
+
+    fn text_hashing(&mut self, ui: &mut Ui) {
+        let font = FontSelection::FontId(FontId::monospace(13.0));
+        let font_2 = FontSelection::FontId(FontId::monospace(13.0));
+
+        self.key_input(ui);
+
+        ui.horizontal(|ui| {
+            ui.label(rich_text("Input Text").size(16.0));
+            self.hash_outdated |= select_encoding(ui, "input_encoding", &mut self.input_encoding, InputEncoding::to_vec());
+        });
 
         let input_edit = TextEdit::multiline(&mut self.input_text)
             .desired_width(300.0)
-            .desired_rows(10)
+            .desired_rows(5)
             .text_color(Color32::WHITE)
             .font(font);
-        ui.add(input_edit);
+        self.hash_outdated |= ui.add(input_edit).changed();
+
+        let output_name = if self.keyed { self.algorithm.keyed_name() } else { "Hash".to_string() };
+
+        ui.horizontal(|ui| {
+            ui.label(rich_text(format!("{} Output", output_name)).size(16.0));
+            self.hash_outdated |= select_encoding(ui, "output_encoding", &mut self.output_encoding, OutputEncoding::to_vec());
 
-        ui.label(rich_text("Hash Output").size(16.0));
+            let copy = button(rich_text("Copy").color(Color32::BLACK));
+            if ui.add_enabled(!self.output_hash.is_empty(), copy).clicked() {
+                if let Err(e) = self.clipboard.copy(&self.output_hash) {
+                    self.pop_msg.write().unwrap().show("Failed to copy", format!("{:?}", e));
+                }
+            }
+        });
+
+        if self.hash_outdated {
+            self.hash_outdated = false;
+
+            if !self.input_text.is_empty() {
+                self.calculate_hash();
+            } else {
+                self.output_hash.zeroize();
+                self.output_digest.zeroize();
+                self.output_error = None;
+            }
+        }
 
-        let output_edit = TextEdit::multiline(&mut self.output_hash)
+        if let Some(error) = &self.output_error {
+            ui.label(rich_text(error).color(Color32::from_rgb(255, 200, 0)));
+        }
+
+        // Read only, it would be overwritten by the next change anyway
+        let mut output_hash = self.output_hash.as_str();
+        let output_edit = TextEdit::multiline(&mut output_hash)
             .desired_width(300.0)
-            .desired_rows(10)
+            .desired_rows(3)
             .text_color(Color32::WHITE)
             .font(font_2);
         ui.add(output_edit);
 
-        if !self.input_text.is_empty() {
-            self.calculate_hash();
-        } else {
-            self.output_hash.clear();
+        ui.label(rich_text("Expected:"));
+        let hint = "Paste a digest or a signature like sha256=...";
+        ui.add(text_edit(&mut self.expected_output).desired_width(300.0).hint_text(hint));
+
+        if !self.expected_output.trim().is_empty() && !self.output_digest.is_empty() {
+            match_label(ui, hashing::signature_matches(&self.output_digest, &self.expected_output));
+        }
+    }
+
+    fn key_input(&mut self, ui: &mut Ui) {
+        self.hash_outdated |= ui.checkbox(&mut self.keyed, rich_text("Keyed (HMAC, KMAC, BLAKE3)")).changed();
+
+        if !self.keyed {
+            return;
+        }
+
+        ui.horizontal(|ui| {
+            ui.label(rich_text("Key:"));
+            self.hash_outdated |= ui.add(secret_edit(&mut SecretText(&mut self.key)).password(true)).changed();
+            self.hash_outdated |= select_encoding(ui, "key_encoding", &mut self.key_encoding, InputEncoding::to_vec());
+        });
+
+        if self.algorithm.is_kmac() {
+            ui.horizontal(|ui| {
+                ui.label(rich_text("Customization:"));
+                let customization = text_edit(&mut self.customization).desired_width(200.0).hint_text("Optional");
+                self.hash_outdated |= ui.add(customization).changed();
+            });
         }
     }
 
     pub fn calculate_hash(&mut self) {
-        if self.algorithm == HashAlgorithm::Sha3_224 {
-            let mut hasher = Sha3_224::new();
-            hasher.update(self.input_text.as_bytes());
-            let result = hasher.finalize();
-            self.output_hash = format!("{:x}", result);
-        } else if self.algorithm == HashAlgorithm::Sha3_256 {
-            let mut hasher = Sha3_256::new();
-            hasher.update(self.input_text.as_bytes());
-            let result = hasher.finalize();
-            self.output_hash = format!("{:x}", result);
-        } else if self.algorithm == HashAlgorithm::Sha3_384 {
-            let mut hasher = Sha3_384::new();
-            hasher.update(self.input_text.as_bytes());
-            let result = hasher.finalize();
-            self.output_hash = format!("{:x}", result);
-        } else {
-            let mut hasher = Sha3_512::new();
-            hasher.update(self.input_text.as_bytes());
-            let result = hasher.finalize();
-            self.output_hash = format!("{:x}", result);
+        let result = self.input_encoding.decode(&self.input_text).and_then(|data| {
+            let data = SecretBytes::from(data);
+
+            if !self.keyed {
+                return Ok(hashing::hash(self.algorithm, data.expose()));
+            }
+
+            if self.key.is_empty() {
+                return Err(anyhow::anyhow!("Enter a key"));
+            }
+
+            let key = SecretBytes::from(self.key_encoding.decode(self.key.expose())?);
+            hashing::keyed_hash(self.algorithm, key.expose(), self.customization.as_bytes(), data.expose())
+        });
+
+        // The previous MAC may be keyed, zeroize it before it is replaced
+        self.output_hash.zeroize();
+        self.output_digest.zeroize();
+
+        match result {
+            Ok(digest) => {
+                self.output_hash = self.output_encoding.encode(&digest);
+                self.output_digest = digest;
+                self.output_error = None;
+            }
+            Err(e) => {
+                self.output_error = Some(format!("{}", e));
+            }
         }
     }
 
     fn select_algorithm(&mut self, ui: &mut Ui) {
-        ComboBox::from_label("")
-            .selected_text(self.algorithm.to_string())
-            .show_ui(ui, |ui| {
-                let mut algorithms = self.algorithm.to_vec();
-
-                for selected_algorithm in algorithms.iter_mut() {
-                    let value = ui.selectable_value(
-                        &mut self.algorithm,
-                        selected_algorithm.clone(),
-                        selected_algorithm.to_string()
-                    );
-
-                    if value.clicked() {
-                        self.algorithm = selected_algorithm.clone();
+        let previous = self.algorithm;
+
+        ui.horizontal(|ui| {
+            ComboBox::from_label("")
+                .selected_text(self.algorithm.to_string())
+                .show_ui(ui, |ui| {
+                    for algorithm in self.algorithm.to_vec() {
+                        let selected = self.algorithm.same_kind(&algorithm);
+
+                        // Keep the output length when SHAKE is selected again
+                        if ui.selectable_label(selected, algorithm.to_string()).clicked() && !selected {
+                            self.algorithm = algorithm;
+                        }
+                    }
+                });
+
+            if self.algorithm.is_xof() {
+                let mut bits = self.algorithm.output_len() * 8;
+
+                ui.label(rich_text("Output bits"));
+                let drag = DragValue::new(&mut bits).range(8..=hashing::MAX_XOF_LEN * 8).speed(8.0);
+
+                if ui.add(drag).changed() {
+                    self.algorithm = self.algorithm.with_output_len(bits.div_ceil(8));
+                }
+            }
+        });
+
+        self.hash_outdated |= self.algorithm != previous;
+    }
+
+    /// Hash a file and compare it, create and check checksum files
+    fn file_hashing(&mut self, ui: &mut Ui) {
+        ui.label(rich_text("Files").size(16.0));
+
+        ui.horizontal(|ui| {
+            ui.add_space(40.0);
+
+            if ui.add(button(rich_text("Hash a File").color(Color32::BLACK))).clicked() {
+                if let Some(path) = rfd::FileDialog::new().pick_file() {
+                    self.hash_file(path);
+                }
+            }
+
+            let text = rich_text("Checksum Files").color(Color32::BLACK);
+            if ui.add(button(text)).on_hover_text("Create a checksum file for several files").clicked() {
+                if let Some(files) = rfd::FileDialog::new().pick_files() {
+                    self.create_checksums(files, None);
+                }
+            }
+
+            let text = rich_text("Checksum Folder").color(Color32::BLACK);
+            if ui.add(button(text)).on_hover_text("Create a checksum file for every file in a folder").clicked() {
+                if let Some(dir) = rfd::FileDialog::new().pick_folder() {
+                    match hashing::collect_files(&dir) {
+                        Ok(files) => self.create_checksums(files, Some(dir)),
+                        Err(e) => self.pop_msg.write().unwrap().show("Failed to read the folder", format!("{:?}", e)),
                     }
                 }
-            });
+            }
+
+            let text = rich_text("Verify Checksums").color(Color32::BLACK);
+            if ui.add(button(text)).on_hover_text("Check the files listed in a checksum file").clicked() {
+                if let Some(path) = rfd::FileDialog::new().pick_file() {
+                    self.verify_checksums(path);
+                }
+            }
+        });
+
+        let file_digest = self.file_digest.read().unwrap();
+        let Some(algorithm) = file_digest.algorithm else {
+            return;
+        };
+
+        ui.horizontal(|ui| {
+            ui.label(rich_text(format!("{} of {}", algorithm, file_digest.path.display())));
+
+            if ui.add(button(rich_text("Copy").color(Color32::BLACK))).clicked() {
+                if let Err(e) = self.clipboard.copy(&file_digest.digest) {
+                    self.pop_msg.write().unwrap().show("Failed to copy", format!("{:?}", e));
+                }
+            }
+        });
+
+        let mut digest = file_digest.digest.as_str();
+        ui.add(
+            TextEdit::multiline(&mut digest)
+                .desired_width(300.0)
+                .desired_rows(2)
+                .font(FontSelection::FontId(FontId::monospace(13.0)))
+        );
+
+        ui.label(rich_text("Expected digest:"));
+        ui.add(text_edit(&mut self.expected_digest).desired_width(300.0).hint_text("Paste the published digest"));
+
+        if !self.expected_digest.trim().is_empty() {
+            match_label(ui, hashing::digest_matches(&file_digest.digest, &self.expected_digest));
+        }
+    }
+
+    fn hash_file(&mut self, path: PathBuf) {
+        let algorithm = self.algorithm;
+        let file_digest = self.file_digest.clone();
+        let title = format!("{} of {}", algorithm, path.file_name().unwrap_or(path.as_os_str()).to_string_lossy());
+
+        self.jobs.submit(title, move |ctx| {
+            let total = std::fs::metadata(&path).map(|m| m.len()).unwrap_or(0);
+            let mut processed = 0;
+
+            let digest = hashing::hash_file(algorithm, &path, &mut |n| {
+                processed += n;
+                ctx.report(Progress::Payload { processed, total });
+            }, ctx.cancel_token())?;
+
+            ctx.cancel_token().check()?;
+            *file_digest.write().unwrap() = FileDigest { path, algorithm: Some(algorithm), digest: digest.clone() };
+
+            Ok(digest)
+        });
+    }
+
+    /// Hash the files and save a checksum file
+    ///
+    /// `dir` is the folder the files were collected from, where the checksum file is suggested
+    fn create_checksums(&mut self, files: Vec<PathBuf>, dir: Option<PathBuf>) {
+        let Some(dir) = dir.or_else(|| files.first().and_then(|f| f.parent()).map(Path::to_path_buf)) else {
+            return;
+        };
+
+        let Some(output) = rfd::FileDialog::new()
+            .set_directory(&dir)
+            .set_file_name(self.algorithm.checksum_file_name())
+            .save_file()
+        else {
+            return;
+        };
+
+        // Don't hash an old version of the checksum file itself
+        let files: Vec<PathBuf> = files.into_iter().filter(|f| *f != output).collect();
+
+        let algorithm = self.algorithm;
+
+        self.jobs.submit(format!("Hashing {} files", files.len()), move |ctx| {
+            let base = output.parent().unwrap_or(Path::new("."));
+
+            let checksums = hashing::create_checksums(algorithm, &files, base, &mut ctx.progress(), ctx.cancel_token())?;
+            file::write_atomic(&output, checksums.as_bytes(), true, ctx.cancel_token())
+                .map_err(|e| e.context("Failed to save the checksums"))?;
+
+            Ok(format!("{} checksums of {} files saved to {}", algorithm, files.len(), output.display()))
+        });
+    }
+
+    /// Check every file listed in a checksum file and show which ones match
+    fn verify_checksums(&mut self, path: PathBuf) {
+        let entries = std::fs::read_to_string(&path)
+            .map_err(|e| e.into())
+            .and_then(|text| hashing::parse_checksums(&text));
+
+        let entries = match entries {
+            Ok(entries) => entries,
+            Err(e) => return self.pop_msg.write().unwrap().show("Invalid checksum file", format!("{:?}", e)),
+        };
+
+        // Lines without an algorithm use the one the file is named after, or the selected one
+        let default = path
+            .file_name()
+            .and_then(|name| HashAlgorithm::from_checksum_file_name(&name.to_string_lossy()))
+            .unwrap_or(self.algorithm);
+
+        self.jobs.submit(format!("Checking {} files", entries.len()), move |ctx| {
+            let base = path.parent().unwrap_or(Path::new("."));
+
+            let results = hashing::verify_checksums(&entries, Some(default), base, &mut ctx.progress(), ctx.cancel_token())?;
+
+            let failed: Vec<_> = results.iter().filter(|r| r.status != CheckStatus::Ok).collect();
+
+            let mut message = format!("{} of {} files match", results.len() - failed.len(), results.len());
+
+            // Same wording as `sha3sum -c`
+            for result in &failed {
+                match &result.status {
+                    CheckStatus::Mismatch => message.push_str(&format!("\n{}: FAILED", result.path)),
+                    CheckStatus::Error(e) => message.push_str(&format!("\n{}: FAILED open or read ({})", result.path, e)),
+                    CheckStatus::Ok => {}
+                }
+            }
+
+            if failed.is_empty() {
+                Ok(message)
+            } else {
+                Err(anyhow::anyhow!(message))
+            }
+        });
     }
-}
\ No newline at end of file
+}
+
+/// Returns true when another encoding was selected
+fn select_encoding<T: Display + PartialEq + Copy>(ui: &mut Ui, id: &str, encoding: &mut T, encodings: Vec<T>) -> bool {
+    let previous = *encoding;
+
+    ComboBox::from_id_salt(id)
+        .selected_text(encoding.to_string())
+        .show_ui(ui, |ui| {
+            for option in encodings {
+                ui.selectable_value(encoding, option, option.to_string());
+            }
+        });
+
+    *encoding != previous
+}
+
+fn match_label(ui: &mut Ui, matches: bool) {
+    if matches {
+        ui.label(rich_text("✔ Match").size(16.0).color(Color32::from_rgb(40, 167, 69)));
+    } else {
+        ui.label(rich_text("✖ Mismatch").size(16.0).color(Color32::from_rgb(220, 53, 69)));
+    }
+}
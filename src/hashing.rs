@@ -0,0 +1,1058 @@
+//! Hashing of text and files, and `sha3sum` compatible checksum files
+//!
+//! Checksum files use the GNU format written by `sha3sum` and `sha256sum`, one `<digest>  <path>` line per file.
+//! Reading also accepts the BSD format, `SHA3-256 (<path>) = <digest>`, written with `--tag`.
+//!
+//! Keyed hashing uses HMAC for the fixed size digests, KMAC (NIST SP 800-185) for SHAKE and the keyed mode of BLAKE3.
+
+use sha2::{ Sha224, Sha256, Sha384, Sha512 };
+use sha3::{ CShake128, CShake128Core, CShake256, CShake256Core, Keccak256, Sha3_224, Sha3_256, Sha3_384, Sha3_512, Shake128, Shake256 };
+use sha3::digest::{ core_api::BlockSizeUser, Digest, ExtendableOutput, Update };
+use blake2::{ Blake2b512, Blake2s256 };
+use hmac::{ Mac, SimpleHmac };
+use base64::{ alphabet, Engine };
+use base64::engine::{ DecodePaddingMode, GeneralPurpose, GeneralPurposeConfig };
+use encryption::progress::{ CancelToken, Progress };
+use encryption::secret::SecretBytes;
+use encryption::zeroize::Zeroize;
+use std::fmt;
+use std::fs::{ self, File };
+use std::io::Read;
+use std::mem;
+use std::path::{ Path, PathBuf };
+use std::sync::OnceLock;
+
+use anyhow::anyhow;
+
+/// Bytes hashed between progress reports
+const CHUNK_SIZE: usize = 1024 * 1024;
+
+/// Largest output of SHAKE the user can ask for, in bytes
+pub const MAX_XOF_LEN: usize = 1024;
+
+/// Length of a BLAKE3 key
+pub const BLAKE3_KEY_LEN: usize = 32;
+
+/// Rates of cSHAKE128 and cSHAKE256 in bytes, KMAC pads the key to them
+const CSHAKE128_RATE: usize = 168;
+const CSHAKE256_RATE: usize = 136;
+
+/// Padding is optional when decoding base64, encoding pads
+const BASE64: GeneralPurpose = GeneralPurpose::new(
+    &alphabet::STANDARD,
+    GeneralPurposeConfig::new().with_decode_padding_mode(DecodePaddingMode::Indifferent)
+);
+
+const BASE64_URL: GeneralPurpose = GeneralPurpose::new(
+    &alphabet::URL_SAFE,
+    GeneralPurposeConfig::new().with_encode_padding(false).with_decode_padding_mode(DecodePaddingMode::Indifferent)
+);
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum HashAlgorithm {
+    Sha224,
+    Sha256,
+    Sha384,
+    Sha512,
+    Sha3_224,
+    Sha3_256,
+    Sha3_384,
+    Sha3_512,
+    Blake2b512,
+    Blake2s256,
+    Blake3,
+    Keccak256,
+
+    /// Output length in bytes
+    Shake128(usize),
+
+    /// Output length in bytes
+    Shake256(usize),
+}
+
+impl fmt::Display for HashAlgorithm {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            HashAlgorithm::Sha224 => "SHA-224",
+            HashAlgorithm::Sha256 => "SHA-256",
+            HashAlgorithm::Sha384 => "SHA-384",
+            HashAlgorithm::Sha512 => "SHA-512",
+            HashAlgorithm::Sha3_224 => "SHA3-224",
+            HashAlgorithm::Sha3_256 => "SHA3-256",
+            HashAlgorithm::Sha3_384 => "SHA3-384",
+            HashAlgorithm::Sha3_512 => "SHA3-512",
+            HashAlgorithm::Blake2b512 => "BLAKE2b-512",
+            HashAlgorithm::Blake2s256 => "BLAKE2s-256",
+            HashAlgorithm::Blake3 => "BLAKE3",
+            HashAlgorithm::Keccak256 => "Keccak-256",
+            HashAlgorithm::Shake128(_) => "SHAKE128",
+            HashAlgorithm::Shake256(_) => "SHAKE256",
+        };
+        write!(f, "{}", name)
+    }
+}
+
+impl HashAlgorithm {
+    pub fn to_vec(&self) -> Vec<HashAlgorithm> {
+        vec![
+            HashAlgorithm::Sha224,
+            HashAlgorithm::Sha256,
+            HashAlgorithm::Sha384,
+            HashAlgorithm::Sha512,
+            HashAlgorithm::Sha3_224,
+            HashAlgorithm::Sha3_256,
+            HashAlgorithm::Sha3_384,
+            HashAlgorithm::Sha3_512,
+            HashAlgorithm::Blake2b512,
+            HashAlgorithm::Blake2s256,
+            HashAlgorithm::Blake3,
+            HashAlgorithm::Keccak256,
+            HashAlgorithm::Shake128(32),
+            HashAlgorithm::Shake256(64)
+        ]
+    }
+
+    pub fn hasher(&self) -> Box<dyn Hasher> {
+        match *self {
+            HashAlgorithm::Sha224 => Box::new(Fixed(Sha224::new())),
+            HashAlgorithm::Sha256 => Box::new(Fixed(Sha256::new())),
+            HashAlgorithm::Sha384 => Box::new(Fixed(Sha384::new())),
+            HashAlgorithm::Sha512 => Box::new(Fixed(Sha512::new())),
+            HashAlgorithm::Sha3_224 => Box::new(Fixed(Sha3_224::new())),
+            HashAlgorithm::Sha3_256 => Box::new(Fixed(Sha3_256::new())),
+            HashAlgorithm::Sha3_384 => Box::new(Fixed(Sha3_384::new())),
+            HashAlgorithm::Sha3_512 => Box::new(Fixed(Sha3_512::new())),
+            HashAlgorithm::Blake2b512 => Box::new(Fixed(Blake2b512::new())),
+            HashAlgorithm::Blake2s256 => Box::new(Fixed(Blake2s256::new())),
+            HashAlgorithm::Blake3 => Box::new(Blake3(blake3::Hasher::new())),
+            HashAlgorithm::Keccak256 => Box::new(Fixed(Keccak256::new())),
+            HashAlgorithm::Shake128(output_len) => Box::new(Xof { xof: Shake128::default(), output_len }),
+            HashAlgorithm::Shake256(output_len) => Box::new(Xof { xof: Shake256::default(), output_len }),
+        }
+    }
+
+    /// Length of the digest in bytes
+    pub fn output_len(&self) -> usize {
+        match *self {
+            HashAlgorithm::Sha224 | HashAlgorithm::Sha3_224 => 28,
+            HashAlgorithm::Sha256 | HashAlgorithm::Sha3_256 => 32,
+            HashAlgorithm::Sha384 | HashAlgorithm::Sha3_384 => 48,
+            HashAlgorithm::Sha512 | HashAlgorithm::Sha3_512 => 64,
+            HashAlgorithm::Blake2b512 => 64,
+            HashAlgorithm::Blake2s256 | HashAlgorithm::Blake3 | HashAlgorithm::Keccak256 => 32,
+            HashAlgorithm::Shake128(output_len) | HashAlgorithm::Shake256(output_len) => output_len,
+        }
+    }
+
+    /// Whether the output length is chosen by the user
+    pub fn is_xof(&self) -> bool {
+        matches!(self, HashAlgorithm::Shake128(_) | HashAlgorithm::Shake256(_))
+    }
+
+    /// Set the output length of SHAKE, other algorithms are returned unchanged
+    pub fn with_output_len(self, output_len: usize) -> Self {
+        let output_len = output_len.clamp(1, MAX_XOF_LEN);
+
+        match self {
+            HashAlgorithm::Shake128(_) => HashAlgorithm::Shake128(output_len),
+            HashAlgorithm::Shake256(_) => HashAlgorithm::Shake256(output_len),
+            algorithm => algorithm,
+        }
+    }
+
+    /// Whether both are the same algorithm, regardless of the output length
+    pub fn same_kind(&self, other: &HashAlgorithm) -> bool {
+        mem::discriminant(self) == mem::discriminant(other)
+    }
+
+    /// The algorithm of a checksum file line, `sha3sum -c` also tells them apart by the digest length
+    pub fn from_digest(digest: &str) -> Option<Self> {
+        match digest.len() {
+            56 => Some(HashAlgorithm::Sha3_224),
+            64 => Some(HashAlgorithm::Sha3_256),
+            96 => Some(HashAlgorithm::Sha3_384),
+            128 => Some(HashAlgorithm::Sha3_512),
+            _ => None,
+        }
+    }
+
+    /// The algorithm named in a BSD style checksum line, `-` and case are ignored so `SHA256` is SHA-256
+    pub fn from_name(name: &str) -> Option<Self> {
+        let normalize = |name: &str| name.replace(['-', '_'], "").to_uppercase();
+        let name = normalize(name);
+
+        HashAlgorithm::Sha256
+            .to_vec()
+            .into_iter()
+            .find(|algorithm| normalize(&algorithm.to_string()) == name || normalize(algorithm.tag()) == name)
+    }
+
+    /// The algorithm of a checksum file named like `SHA256SUMS`, `B2SUMS` or `sha3-256sums.txt`
+    pub fn from_checksum_file_name(file_name: &str) -> Option<Self> {
+        let name = file_name.to_uppercase();
+        let name = name.strip_suffix(".TXT").unwrap_or(&name);
+        let name = name.strip_suffix("SUMS").or_else(|| name.strip_suffix("SUM"))?;
+
+        match name {
+            "B2" => Some(HashAlgorithm::Blake2b512),
+            "B3" => Some(HashAlgorithm::Blake3),
+            name => HashAlgorithm::from_name(name),
+        }
+    }
+
+    /// Name used by the `--tag` output of coreutils
+    fn tag(&self) -> &'static str {
+        match self {
+            HashAlgorithm::Sha224 => "SHA224",
+            HashAlgorithm::Sha256 => "SHA256",
+            HashAlgorithm::Sha384 => "SHA384",
+            HashAlgorithm::Sha512 => "SHA512",
+            HashAlgorithm::Sha3_224 => "SHA3-224",
+            HashAlgorithm::Sha3_256 => "SHA3-256",
+            HashAlgorithm::Sha3_384 => "SHA3-384",
+            HashAlgorithm::Sha3_512 => "SHA3-512",
+            HashAlgorithm::Blake2b512 => "BLAKE2b",
+            HashAlgorithm::Blake2s256 => "BLAKE2s",
+            HashAlgorithm::Blake3 => "BLAKE3",
+            HashAlgorithm::Keccak256 => "KECCAK-256",
+            HashAlgorithm::Shake128(_) => "SHAKE128",
+            HashAlgorithm::Shake256(_) => "SHAKE256",
+        }
+    }
+
+    /// Conventional name of a checksum file, like `SHA256SUMS` or `SHA3-256SUMS`
+    pub fn checksum_file_name(&self) -> String {
+        match self {
+            HashAlgorithm::Blake2b512 => "B2SUMS".to_string(),
+            HashAlgorithm::Blake3 => "B3SUMS".to_string(),
+            algorithm => format!("{}SUMS", algorithm.tag().to_uppercase()),
+        }
+    }
+
+    /// Name of the keyed mode, like `HMAC-SHA-256`, `KMAC128` or `BLAKE3-keyed`
+    pub fn keyed_name(&self) -> String {
+        match self {
+            HashAlgorithm::Shake128(_) => "KMAC128".to_string(),
+            HashAlgorithm::Shake256(_) => "KMAC256".to_string(),
+            HashAlgorithm::Blake3 => "BLAKE3-keyed".to_string(),
+            algorithm => format!("HMAC-{}", algorithm),
+        }
+    }
+
+    /// Whether the keyed mode takes a customization string, only KMAC does
+    pub fn is_kmac(&self) -> bool {
+        self.is_xof()
+    }
+
+    /// A hasher of the keyed mode
+    ///
+    /// ### Arguments
+    ///
+    /// - `key` - The secret key, any length except for BLAKE3 which takes 32 bytes
+    /// - `customization` - The customization string of KMAC, ignored by the other modes
+    pub fn keyed_hasher(&self, key: &[u8], customization: &[u8]) -> Result<Box<dyn Hasher>, anyhow::Error> {
+        let hasher: Box<dyn Hasher> = match *self {
+            HashAlgorithm::Sha224 => hmac::<Sha224>(key)?,
+            HashAlgorithm::Sha256 => hmac::<Sha256>(key)?,
+            HashAlgorithm::Sha384 => hmac::<Sha384>(key)?,
+            HashAlgorithm::Sha512 => hmac::<Sha512>(key)?,
+            HashAlgorithm::Sha3_224 => hmac::<Sha3_224>(key)?,
+            HashAlgorithm::Sha3_256 => hmac::<Sha3_256>(key)?,
+            HashAlgorithm::Sha3_384 => hmac::<Sha3_384>(key)?,
+            HashAlgorithm::Sha3_512 => hmac::<Sha3_512>(key)?,
+            HashAlgorithm::Blake2b512 => hmac::<Blake2b512>(key)?,
+            HashAlgorithm::Blake2s256 => hmac::<Blake2s256>(key)?,
+            HashAlgorithm::Keccak256 => hmac::<Keccak256>(key)?,
+            HashAlgorithm::Blake3 => {
+                let key: &[u8; BLAKE3_KEY_LEN] = key
+                    .try_into()
+                    .map_err(|_| anyhow!("BLAKE3 keys are {} bytes, this key is {} bytes", BLAKE3_KEY_LEN, key.len()))?;
+                Box::new(Blake3(blake3::Hasher::new_keyed(key)))
+            }
+            HashAlgorithm::Shake128(output_len) => {
+                let cshake = CShake128::from_core(CShake128Core::new_with_function_name(b"KMAC", customization));
+                Box::new(Kmac::new(cshake, CSHAKE128_RATE, key, output_len))
+            }
+            HashAlgorithm::Shake256(output_len) => {
+                let cshake = CShake256::from_core(CShake256Core::new_with_function_name(b"KMAC", customization));
+                Box::new(Kmac::new(cshake, CSHAKE256_RATE, key, output_len))
+            }
+        };
+
+        Ok(hasher)
+    }
+
+    /// The algorithm to check a digest with, named in the line or the default if the length fits
+    fn resolve(entry: &ChecksumEntry, default: Option<HashAlgorithm>) -> Option<Self> {
+        let fits = |algorithm: &HashAlgorithm| algorithm.output_len() * 2 == entry.digest.len();
+
+        entry.algorithm
+            .or_else(|| default.map(|d| d.with_output_len(entry.digest.len() / 2)).filter(fits))
+            .or_else(|| HashAlgorithm::from_digest(&entry.digest))
+    }
+}
+
+/// A hash computation in progress
+///
+/// Every algorithm is driven through this trait, [HashAlgorithm::hasher] picks the implementation
+pub trait Hasher: Send {
+    fn update(&mut self, data: &[u8]);
+
+    /// Consume the hasher and return the digest
+    fn finalize(self: Box<Self>) -> Vec<u8>;
+}
+
+/// A fixed size digest of the RustCrypto crates
+struct Fixed<D>(D);
+
+impl<D: Digest + Send> Hasher for Fixed<D> {
+    fn update(&mut self, data: &[u8]) {
+        Digest::update(&mut self.0, data);
+    }
+
+    fn finalize(self: Box<Self>) -> Vec<u8> {
+        self.0.finalize().to_vec()
+    }
+}
+
+/// An extendable output function, read up to the chosen length
+struct Xof<X> {
+    xof: X,
+    output_len: usize,
+}
+
+impl<X: Update + ExtendableOutput + Send> Hasher for Xof<X> {
+    fn update(&mut self, data: &[u8]) {
+        self.xof.update(data);
+    }
+
+    fn finalize(self: Box<Self>) -> Vec<u8> {
+        self.xof.finalize_boxed(self.output_len).into_vec()
+    }
+}
+
+/// HMAC over any of the fixed size digests
+struct Hmac<M>(M);
+
+impl<M: Mac + Send> Hasher for Hmac<M> {
+    fn update(&mut self, data: &[u8]) {
+        Mac::update(&mut self.0, data);
+    }
+
+    fn finalize(self: Box<Self>) -> Vec<u8> {
+        self.0.finalize().into_bytes().to_vec()
+    }
+}
+
+fn hmac<D>(key: &[u8]) -> Result<Box<dyn Hasher>, anyhow::Error>
+where
+    D: Digest + BlockSizeUser + Clone + Send + 'static,
+{
+    let mac = SimpleHmac::<D>::new_from_slice(key).map_err(|e| anyhow!("Invalid HMAC key {}", e))?;
+    Ok(Box::new(Hmac(mac)))
+}
+
+/// KMAC, cSHAKE named "KMAC" that absorbs the padded key before the data
+/// and the output length after it
+struct Kmac<X> {
+    cshake: X,
+    output_len: usize,
+}
+
+impl<X: Update> Kmac<X> {
+    fn new(mut cshake: X, rate: usize, key: &[u8], output_len: usize) -> Self {
+        let mut padded_key = bytepad(&encode_string(key), rate);
+        cshake.update(&padded_key);
+        padded_key.zeroize();
+
+        Self { cshake, output_len }
+    }
+}
+
+impl<X: Update + ExtendableOutput + Send> Hasher for Kmac<X> {
+    fn update(&mut self, data: &[u8]) {
+        self.cshake.update(data);
+    }
+
+    fn finalize(mut self: Box<Self>) -> Vec<u8> {
+        self.cshake.update(&right_encode(self.output_len as u64 * 8));
+        self.cshake.finalize_boxed(self.output_len).into_vec()
+    }
+}
+
+/// The integer encodings of SP 800-185, the big endian bytes with their count before or after them
+fn left_encode(x: u64) -> Vec<u8> {
+    let bytes = x.to_be_bytes();
+    let skip = bytes.iter().take_while(|b| **b == 0).count().min(7);
+
+    let mut encoded = vec![(8 - skip) as u8];
+    encoded.extend_from_slice(&bytes[skip..]);
+    encoded
+}
+
+fn right_encode(x: u64) -> Vec<u8> {
+    let mut encoded = left_encode(x);
+    encoded.rotate_left(1);
+    encoded
+}
+
+fn encode_string(s: &[u8]) -> Vec<u8> {
+    let mut encoded = left_encode(s.len() as u64 * 8);
+    encoded.extend_from_slice(s);
+    encoded
+}
+
+fn bytepad(x: &[u8], w: usize) -> Vec<u8> {
+    let mut padded = left_encode(w as u64);
+    padded.extend_from_slice(x);
+    padded.resize(padded.len().div_ceil(w) * w, 0);
+    padded
+}
+
+struct Blake3(blake3::Hasher);
+
+impl Hasher for Blake3 {
+    fn update(&mut self, data: &[u8]) {
+        self.0.update(data);
+    }
+
+    fn finalize(self: Box<Self>) -> Vec<u8> {
+        self.0.finalize().as_bytes().to_vec()
+    }
+}
+
+/// Hash the data and return the digest as lowercase hex
+pub fn hash_bytes(algorithm: HashAlgorithm, data: &[u8]) -> String {
+    to_hex(&hash(algorithm, data))
+}
+
+pub fn hash(algorithm: HashAlgorithm, data: &[u8]) -> Vec<u8> {
+    let mut hasher = algorithm.hasher();
+    hasher.update(data);
+    hasher.finalize()
+}
+
+/// The keyed hash of the data, see [HashAlgorithm::keyed_hasher]
+pub fn keyed_hash(algorithm: HashAlgorithm, key: &[u8], customization: &[u8], data: &[u8]) -> Result<Vec<u8>, anyhow::Error> {
+    let mut hasher = algorithm.keyed_hasher(key, customization)?;
+    hasher.update(data);
+    Ok(hasher.finalize())
+}
+
+/// Keyed BLAKE3 of a secret, to tell later whether it changed or is still in the clipboard
+///
+/// The key is drawn once per run, a fingerprint left in memory cannot be used to guess the secret offline
+pub fn fingerprint(data: &[u8]) -> blake3::Hash {
+    static KEY: OnceLock<SecretBytes> = OnceLock::new();
+
+    let key = KEY.get_or_init(|| encryption::generate::random_bytes(blake3::KEY_LEN));
+    blake3::keyed_hash(key.expose().try_into().expect("BLAKE3 key length"), data)
+}
+
+/// How a digest is written out
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum OutputEncoding {
+    HexLower,
+    HexUpper,
+    Base64,
+
+    /// URL safe alphabet without padding
+    Base64Url,
+
+    /// Like `[0x3a, 0x98]`, to paste in code
+    ByteArray,
+}
+
+impl fmt::Display for OutputEncoding {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            OutputEncoding::HexLower => "hex",
+            OutputEncoding::HexUpper => "HEX",
+            OutputEncoding::Base64 => "Base64",
+            OutputEncoding::Base64Url => "Base64url",
+            OutputEncoding::ByteArray => "Byte array",
+        };
+        write!(f, "{}", name)
+    }
+}
+
+impl OutputEncoding {
+    pub fn to_vec() -> Vec<Self> {
+        vec![
+            OutputEncoding::HexLower,
+            OutputEncoding::HexUpper,
+            OutputEncoding::Base64,
+            OutputEncoding::Base64Url,
+            OutputEncoding::ByteArray
+        ]
+    }
+
+    pub fn encode(&self, digest: &[u8]) -> String {
+        match self {
+            OutputEncoding::HexLower => to_hex(digest),
+            OutputEncoding::HexUpper => to_hex(digest).to_uppercase(),
+            OutputEncoding::Base64 => BASE64.encode(digest),
+            OutputEncoding::Base64Url => BASE64_URL.encode(digest),
+            OutputEncoding::ByteArray => {
+                let bytes: Vec<String> = digest.iter().map(|b| format!("0x{:02x}", b)).collect();
+                format!("[{}]", bytes.join(", "))
+            }
+        }
+    }
+
+    pub fn decode(&self, text: &str) -> Option<Vec<u8>> {
+        match self {
+            OutputEncoding::HexLower | OutputEncoding::HexUpper => from_hex(text).ok(),
+            OutputEncoding::Base64 => BASE64.decode(strip_whitespace(text)).ok(),
+            OutputEncoding::Base64Url => BASE64_URL.decode(strip_whitespace(text)).ok(),
+            OutputEncoding::ByteArray => {
+                let text = text.trim().trim_start_matches('[').trim_end_matches(']');
+
+                text.split(',')
+                    .map(|byte| {
+                        let byte = byte.trim();
+                        match byte.strip_prefix("0x") {
+                            Some(hex) => u8::from_str_radix(hex, 16).ok(),
+                            None => byte.parse().ok(),
+                        }
+                    })
+                    .collect()
+            }
+        }
+    }
+}
+
+/// How the text typed by the user is turned into bytes
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum InputEncoding {
+    Utf8,
+    Hex,
+    Base64,
+}
+
+impl fmt::Display for InputEncoding {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            InputEncoding::Utf8 => "UTF-8",
+            InputEncoding::Hex => "Hex",
+            InputEncoding::Base64 => "Base64",
+        };
+        write!(f, "{}", name)
+    }
+}
+
+impl InputEncoding {
+    pub fn to_vec() -> Vec<Self> {
+        vec![InputEncoding::Utf8, InputEncoding::Hex, InputEncoding::Base64]
+    }
+
+    /// Whitespace is ignored in hex and base64, base64 may use either alphabet
+    pub fn decode(&self, text: &str) -> Result<Vec<u8>, anyhow::Error> {
+        match self {
+            InputEncoding::Utf8 => Ok(text.as_bytes().to_vec()),
+            InputEncoding::Hex => from_hex(text),
+            InputEncoding::Base64 => {
+                let mut text = strip_whitespace(text);
+                let decoded = if text.contains(['-', '_']) { BASE64_URL.decode(&text) } else { BASE64.decode(&text) };
+                text.zeroize();
+
+                decoded.map_err(|e| anyhow!("Invalid base64 {}", e))
+            }
+        }
+    }
+}
+
+/// Whether a digest or signature pasted by the user matches
+///
+/// The expected value can be in any [OutputEncoding] and prefixed like the `sha256=` of webhook headers.
+/// Compared in constant time.
+pub fn signature_matches(digest: &[u8], expected: &str) -> bool {
+    let expected = expected.trim();
+    let expected = match expected.split_once('=') {
+        Some((name, signature)) if !name.is_empty() && !signature.is_empty() && name.chars().all(|c| c.is_ascii_alphanumeric() || c == '-') => signature,
+        _ => expected,
+    };
+
+    OutputEncoding::to_vec()
+        .iter()
+        .filter_map(|encoding| encoding.decode(expected))
+        .any(|expected| constant_time_eq(digest, &expected))
+}
+
+fn constant_time_eq(a: &[u8], b: &[u8]) -> bool {
+    a.len() == b.len() && a.iter().zip(b).fold(0, |diff, (a, b)| diff | (a ^ b)) == 0
+}
+
+/// Hash a file without reading it into memory at once
+///
+/// ### Arguments
+///
+/// - `progress` - Called with the number of bytes hashed after each chunk
+/// - `cancel` - Checked between chunks
+pub fn hash_file(
+    algorithm: HashAlgorithm,
+    path: &Path,
+    progress: &mut dyn FnMut(u64),
+    cancel: &CancelToken,
+) -> Result<String, anyhow::Error> {
+    let mut file = File::open(path).map_err(|e| anyhow!("Failed to open {} {}", path.display(), e))?;
+    let mut hasher = algorithm.hasher();
+    let mut buf = vec![0u8; CHUNK_SIZE];
+
+    loop {
+        cancel.check()?;
+
+        let n = file.read(&mut buf).map_err(|e| anyhow!("Failed to read {} {}", path.display(), e))?;
+        if n == 0 {
+            break;
+        }
+
+        hasher.update(&buf[..n]);
+        progress(n as u64);
+    }
+
+    Ok(to_hex(&hasher.finalize()))
+}
+
+/// Whether a digest pasted by the user matches, ignoring case and surrounding whitespace
+pub fn digest_matches(digest: &str, expected: &str) -> bool {
+    digest.eq_ignore_ascii_case(expected.trim())
+}
+
+/// A line of a checksum file
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ChecksumEntry {
+    pub digest: String,
+
+    /// As written in the file, relative to the folder of the checksum file
+    pub path: String,
+
+    /// Named in BSD style lines, otherwise the default of [verify_checksums] or guessed from the digest length
+    pub algorithm: Option<HashAlgorithm>,
+}
+
+/// Result of checking one file of a checksum file
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum CheckStatus {
+    Ok,
+    Mismatch,
+
+    /// The file is missing, unreadable or its line could not be understood
+    Error(String),
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct CheckResult {
+    pub path: String,
+    pub status: CheckStatus,
+}
+
+/// Every file in the folder and its subfolders, sorted, symbolic links are not followed
+pub fn collect_files(dir: &Path) -> Result<Vec<PathBuf>, anyhow::Error> {
+    let mut files = Vec::new();
+    let mut dirs = vec![dir.to_path_buf()];
+
+    while let Some(dir) = dirs.pop() {
+        for entry in fs::read_dir(&dir).map_err(|e| anyhow!("Failed to read {} {}", dir.display(), e))? {
+            let entry = entry?;
+            let file_type = entry.file_type()?;
+
+            if file_type.is_dir() {
+                dirs.push(entry.path());
+            } else if file_type.is_file() {
+                files.push(entry.path());
+            }
+        }
+    }
+
+    files.sort();
+    Ok(files)
+}
+
+/// Hash the files and return the content of a checksum file
+///
+/// ### Arguments
+///
+/// - `files` - The files to hash
+/// - `base` - The folder of the checksum file, paths are written relative to it when possible
+/// - `progress` - Reports the bytes hashed over all the files
+/// - `cancel` - Checked between chunks
+pub fn create_checksums(
+    algorithm: HashAlgorithm,
+    files: &[PathBuf],
+    base: &Path,
+    progress: &mut dyn FnMut(Progress),
+    cancel: &CancelToken,
+) -> Result<String, anyhow::Error> {
+    let total = total_size(files.iter().map(|p| p.as_path()));
+    let mut processed = 0;
+    let mut checksums = String::new();
+
+    for path in files {
+        let name = relative_name(path, base)?;
+
+        let digest = hash_file(algorithm, path, &mut |n| {
+            processed += n;
+            progress(Progress::Payload { processed, total });
+        }, cancel)?;
+
+        checksums.push_str(&format_line(&digest, &name));
+    }
+
+    Ok(checksums)
+}
+
+/// Parse a checksum file, empty lines and `#` comments are skipped
+pub fn parse_checksums(text: &str) -> Result<Vec<ChecksumEntry>, anyhow::Error> {
+    let mut entries = Vec::new();
+
+    for (i, line) in text.lines().enumerate() {
+        let line = line.trim_end_matches('\r');
+
+        if line.trim().is_empty() || line.starts_with('#') {
+            continue;
+        }
+
+        let entry = parse_line(line).ok_or(anyhow!("Line {} is not a checksum line: {}", i + 1, line))?;
+        entries.push(entry);
+    }
+
+    if entries.is_empty() {
+        return Err(anyhow!("No checksums found"));
+    }
+
+    Ok(entries)
+}
+
+/// Check the files listed in a checksum file
+///
+/// A file that can't be read is reported in its [CheckResult], only cancelling stops the check
+///
+/// ### Arguments
+///
+/// - `entries` - The parsed checksum file
+/// - `default` - Algorithm of the lines that don't name one, usually from the name of the checksum file
+/// - `base` - The folder of the checksum file, relative paths are resolved from it
+/// - `progress` - Reports the bytes hashed over all the files
+/// - `cancel` - Checked between chunks
+pub fn verify_checksums(
+    entries: &[ChecksumEntry],
+    default: Option<HashAlgorithm>,
+    base: &Path,
+    progress: &mut dyn FnMut(Progress),
+    cancel: &CancelToken,
+) -> Result<Vec<CheckResult>, anyhow::Error> {
+    let paths: Vec<PathBuf> = entries.iter().map(|entry| base.join(&entry.path)).collect();
+    let total = total_size(paths.iter().map(|p| p.as_path()));
+    let mut processed = 0;
+    let mut results = Vec::new();
+
+    for (entry, path) in entries.iter().zip(&paths) {
+        let status = match HashAlgorithm::resolve(entry, default) {
+            None => CheckStatus::Error("Unknown digest length".to_string()),
+            Some(algorithm) if algorithm.output_len() * 2 != entry.digest.len() => {
+                CheckStatus::Error(format!("Wrong digest length for {}", algorithm))
+            }
+            Some(algorithm) => {
+                let digest = hash_file(algorithm, path, &mut |n| {
+                    processed += n;
+                    progress(Progress::Payload { processed, total });
+                }, cancel);
+
+                match digest {
+                    Ok(digest) if digest_matches(&digest, &entry.digest) => CheckStatus::Ok,
+                    Ok(_) => CheckStatus::Mismatch,
+                    Err(e) if e.is::<encryption::progress::Cancelled>() => return Err(e),
+                    Err(e) => CheckStatus::Error(format!("{}", e)),
+                }
+            }
+        };
+
+        results.push(CheckResult { path: entry.path.clone(), status });
+    }
+
+    Ok(results)
+}
+
+fn total_size<'a>(paths: impl Iterator<Item = &'a Path>) -> u64 {
+    paths.filter_map(|path| fs::metadata(path).ok()).map(|m| m.len()).sum()
+}
+
+/// A GNU style line, names with a backslash or a newline are escaped and the line starts with a backslash
+fn format_line(digest: &str, name: &str) -> String {
+    if name.contains(['\\', '\n']) {
+        let escaped = name.replace('\\', "\\\\").replace('\n', "\\n");
+        format!("\\{}  {}\n", digest, escaped)
+    } else {
+        format!("{}  {}\n", digest, name)
+    }
+}
+
+fn parse_line(line: &str) -> Option<ChecksumEntry> {
+    let (escaped, line) = match line.strip_prefix('\\') {
+        Some(line) => (true, line),
+        None => (false, line),
+    };
+
+    if let Some(entry) = parse_bsd_line(line, escaped) {
+        return Some(entry);
+    }
+
+    // GNU style: digest, a space, a space or `*` for binary mode, then the name
+    let (digest, rest) = line.split_once(' ')?;
+    let path = rest.strip_prefix([' ', '*'])?;
+
+    if !is_hex(digest) || path.is_empty() {
+        return None;
+    }
+
+    Some(ChecksumEntry {
+        digest: digest.to_lowercase(),
+        path: unescape(path, escaped),
+        algorithm: None,
+    })
+}
+
+/// BSD style: SHA3-256 (name) = digest
+fn parse_bsd_line(line: &str, escaped: bool) -> Option<ChecksumEntry> {
+    let (name, digest) = line.rsplit_once(") = ")?;
+    let (algorithm, path) = name.split_once(" (")?;
+    let algorithm = HashAlgorithm::from_name(algorithm)?.with_output_len(digest.len() / 2);
+
+    is_hex(digest).then(|| ChecksumEntry {
+        digest: digest.to_lowercase(),
+        path: unescape(path, escaped),
+        algorithm: Some(algorithm),
+    })
+}
+
+fn unescape(name: &str, escaped: bool) -> String {
+    if !escaped {
+        return name.to_string();
+    }
+
+    let mut unescaped = String::with_capacity(name.len());
+    let mut chars = name.chars();
+
+    while let Some(c) = chars.next() {
+        match (c, chars.clone().next()) {
+            ('\\', Some('\\')) => {
+                unescaped.push('\\');
+                chars.next();
+            }
+            ('\\', Some('n')) => {
+                unescaped.push('\n');
+                chars.next();
+            }
+            _ => unescaped.push(c),
+        }
+    }
+
+    unescaped
+}
+
+fn is_hex(s: &str) -> bool {
+    !s.is_empty() && s.chars().all(|c| c.is_ascii_hexdigit())
+}
+
+/// Path relative to `base` with `/` separators, the full path if it is not inside `base`
+///
+/// Fails for names that are not valid UTF-8, they could not be found again when verifying
+fn relative_name(path: &Path, base: &Path) -> Result<String, anyhow::Error> {
+    let invalid = || anyhow!("{} is not a valid UTF-8 name", path.display());
+
+    match path.strip_prefix(base) {
+        Ok(relative) => relative
+            .components()
+            .map(|c| c.as_os_str().to_str().ok_or_else(invalid))
+            .collect::<Result<Vec<_>, _>>()
+            .map(|names| names.join("/")),
+        Err(_) => path.to_str().map(str::to_string).ok_or_else(invalid),
+    }
+}
+
+pub fn to_hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|b| format!("{:02x}", b)).collect()
+}
+
+/// Whitespace and a `0x` prefix are ignored
+///
+/// The text may be a key, the copy without whitespace is zeroized
+pub fn from_hex(text: &str) -> Result<Vec<u8>, anyhow::Error> {
+    let mut stripped = strip_whitespace(text);
+    let decoded = decode_hex(&stripped);
+    stripped.zeroize();
+
+    decoded
+}
+
+fn decode_hex(text: &str) -> Result<Vec<u8>, anyhow::Error> {
+    let text = text.strip_prefix("0x").unwrap_or(text);
+
+    if !text.len().is_multiple_of(2) || !(text.is_empty() || is_hex(text)) {
+        return Err(anyhow!("Invalid hex, expected pairs of 0-9 and a-f"));
+    }
+
+    (0..text.len())
+        .step_by(2)
+        .map(|i| u8::from_str_radix(&text[i..i + 2], 16).map_err(|e| anyhow!("Invalid hex {}", e)))
+        .collect()
+}
+
+fn strip_whitespace(text: &str) -> String {
+    text.chars().filter(|c| !c.is_whitespace()).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn known_digests() {
+        assert_eq!(
+            hash_bytes(HashAlgorithm::Sha3_256, b"abc"),
+            "3a985da74fe225b2045c172d6bd390bd855f086e3e9d525b46bfe24511431532"
+        );
+        assert_eq!(HashAlgorithm::from_digest(&hash_bytes(HashAlgorithm::Sha3_384, b"")), Some(HashAlgorithm::Sha3_384));
+        assert!(digest_matches("3A98", " 3a98\n"));
+    }
+
+    #[test]
+    fn every_algorithm() {
+        let cases = [
+            (HashAlgorithm::Sha256, "ba7816bf8f01cfea414140de5dae2223b00361a396177a9cb410ff61f20015ad"),
+            (HashAlgorithm::Blake2s256, "508c5e8c327c14e2e1a72ba34eeb452f37458b209ed63a294d999b4c86675982"),
+            (HashAlgorithm::Blake3, "6437b3ac38465133ffb63b75273a8db548c558465d79db03fd359c6cd5bd9d85"),
+            (HashAlgorithm::Keccak256, "4e03657aea45a94fc7d47ba826c8d667c0d1e6e33a64a036ec44f58fa12d6c45"),
+            (HashAlgorithm::Shake128(32), "5881092dd818bf5cf8a3ddb793fbcba74097d5c526a6d35f97b83351940f2cc8"),
+        ];
+
+        for (algorithm, digest) in cases {
+            assert_eq!(hash_bytes(algorithm, b"abc"), digest, "{}", algorithm);
+        }
+
+        for algorithm in HashAlgorithm::Sha256.to_vec() {
+            assert_eq!(hash_bytes(algorithm, b"").len(), algorithm.output_len() * 2, "{}", algorithm);
+            assert_eq!(HashAlgorithm::from_name(&algorithm.to_string()), Some(algorithm));
+        }
+
+        // SHAKE output is a prefix of a longer output
+        let short = hash_bytes(HashAlgorithm::Shake256(5), b"abc");
+        assert_eq!(short, "4833666013");
+        assert!(hash_bytes(HashAlgorithm::Shake256(100), b"abc").starts_with(&short));
+        assert_eq!(HashAlgorithm::Shake256(64).with_output_len(0), HashAlgorithm::Shake256(1));
+
+        assert_eq!(HashAlgorithm::from_name("sha256"), Some(HashAlgorithm::Sha256));
+        assert_eq!(HashAlgorithm::from_checksum_file_name("SHA256SUMS"), Some(HashAlgorithm::Sha256));
+        assert_eq!(HashAlgorithm::from_checksum_file_name("sha3-512sums.txt"), Some(HashAlgorithm::Sha3_512));
+        assert_eq!(HashAlgorithm::from_checksum_file_name("B2SUMS"), Some(HashAlgorithm::Blake2b512));
+        assert_eq!(HashAlgorithm::from_checksum_file_name("notes.txt"), None);
+    }
+
+    #[test]
+    fn keyed_hashes() {
+        // RFC 4231 test case 2
+        let mac = keyed_hash(HashAlgorithm::Sha256, b"Jefe", b"", b"what do ya want for nothing?").unwrap();
+        assert_eq!(to_hex(&mac), "5bdcc146bf60754e6a042426089575c75a003f089d2739839dec58b964ec3843");
+        assert!(signature_matches(&mac, "sha256=5bdcc146bf60754e6a042426089575c75a003f089d2739839dec58b964ec3843"));
+        assert!(signature_matches(&mac, &OutputEncoding::Base64.encode(&mac)));
+        assert!(!signature_matches(&mac, "5bdcc146bf60754e6a042426089575c75a003f089d2739839dec58b964ec3844"));
+
+        // NIST SP 800-185 KMAC samples 1, 2 and 4
+        let key: Vec<u8> = (0x40..=0x5f).collect();
+        let data = [0, 1, 2, 3];
+        let kmac128 = keyed_hash(HashAlgorithm::Shake128(32), &key, b"", &data).unwrap();
+        assert_eq!(to_hex(&kmac128), "e5780b0d3ea6f7d3a429c5706aa43a00fadbd7d49628839e3187243f456ee14e");
+
+        let kmac128 = keyed_hash(HashAlgorithm::Shake128(32), &key, b"My Tagged Application", &data).unwrap();
+        assert_eq!(to_hex(&kmac128), "3b1fba963cd8b0b59e8c1a6d71888b7143651af8ba0a7070c0979e2811324aa5");
+
+        let kmac256 = keyed_hash(HashAlgorithm::Shake256(64), &key, b"My Tagged Application", &data).unwrap();
+        assert_eq!(
+            to_hex(&kmac256),
+            "20c570c31346f703c9ac36c61c03cb64c3970d0cfc787e9b79599d273a68d2f7f69d4cc3de9d104a351689f27cf6f5951f0103f33f4f24871024d9c27773a8dd"
+        );
+
+        assert!(keyed_hash(HashAlgorithm::Blake3, &key, b"", &data).is_ok());
+        assert!(keyed_hash(HashAlgorithm::Blake3, b"short", b"", &data).is_err());
+    }
+
+    #[test]
+    fn encodings() {
+        let digest = [0x3a, 0x98, 0xfb, 0xff];
+
+        for encoding in OutputEncoding::to_vec() {
+            assert_eq!(encoding.decode(&encoding.encode(&digest)).unwrap(), digest, "{}", encoding);
+        }
+
+        assert_eq!(OutputEncoding::HexUpper.encode(&digest), "3A98FBFF");
+        assert_eq!(OutputEncoding::Base64Url.encode(&digest), "Opj7_w");
+        assert_eq!(OutputEncoding::ByteArray.encode(&digest), "[0x3a, 0x98, 0xfb, 0xff]");
+
+        assert_eq!(InputEncoding::Hex.decode("0x3a98 fbff").unwrap(), digest);
+        assert_eq!(InputEncoding::Base64.decode("Opj7/w==").unwrap(), digest);
+        assert_eq!(InputEncoding::Base64.decode("Opj7_w").unwrap(), digest);
+        assert!(InputEncoding::Hex.decode("3a9").is_err());
+        assert!(InputEncoding::Base64.decode("not base64!").is_err());
+    }
+
+    #[test]
+    fn parses_gnu_and_bsd_lines() {
+        let digest = "a".repeat(64);
+        let text = format!(
+            "# comment\n{d}  dir/file.txt\n{d} *binary.bin\r\nSHA3-512 (bsd name.txt) = {d512}\n\\{d}  back\\\\slash\\nnewline\n",
+            d = digest,
+            d512 = "b".repeat(128)
+        );
+
+        let entries = parse_checksums(&text).unwrap();
+        assert_eq!(entries.len(), 4);
+        assert_eq!(entries[0].path, "dir/file.txt");
+        assert_eq!(entries[1].path, "binary.bin");
+        assert_eq!(entries[2].path, "bsd name.txt");
+        assert_eq!(entries[2].algorithm, Some(HashAlgorithm::Sha3_512));
+        assert_eq!(entries[3].path, "back\\slash\nnewline");
+        assert_eq!(format_line(&digest, &entries[3].path).trim_end(), text.lines().nth(4).unwrap());
+
+        assert!(parse_checksums("not a checksum").is_err());
+    }
+
+    #[test]
+    fn create_and_verify_checksums() {
+        let dir = std::env::temp_dir().join(format!("ncrypt_checksum_test_{}", std::process::id()));
+        fs::create_dir_all(dir.join("sub")).unwrap();
+        fs::write(dir.join("a.txt"), b"first").unwrap();
+        fs::write(dir.join("sub").join("b.txt"), vec![7u8; 3 * CHUNK_SIZE]).unwrap();
+
+        let cancel = CancelToken::new();
+        let files = collect_files(&dir).unwrap();
+        let checksums = create_checksums(HashAlgorithm::Sha3_256, &files, &dir, &mut |_| {}, &cancel).unwrap();
+        let sha256 = create_checksums(HashAlgorithm::Sha256, &files, &dir, &mut |_| {}, &cancel).unwrap();
+        assert!(checksums.contains("  sub/b.txt\n"));
+
+        fs::write(dir.join("a.txt"), b"changed").unwrap();
+        let mut entries = parse_checksums(&checksums).unwrap();
+        entries.push(ChecksumEntry { digest: "0".repeat(64), path: "missing.txt".to_string(), algorithm: None });
+
+        let results = verify_checksums(&entries, None, &dir, &mut |_| {}, &cancel).unwrap();
+        assert_eq!(results[0].status, CheckStatus::Mismatch);
+        assert_eq!(results[1].status, CheckStatus::Ok);
+        assert!(matches!(results[2].status, CheckStatus::Error(_)));
+
+        // Same digest length as SHA3-256, told apart by the name of the checksum file
+        let entries = parse_checksums(&sha256).unwrap();
+        let default = HashAlgorithm::from_checksum_file_name(&HashAlgorithm::Sha256.checksum_file_name());
+        let results = verify_checksums(&entries, default, &dir, &mut |_| {}, &cancel).unwrap();
+        assert_eq!(results[1].status, CheckStatus::Ok);
+
+        // A lossy name would point to another file
+        #[cfg(unix)]
+        {
+            use std::os::unix::ffi::OsStrExt;
+            let invalid = dir.join(std::ffi::OsStr::from_bytes(b"caf\xe9.txt"));
+            assert!(relative_name(&invalid, &dir).is_err());
+        }
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -0,0 +1,498 @@
+//! Background jobs run on a bounded pool of worker threads
+//!
+//! Tabs submit jobs through a [JobSender] and keep the returned [JobId]. Workers report what happens
+//! to a job over a channel that the UI drains with [JobManager::poll] every frame, so the list of jobs
+//! is only ever touched by the UI thread. A job that panics is reported as failed like any other error.
+//!
+//! Cancelling a job marks it cancelled right away, even if its worker is stuck in a step that can't be
+//! interrupted like Argon2. The worker stops sending events and the jobs check their [CancelToken] before
+//! handing out any result, so what a cancelled job produces is thrown away.
+
+use encryption::progress::{ CancelToken, Cancelled, Progress };
+use std::any::Any;
+use std::fmt;
+use std::panic::{ self, AssertUnwindSafe };
+use std::sync::atomic::{ AtomicU64, Ordering };
+use std::sync::mpsc::{ self, Receiver, Sender };
+use std::sync::{ Arc, Mutex };
+use std::thread;
+
+/// Jobs running at the same time, Argon2 can use gigabytes of memory per job
+pub const WORKERS: usize = 2;
+
+/// Identifies a job for its whole life, never reused
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct JobId(u64);
+
+impl fmt::Display for JobId {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "#{}", self.0)
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum JobState {
+    Queued,
+    Running,
+    Succeeded,
+    Failed,
+    Cancelled,
+}
+
+impl JobState {
+    pub fn is_finished(&self) -> bool {
+        matches!(self, JobState::Succeeded | JobState::Failed | JobState::Cancelled)
+    }
+}
+
+impl fmt::Display for JobState {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            JobState::Queued => "Queued",
+            JobState::Running => "Running",
+            JobState::Succeeded => "Done",
+            JobState::Failed => "Failed",
+            JobState::Cancelled => "Cancelled",
+        };
+        write!(f, "{}", name)
+    }
+}
+
+pub struct Job {
+    pub id: JobId,
+
+    /// What the job does, like `Encrypting report.pdf`
+    pub title: String,
+
+    /// The current step of a job with several, like `Verifying 2/5`
+    pub status: String,
+
+    pub state: JobState,
+
+    pub progress: Option<Progress>,
+
+    /// The summary of a job that succeeded, the error of a job that failed
+    pub message: String,
+
+    /// Only cancelled through [JobManager::cancel], which also marks the job
+    cancel: CancelToken,
+}
+
+/// What the UI learns about a job, sent by [JobSender::submit] and the workers
+enum JobEvent {
+    Queued { id: JobId, title: String, cancel: CancelToken },
+    Started(JobId),
+    Progress(JobId, Progress),
+    Status(JobId, String),
+    Finished { id: JobId, state: JobState, message: String },
+}
+
+type Work = Box<dyn FnOnce(&JobContext) -> Result<String, anyhow::Error> + Send>;
+
+struct Task {
+    id: JobId,
+    cancel: CancelToken,
+    work: Work,
+}
+
+type Wake = Arc<dyn Fn() + Send + Sync>;
+
+/// Given to a running job to report its progress and check for cancellation
+pub struct JobContext {
+    id: JobId,
+    cancel: CancelToken,
+    events: Sender<JobEvent>,
+    wake: Wake,
+}
+
+impl JobContext {
+    pub fn id(&self) -> JobId {
+        self.id
+    }
+
+    pub fn cancel_token(&self) -> &CancelToken {
+        &self.cancel
+    }
+
+    pub fn report(&self, progress: Progress) {
+        self.send(JobEvent::Progress(self.id, progress));
+    }
+
+    /// Show the current step, the progress is reset
+    pub fn set_status(&self, status: impl Into<String>) {
+        self.send(JobEvent::Status(self.id, status.into()));
+    }
+
+    /// A progress callback for the functions of the encryption crate
+    pub fn progress(&self) -> impl FnMut(Progress) + '_ {
+        move |progress| self.report(progress)
+    }
+
+    fn send(&self, event: JobEvent) {
+        // The job is already shown as cancelled
+        if self.cancel.is_cancelled() {
+            return;
+        }
+
+        // Only fails when the app is closing
+        let _ = self.events.send(event);
+        (self.wake)();
+    }
+}
+
+/// Submits jobs to the worker pool, every tab has a clone
+#[derive(Clone)]
+pub struct JobSender {
+    tasks: Sender<Task>,
+    events: Sender<JobEvent>,
+    next_id: Arc<AtomicU64>,
+    wake: Wake,
+}
+
+impl JobSender {
+    /// Queue a job, it runs when a worker is free
+    ///
+    /// The job returns a summary shown when it succeeds. An error that is [Cancelled] marks it as cancelled.
+    pub fn submit<F>(&self, title: impl Into<String>, work: F) -> JobId
+    where
+        F: FnOnce(&JobContext) -> Result<String, anyhow::Error> + Send + 'static,
+    {
+        let id = JobId(self.next_id.fetch_add(1, Ordering::Relaxed));
+        let cancel = CancelToken::new();
+
+        // Sent before the task so the UI always hears about the job before a worker starts it
+        let _ = self.events.send(JobEvent::Queued { id, title: title.into(), cancel: cancel.clone() });
+        let _ = self.tasks.send(Task { id, cancel, work: Box::new(work) });
+        (self.wake)();
+
+        id
+    }
+}
+
+pub struct JobManager {
+    jobs: Vec<Job>,
+    events: Receiver<JobEvent>,
+    sender: JobSender,
+
+    /// Cancelled since the last poll, reported as finished by the next one
+    cancelled: Vec<JobId>,
+}
+
+impl JobManager {
+    /// Start the worker pool
+    ///
+    /// ### Arguments
+    ///
+    /// - `workers` - How many jobs can run at the same time
+    /// - `wake` - Called from the workers when something changed, to repaint the UI
+    pub fn new(workers: usize, wake: impl Fn() + Send + Sync + 'static) -> Self {
+        let (task_sender, task_receiver) = mpsc::channel::<Task>();
+        let (event_sender, event_receiver) = mpsc::channel();
+        let wake: Wake = Arc::new(wake);
+        let task_receiver = Arc::new(Mutex::new(task_receiver));
+
+        for _ in 0..workers.max(1) {
+            let tasks = task_receiver.clone();
+            let events = event_sender.clone();
+            let wake = wake.clone();
+
+            thread::spawn(move || loop {
+                // Ends when the manager and every sender are dropped
+                let task = match tasks.lock() {
+                    Ok(tasks) => tasks.recv(),
+                    Err(_) => return,
+                };
+
+                match task {
+                    Ok(task) => run(task, events.clone(), wake.clone()),
+                    Err(_) => return,
+                }
+            });
+        }
+
+        let sender = JobSender {
+            tasks: task_sender,
+            events: event_sender,
+            next_id: Arc::new(AtomicU64::new(1)),
+            wake,
+        };
+
+        Self { jobs: Vec::new(), events: event_receiver, sender, cancelled: Vec::new() }
+    }
+
+    pub fn sender(&self) -> JobSender {
+        self.sender.clone()
+    }
+
+    /// Apply what the workers reported, returns the jobs that finished since the last call
+    pub fn poll(&mut self) -> Vec<JobId> {
+        let mut finished = std::mem::take(&mut self.cancelled);
+
+        while let Ok(event) = self.events.try_recv() {
+            match event {
+                JobEvent::Queued { id, title, cancel } => self.jobs.push(Job {
+                    id,
+                    title,
+                    status: String::new(),
+                    state: JobState::Queued,
+                    progress: None,
+                    message: String::new(),
+                    cancel,
+                }),
+                JobEvent::Started(id) => {
+                    if let Some(job) = self.get_active(id) {
+                        job.state = JobState::Running;
+                    }
+                }
+                JobEvent::Progress(id, progress) => {
+                    if let Some(job) = self.get_active(id) {
+                        job.progress = Some(progress);
+                    }
+                }
+                JobEvent::Status(id, status) => {
+                    if let Some(job) = self.get_active(id) {
+                        job.status = status;
+                        job.progress = None;
+                    }
+                }
+                JobEvent::Finished { id, state, message } => {
+                    if let Some(job) = self.get_active(id) {
+                        job.state = state;
+                        job.message = message;
+                        job.progress = None;
+                        finished.push(id);
+                    }
+                }
+            }
+        }
+
+        finished
+    }
+
+    /// Every job in the order they were submitted
+    pub fn jobs(&self) -> &[Job] {
+        &self.jobs
+    }
+
+    pub fn get(&self, id: JobId) -> Option<&Job> {
+        self.jobs.iter().find(|job| job.id == id)
+    }
+
+    /// A job that is not finished, events that arrive for a cancelled job are ignored
+    fn get_active(&mut self, id: JobId) -> Option<&mut Job> {
+        self.jobs.iter_mut().find(|job| job.id == id && !job.state.is_finished())
+    }
+
+    /// Stop a job and mark it cancelled right away
+    ///
+    /// A queued job never starts. A running one is left to its worker until it next checks the token,
+    /// nothing it reports after this is shown.
+    pub fn cancel(&mut self, id: JobId) {
+        if let Some(job) = self.get_active(id) {
+            job.cancel.cancel();
+            job.state = JobState::Cancelled;
+            job.progress = None;
+            self.cancelled.push(id);
+        }
+    }
+
+    /// Cancel every job that is queued or running
+    pub fn cancel_all(&mut self) {
+        let active: Vec<JobId> = self.jobs.iter().filter(|job| !job.state.is_finished()).map(|job| job.id).collect();
+
+        for id in active {
+            self.cancel(id);
+        }
+    }
+
+    /// Jobs that are queued or running
+    pub fn active(&self) -> usize {
+        self.jobs.iter().filter(|job| !job.state.is_finished()).count()
+    }
+
+    /// Remove the finished jobs from the list
+    pub fn clear_finished(&mut self) {
+        self.jobs.retain(|job| !job.state.is_finished());
+    }
+}
+
+/// Run a task on the current worker and report how it ended
+fn run(task: Task, events: Sender<JobEvent>, wake: Wake) {
+    let Task { id, cancel, work } = task;
+
+    // Cancelled through the manager, which already shows it
+    if cancel.is_cancelled() {
+        return;
+    }
+
+    let _ = events.send(JobEvent::Started(id));
+    wake();
+
+    let ctx = JobContext { id, cancel, events: events.clone(), wake: wake.clone() };
+
+    let (state, message) = match panic::catch_unwind(AssertUnwindSafe(|| work(&ctx))) {
+        Ok(Ok(message)) => (JobState::Succeeded, message),
+        Ok(Err(e)) if e.is::<Cancelled>() => (JobState::Cancelled, String::new()),
+        Ok(Err(e)) => (JobState::Failed, format!("{:#}", e)),
+        Err(panic) => (JobState::Failed, format!("The job crashed: {}", panic_message(&panic))),
+    };
+
+    // Nothing is sent after a cancel
+    if ctx.cancel.is_cancelled() {
+        return;
+    }
+
+    let _ = events.send(JobEvent::Finished { id, state, message });
+    wake();
+}
+
+fn panic_message(panic: &Box<dyn Any + Send>) -> &str {
+    panic
+        .downcast_ref::<&str>()
+        .copied()
+        .or_else(|| panic.downcast_ref::<String>().map(|s| s.as_str()))
+        .unwrap_or("unknown error")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use encryption::{ file, prelude::* };
+    use std::sync::mpsc::RecvTimeoutError;
+    use std::time::Duration;
+
+    /// Poll until every job finished, `wake` signals the channel so this does not spin
+    fn wait(manager: &mut JobManager, woken: &Receiver<()>) {
+        // Poll before checking, the jobs submitted since the last poll are not counted yet
+        manager.poll();
+
+        while manager.active() > 0 || manager.jobs().is_empty() {
+            if let Err(RecvTimeoutError::Disconnected) = woken.recv_timeout(Duration::from_millis(100)) {
+                break;
+            }
+            manager.poll();
+        }
+    }
+
+    fn manager(workers: usize) -> (JobManager, Receiver<()>) {
+        let (wake_sender, woken) = mpsc::channel();
+        let wake_sender = Mutex::new(wake_sender);
+        let manager = JobManager::new(workers, move || {
+            let _ = wake_sender.lock().unwrap().send(());
+        });
+        (manager, woken)
+    }
+
+    #[test]
+    fn reports_every_outcome() {
+        let (mut manager, woken) = manager(WORKERS);
+        let sender = manager.sender();
+
+        let ok = sender.submit("ok", |ctx| {
+            ctx.set_status("step 1");
+            ctx.report(Progress::Payload { processed: 1, total: 2 });
+            Ok("done".to_string())
+        });
+        let failed = sender.submit("failed", |_| Err(anyhow::anyhow!("no such file")));
+        let cancelled = sender.submit("cancelled", |_| Err(Cancelled.into()));
+        let crashed = sender.submit("crashed", |_| panic!("boom"));
+
+        wait(&mut manager, &woken);
+
+        let job = manager.get(ok).unwrap();
+        assert_eq!((job.state, job.message.as_str(), job.status.as_str()), (JobState::Succeeded, "done", "step 1"));
+        assert_eq!(manager.get(failed).unwrap().state, JobState::Failed);
+        assert_eq!(manager.get(failed).unwrap().message, "no such file");
+        assert_eq!(manager.get(cancelled).unwrap().state, JobState::Cancelled);
+        assert_eq!(manager.get(crashed).unwrap().state, JobState::Failed);
+        assert!(manager.get(crashed).unwrap().message.contains("boom"));
+
+        // The worker that caught the panic still runs jobs
+        let after = sender.submit("after", |_| Ok(String::new()));
+        wait(&mut manager, &woken);
+        assert_eq!(manager.get(after).unwrap().state, JobState::Succeeded);
+
+        manager.clear_finished();
+        assert!(manager.jobs().is_empty());
+    }
+
+    #[test]
+    fn bounded_pool_runs_in_order() {
+        let (mut manager, woken) = manager(1);
+        let sender = manager.sender();
+        let (release, blocked) = mpsc::channel::<()>();
+
+        let first = sender.submit("first", move |_| {
+            blocked.recv().ok();
+            Ok(String::new())
+        });
+        let second = sender.submit("second", |_| Ok(String::new()));
+
+        // The only worker is busy, the second job waits in the queue and can be cancelled there
+        manager.poll();
+        manager.cancel(second);
+        assert_eq!(manager.get(second).unwrap().state, JobState::Cancelled);
+        assert_eq!(manager.poll(), vec![second]);
+        assert!(first < second);
+
+        release.send(()).unwrap();
+        wait(&mut manager, &woken);
+
+        assert_eq!(manager.get(first).unwrap().state, JobState::Succeeded);
+        assert_eq!(manager.get(second).unwrap().state, JobState::Cancelled);
+    }
+
+    #[test]
+    fn cancel_while_deriving_the_key_writes_nothing() {
+        let (mut manager, woken) = manager(1);
+        let sender = manager.sender();
+        let output = std::env::temp_dir().join(format!("ncrypt_jobs_cancel_{}.ncrypt", std::process::id()));
+        let (deriving, derived) = mpsc::channel::<()>();
+        let (release, blocked) = mpsc::channel::<()>();
+
+        let path = output.clone();
+        let id = sender.submit("Encrypting", move |ctx| {
+            let credentials = Credentials::new("user", "x7$Kq!9vLm#2Pz", "x7$Kq!9vLm#2Pz");
+            let mut report = ctx.progress();
+
+            // Held at the start of the key derivation until the test cancelled the job
+            let mut progress = |progress| {
+                report(progress);
+                if progress == Progress::DerivingKey {
+                    deriving.send(()).unwrap();
+                    blocked.recv().ok();
+                }
+            };
+
+            let data = encrypt_data_with(
+                Argon2Params::very_fast(),
+                b"secret".to_vec(),
+                credentials,
+                &PasswordPolicy::none(),
+                &mut progress,
+                ctx.cancel_token(),
+            )?;
+            file::write_atomic(&path, &data, false, ctx.cancel_token())?;
+
+            Ok("encrypted".to_string())
+        });
+
+        derived.recv().unwrap();
+        manager.poll();
+        assert_eq!(manager.get(id).unwrap().progress, Some(Progress::DerivingKey));
+
+        manager.cancel(id);
+        assert_eq!(manager.get(id).unwrap().state, JobState::Cancelled);
+        assert_eq!(manager.get(id).unwrap().progress, None);
+
+        // Queued behind the cancelled job on the only worker, done once its worker is free again
+        release.send(()).unwrap();
+        let after = sender.submit("after", |_| Ok(String::new()));
+        wait(&mut manager, &woken);
+        assert_eq!(manager.get(after).unwrap().state, JobState::Succeeded);
+
+        let job = manager.get(id).unwrap();
+        assert_eq!((job.state, job.message.as_str()), (JobState::Cancelled, ""));
+        assert!(!output.exists());
+    }
+}
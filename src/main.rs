@@ -2,17 +2,29 @@
 
 pub mod gui;
 pub mod app;
+pub mod hashing;
+pub mod jobs;
+pub mod settings;
+pub mod preview;
+pub mod edit;
 
 use eframe::egui::ViewportBuilder;
 use app::NCryptApp;
 
 fn main() -> Result<(), eframe::Error> {
+    // Keep secrets in memory from being written to disk on a crash
+    if let Err(e) = encryption::memlock::disable_core_dumps() {
+        eprintln!("Warning: {:?}", e);
+    }
+
     let options = eframe::NativeOptions {
         viewport: ViewportBuilder::default()
             .with_decorations(false) // Hide the OS-specific "chrome" around the window
             .with_inner_size([860.0, 500.0])
             .with_min_inner_size([860.0, 500.0])
+            .with_drag_and_drop(true) // Files dropped on the window are added to the queue
             .with_transparent(true), // To have rounded corners we need transparency
+        persist_window: false, // Restored from the settings, only if the user wants it
         ..Default::default()
     };
     eframe::run_native(
@@ -0,0 +1,224 @@
+//! Decrypted content shown inside the app instead of being written to disk
+//!
+//! The decrypted bytes, the decoded text and the decoded pixels are kept in zeroizing buffers
+//! and wiped when the [Preview] is dropped.
+
+use chardetng::EncodingDetector;
+use encoding_rs::{ Encoding, UTF_8 };
+use encryption::prelude::{ SecretBytes, SecretString };
+use encryption::zeroize::Zeroize;
+use image::DynamicImage;
+use std::borrow::Cow;
+use std::fmt::Write;
+
+/// Bytes shown on a row of the hex view
+pub const HEX_ROW_LEN: usize = 16;
+
+/// Share of control characters above which decoded text is treated as binary
+const MAX_CONTROL_RATIO: f64 = 0.05;
+
+pub enum PreviewContent {
+    Text {
+        text: SecretString,
+
+        /// Name of the detected encoding, like `UTF-8` or `windows-1252`
+        encoding: &'static str,
+    },
+
+    /// RGBA pixels, unmultiplied
+    Image {
+        width: u32,
+        height: u32,
+        pixels: SecretBytes,
+    },
+
+    /// Neither text nor a supported image, only the hex view is available
+    Binary,
+}
+
+/// A decrypted file and what it was recognized as
+pub struct Preview {
+    pub name: String,
+    pub data: SecretBytes,
+    pub content: PreviewContent,
+}
+
+impl Preview {
+    pub fn new(name: impl Into<String>, data: SecretBytes) -> Self {
+        let content = detect(data.expose());
+        Self { name: name.into(), data, content }
+    }
+
+    /// Rows of the hex view
+    pub fn hex_rows(&self) -> usize {
+        self.data.len().div_ceil(HEX_ROW_LEN)
+    }
+}
+
+/// Recognize images by their signature, then text in any encoding chardetng knows
+pub fn detect(data: &[u8]) -> PreviewContent {
+    if let Some(content) = decode_image(data) {
+        return content;
+    }
+
+    match decode_text(data) {
+        Some((text, encoding)) => PreviewContent::Text { text, encoding },
+        None => PreviewContent::Binary,
+    }
+}
+
+fn decode_image(data: &[u8]) -> Option<PreviewContent> {
+    image::guess_format(data).ok()?;
+    let image = image::load_from_memory(data).ok()?;
+
+    let rgba = image.to_rgba8();
+    let (width, height) = rgba.dimensions();
+    let pixels = SecretBytes::from(rgba.into_raw());
+    wipe_image(image);
+
+    Some(PreviewContent::Image { width, height, pixels })
+}
+
+/// Zeroize the pixels of a decoded image
+fn wipe_image(image: DynamicImage) {
+    match image {
+        DynamicImage::ImageLuma8(image) => image.into_raw().zeroize(),
+        DynamicImage::ImageLumaA8(image) => image.into_raw().zeroize(),
+        DynamicImage::ImageRgb8(image) => image.into_raw().zeroize(),
+        DynamicImage::ImageRgba8(image) => image.into_raw().zeroize(),
+        DynamicImage::ImageLuma16(image) => image.into_raw().zeroize(),
+        DynamicImage::ImageLumaA16(image) => image.into_raw().zeroize(),
+        DynamicImage::ImageRgb16(image) => image.into_raw().zeroize(),
+        DynamicImage::ImageRgba16(image) => image.into_raw().zeroize(),
+        DynamicImage::ImageRgb32F(image) => image.into_raw().zeroize(),
+        DynamicImage::ImageRgba32F(image) => image.into_raw().zeroize(),
+        _ => {}
+    }
+}
+
+/// The text and the name of its encoding, `None` if the data does not look like text
+///
+/// A byte order mark wins, then UTF-8 if the data is valid UTF-8, then the guess of chardetng.
+pub fn decode_text(data: &[u8]) -> Option<(SecretString, &'static str)> {
+    let encoding = match Encoding::for_bom(data) {
+        Some((encoding, _)) => encoding,
+        None if std::str::from_utf8(data).is_ok() => UTF_8,
+        None => {
+            let mut detector = EncodingDetector::new();
+            detector.feed(data, true);
+            detector.guess(None, true)
+        }
+    };
+
+    let (decoded, _, had_errors) = encoding.decode(data);
+
+    let text = match decoded {
+        Cow::Borrowed(text) => SecretString::from(text),
+        Cow::Owned(text) => SecretString::from(text),
+    };
+
+    if had_errors || !looks_like_text(text.expose()) {
+        return None;
+    }
+
+    Some((text, encoding.name()))
+}
+
+/// Few control characters other than line breaks and tabs
+fn looks_like_text(text: &str) -> bool {
+    let mut chars = 0usize;
+    let mut control = 0usize;
+
+    for c in text.chars() {
+        chars += 1;
+
+        if c == '\0' {
+            return false;
+        }
+
+        if c.is_control() && !matches!(c, '\n' | '\r' | '\t' | '\u{c}') {
+            control += 1;
+        }
+    }
+
+    chars == 0 || (control as f64 / chars as f64) <= MAX_CONTROL_RATIO
+}
+
+/// A row of the hex view, like `00000010  3a 98 ...  |:.|`
+pub fn hex_row(data: &[u8], row: usize) -> String {
+    let start = row * HEX_ROW_LEN;
+    let bytes = &data[start.min(data.len())..(start + HEX_ROW_LEN).min(data.len())];
+
+    let mut line = format!("{:08x}  ", start);
+
+    for i in 0..HEX_ROW_LEN {
+        match bytes.get(i) {
+            Some(byte) => write!(line, "{:02x} ", byte).unwrap(),
+            None => line.push_str("   "),
+        }
+
+        if i == HEX_ROW_LEN / 2 - 1 {
+            line.push(' ');
+        }
+    }
+
+    line.push_str(" |");
+    line.extend(bytes.iter().map(|&b| if b.is_ascii_graphic() || b == b' ' { b as char } else { '.' }));
+    line.push('|');
+
+    line
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn detects_text_encodings() {
+        let (text, encoding) = decode_text("héllo wörld\n".as_bytes()).unwrap();
+        assert_eq!((text.expose(), encoding), ("héllo wörld\n", "UTF-8"));
+
+        // UTF-16 with a byte order mark
+        let utf16: Vec<u8> = [0xff, 0xfe].into_iter().chain("hi".encode_utf16().flat_map(|u| u.to_le_bytes())).collect();
+        let (text, encoding) = decode_text(&utf16).unwrap();
+        assert_eq!((text.expose(), encoding), ("hi", "UTF-16LE"));
+
+        // Latin-1 is not valid UTF-8
+        let latin1 = b"Le caf\xe9 est tr\xe8s bon, merci beaucoup pour le d\xe9jeuner";
+        let (text, _) = decode_text(latin1).unwrap();
+        assert!(text.expose().starts_with("Le café"));
+
+        assert!(decode_text(&[0, 1, 2, 3, 0xff, 0xfe, 0x00, 0x10]).is_none());
+    }
+
+    #[test]
+    fn detects_images() {
+        let mut png = Vec::new();
+        let image = image::RgbaImage::from_pixel(2, 3, image::Rgba([255, 0, 0, 255]));
+        image.write_to(&mut std::io::Cursor::new(&mut png), image::ImageFormat::Png).unwrap();
+
+        match detect(&png) {
+            PreviewContent::Image { width, height, pixels } => {
+                assert_eq!((width, height), (2, 3));
+                assert_eq!(&pixels.expose()[..4], &[255, 0, 0, 255]);
+            }
+            _ => panic!("not detected as an image"),
+        }
+
+        assert!(matches!(detect(&[0x89, 0, 0, 7, 1]), PreviewContent::Binary));
+    }
+
+    #[test]
+    fn hex_rows() {
+        let data: Vec<u8> = (0x40..0x52).collect();
+        let preview = Preview::new("data", SecretBytes::from(data.clone()));
+        assert_eq!(preview.hex_rows(), 2);
+
+        assert_eq!(
+            hex_row(&data, 0),
+            "00000000  40 41 42 43 44 45 46 47  48 49 4a 4b 4c 4d 4e 4f  |@ABCDEFGHIJKLMNO|"
+        );
+        assert_eq!(hex_row(&data, 1), format!("00000010  50 51{}|PQ|", " ".repeat(45)));
+        assert_eq!(hex_row(&data, 0).len(), hex_row(&data, 1).len() + HEX_ROW_LEN - 2);
+    }
+}
@@ -0,0 +1,219 @@
+//! Settings kept between runs in the eframe storage
+//!
+//! Only preferences and, if the user opts in, the paths of recent files are stored.
+//! Credentials, keys and the content of files are never part of the settings.
+
+use eframe::egui::Color32;
+use encryption::Argon2Params;
+use serde::{ Deserialize, Serialize };
+use std::fmt;
+use std::path::PathBuf;
+use crate::gui::ui::file_encryption::{ Overwrite, M_COST_RANGE, P_COST_RANGE, T_COST_RANGE };
+
+/// Key of the settings in the eframe storage
+const STORAGE_KEY: &str = "settings";
+
+/// Most recent files remembered
+pub const MAX_RECENT_FILES: usize = 10;
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub enum Theme {
+    #[default]
+    Dark,
+    Black,
+}
+
+impl fmt::Display for Theme {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            Theme::Dark => "Dark",
+            Theme::Black => "Black",
+        };
+        write!(f, "{}", name)
+    }
+}
+
+impl Theme {
+    pub fn to_vec() -> Vec<Theme> {
+        vec![Theme::Dark, Theme::Black]
+    }
+
+    /// Fill of the panels and windows
+    pub fn background(&self) -> Color32 {
+        match self {
+            Theme::Dark => Color32::from_hex("#212529").unwrap(),
+            Theme::Black => Color32::from_hex("#0b0c0d").unwrap(),
+        }
+    }
+}
+
+/// Position and size of the window in points
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+pub struct WindowGeometry {
+    pub position: [f32; 2],
+    pub size: [f32; 2],
+}
+
+/// Paths of the files opened last, newest first, empty unless enabled
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub struct RecentFiles {
+    enabled: bool,
+    paths: Vec<PathBuf>,
+}
+
+impl RecentFiles {
+    pub fn is_enabled(&self) -> bool {
+        self.enabled
+    }
+
+    /// Disabling forgets the files
+    pub fn set_enabled(&mut self, enabled: bool) {
+        self.enabled = enabled;
+
+        if !enabled {
+            self.paths.clear();
+        }
+    }
+
+    pub fn paths(&self) -> &[PathBuf] {
+        &self.paths
+    }
+
+    /// Move the paths to the top of the list, does nothing unless enabled
+    pub fn add(&mut self, paths: &[PathBuf]) {
+        if !self.enabled {
+            return;
+        }
+
+        for path in paths {
+            self.paths.retain(|recent| recent != path);
+            self.paths.insert(0, path.clone());
+        }
+
+        self.paths.truncate(MAX_RECENT_FILES);
+    }
+
+    pub fn clear(&mut self) {
+        self.paths.clear();
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Settings {
+    /// Argon2 parameters used to encrypt, the preset is found from them
+    pub argon_params: Argon2Params,
+
+    /// Where to write the output, `None` for the folder of the input file
+    pub output_dir: Option<PathBuf>,
+
+    /// What to do when the output of a file already exists
+    pub overwrite: Overwrite,
+
+    pub theme: Theme,
+
+    /// Seconds before copied secrets are cleared from the clipboard, 0 to keep them
+    pub clipboard_timeout: u64,
+
+    /// Minutes without input before the secrets are wiped, 0 to never lock
+    pub lock_timeout: u64,
+
+    /// Wipe the secrets when the window is minimized or loses focus
+    pub wipe_on_minimize: bool,
+
+    /// Restore the position and size of the window on start
+    pub remember_window: bool,
+
+    pub window: Option<WindowGeometry>,
+
+    pub recent_files: RecentFiles,
+}
+
+impl Default for Settings {
+    fn default() -> Self {
+        Self {
+            argon_params: Argon2Params::fast(),
+            output_dir: None,
+            overwrite: Overwrite::Rename,
+            theme: Theme::Dark,
+            clipboard_timeout: 30,
+            lock_timeout: 10,
+            wipe_on_minimize: false,
+            remember_window: true,
+            window: None,
+            recent_files: RecentFiles::default(),
+        }
+    }
+}
+
+impl Settings {
+    /// The saved settings, the defaults on the first run or if they can't be read
+    ///
+    /// Argon2 parameters the sliders could not have set are replaced by [Argon2Params::fast]
+    pub fn load(storage: Option<&dyn eframe::Storage>) -> Self {
+        let mut settings: Self = storage
+            .and_then(|storage| eframe::get_value(storage, STORAGE_KEY))
+            .unwrap_or_default();
+
+        if !valid_argon_params(&settings.argon_params) {
+            settings.argon_params = Argon2Params::fast();
+        }
+
+        settings
+    }
+
+    pub fn save(&self, storage: &mut dyn eframe::Storage) {
+        eframe::set_value(storage, STORAGE_KEY, self);
+    }
+}
+
+fn valid_argon_params(params: &Argon2Params) -> bool {
+    M_COST_RANGE.contains(&params.m_cost)
+        && T_COST_RANGE.contains(&params.t_cost)
+        && P_COST_RANGE.contains(&params.p_cost)
+        && params.hash_length == Argon2Params::fast().hash_length
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn recent_files_are_opt_in() {
+        let mut recent = RecentFiles::default();
+        recent.add(&[PathBuf::from("a")]);
+        assert!(recent.paths().is_empty());
+
+        recent.set_enabled(true);
+        let paths: Vec<PathBuf> = (0..12).map(|i| PathBuf::from(i.to_string())).collect();
+        recent.add(&paths);
+        recent.add(&[PathBuf::from("5")]);
+
+        // Newest first, no duplicates, bounded
+        assert_eq!(recent.paths().len(), MAX_RECENT_FILES);
+        assert_eq!(recent.paths()[0], PathBuf::from("5"));
+        assert_eq!(recent.paths()[1], PathBuf::from("11"));
+        assert_eq!(recent.paths().iter().filter(|p| p.as_path() == std::path::Path::new("5")).count(), 1);
+
+        recent.set_enabled(false);
+        assert!(recent.paths().is_empty());
+    }
+
+    #[test]
+    fn missing_fields_use_defaults() {
+        let settings: Settings = ron::from_str("(theme: Black, lock_timeout: 0)").unwrap();
+
+        assert_eq!(settings.theme, Theme::Black);
+        assert_eq!(settings.lock_timeout, 0);
+        assert_eq!(settings.argon_params, Argon2Params::fast());
+        assert!(!settings.recent_files.is_enabled());
+    }
+
+    #[test]
+    fn argon_params_are_validated() {
+        assert!(valid_argon_params(&Argon2Params::fast()));
+        assert!(!valid_argon_params(&Argon2Params::new(u32::MAX, 3, 4, 64)));
+        assert!(!valid_argon_params(&Argon2Params::new(65_536, 0, 4, 64)));
+        assert!(!valid_argon_params(&Argon2Params::new(65_536, 3, 4, 4)));
+    }
+}